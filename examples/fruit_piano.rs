@@ -0,0 +1,79 @@
+//! Fruit piano: connect pieces of fruit to electrodes 0 to 7 and play notes
+//! by touching them.
+//!
+//! Usage: `cargo run --example fruit_piano -- [--beep] [BUS]`
+//!
+//! Electrodes are configured with `presets::high_impedance_electrodes`,
+//! which also makes the chip debounce touches and releases. Touch events are
+//! read on a separate thread and sent over a channel to the player, which
+//! prints note names, optionally ringing the terminal bell on every press.
+
+use std::io::Write;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use adafruit_mpr121::{presets, Mpr121, Mpr121Error, TouchEvent};
+
+const NOTES: [&str; 8] = ["C4", "D4", "E4", "F4", "G4", "A4", "B4", "C5"];
+
+/// Reads touch events and sends them to `tx` until the receiver is gone
+fn read_events(mut mpr121: Mpr121, tx: mpsc::Sender<TouchEvent>) -> Result<(), Mpr121Error> {
+    loop {
+        for event in mpr121.poll_events()? {
+            if tx.send(event).is_err() {
+                return Ok(());
+            }
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Calls `key` with electrode and whether it was pressed for every event
+fn play<F: FnMut(u8, bool)>(rx: mpsc::Receiver<TouchEvent>, mut key: F) {
+    for event in rx {
+        match event {
+            TouchEvent::Touched { pin } => key(pin, true),
+            TouchEvent::Released { pin } => key(pin, false),
+            TouchEvent::Repeat { .. } => {}
+        }
+    }
+}
+
+fn main() {
+    let mut beep = false;
+    let mut bus = 1;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--beep" => beep = true,
+            other => bus = other.parse().expect("Usage: fruit_piano [--beep] [BUS]"),
+        }
+    }
+
+    let mut mpr121 = Mpr121::new_default(bus).expect("Failed to open sensor");
+    mpr121.reset().expect("Failed to reset sensor");
+    mpr121
+        .import_config_registers(&presets::high_impedance_electrodes())
+        .expect("Failed to configure sensor");
+    // Settle baselines before listening, so the first events are real
+    if let Some(until) = mpr121.settling_until() {
+        std::thread::sleep(until.saturating_duration_since(std::time::Instant::now()));
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let reader = std::thread::spawn(move || read_events(mpr121, tx));
+    println!("Touch the fruit, Ctrl+C to quit");
+    play(rx, |pin, pressed| {
+        let note = NOTES.get(pin as usize).copied().unwrap_or("--");
+        if pressed {
+            print!("{}{} ", if beep { "\x07" } else { "" }, note);
+        } else {
+            print!("({}) ", note);
+        }
+        let _ = std::io::stdout().flush();
+    });
+    match reader.join() {
+        Ok(Err(e)) => eprintln!("Reading sensor failed: {}", e),
+        Ok(Ok(())) => {}
+        Err(_) => eprintln!("Reader thread panicked"),
+    }
+}
@@ -0,0 +1,33 @@
+//! Screen saver trigger: prints a message after a period without any touch
+//! and again on the next touch, which only wakes up the "screen".
+//!
+//! Usage: `cargo run --example screen_saver -- [TIMEOUT_SECS] [BUS]`
+
+use std::time::{Duration, Instant};
+
+use adafruit_mpr121::{Activity, IdleDetector, Mpr121, TouchEvent};
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let usage = "Usage: screen_saver [TIMEOUT_SECS] [BUS]";
+    let timeout = args.next().map_or(60, |a| a.parse().expect(usage));
+    let bus = args.next().map_or(1, |a| a.parse().expect(usage));
+
+    let mut mpr121 = Mpr121::new_default(bus).expect("Failed to open sensor");
+    mpr121.reset().expect("Failed to reset sensor");
+    let mut detector = IdleDetector::new(Duration::from_secs(timeout)).swallow_first_touch(true);
+
+    println!("Screen on, goes dark after {} s without touch", timeout);
+    loop {
+        let events = mpr121.poll_events().expect("Failed to read sensor");
+        for activity in detector.process(&events, Instant::now()) {
+            match activity {
+                Activity::Idle => println!("Idle, screen off"),
+                Activity::Active => println!("Touched, screen on"),
+                Activity::Event(TouchEvent::Touched { pin }) => println!("Button {}", pin),
+                Activity::Event(_) => {}
+            }
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
@@ -0,0 +1,9 @@
+#![no_main]
+use adafruit_mpr121::ConfigImage;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(image) = ConfigImage::from_bytes(data) {
+        assert_eq!(ConfigImage::from_bytes(&image.to_bytes()).unwrap(), image);
+    }
+});
@@ -0,0 +1,13 @@
+#![no_main]
+use adafruit_mpr121::EventReplayer;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(replayer) = EventReplayer::new(data) {
+        for event in replayer {
+            if event.is_err() {
+                break;
+            }
+        }
+    }
+});
@@ -0,0 +1,19 @@
+#![no_main]
+use adafruit_mpr121::{GpioState, Mpr121TouchStatus};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 7 {
+        return;
+    }
+    let (regs, electrodes) = data.split_at(7);
+    let status = Mpr121TouchStatus::from_low_byte(regs[0]);
+    let _ = status.to_string();
+    let _ = status.ascii_art_grid(regs[1] as usize);
+    let (rows, cols) = electrodes.split_at(electrodes.len() / 2);
+    let _ = status.decode_matrix(rows, cols);
+    let map: Vec<(u8, u8)> = electrodes.iter().map(|e| (*e, *e)).collect();
+    let _ = status.decode(&map);
+    let gpio = GpioState::from_registers(regs[2], regs[3], regs[4], regs[5], regs[6]);
+    let _ = gpio.to_string();
+});
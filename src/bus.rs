@@ -0,0 +1,603 @@
+//! Recording of I2C transactions to a file and replaying them later, so that
+//! code processing sensor data can be tested against captured sessions
+//! without hardware:
+//!
+//! ```rust,no_run
+//! use adafruit_mpr121::bus::{I2cRecorder, I2cReplay};
+//! use adafruit_mpr121::Mpr121;
+//! use i2cdev::linux::LinuxI2CDevice;
+//!
+//! // Capture a session with real hardware
+//! let dev = LinuxI2CDevice::new("/dev/i2c-1", 0x5A).unwrap();
+//! let log = std::fs::File::create("session.txt").unwrap();
+//! let mut mpr121 = Mpr121::from_device(I2cRecorder::new(dev, log).unwrap(), "/dev/i2c-1", 0x5A);
+//! mpr121.reset().unwrap();
+//! let events = mpr121.poll_events().unwrap();
+//! drop(mpr121);
+//!
+//! // Later, replay it in a test
+//! let log = std::io::BufReader::new(std::fs::File::open("session.txt").unwrap());
+//! let mut mpr121 = Mpr121::from_device(I2cReplay::new(log).unwrap(), "/dev/i2c-1", 0x5A);
+//! mpr121.reset().unwrap();
+//! assert_eq!(mpr121.poll_events().unwrap(), events);
+//! ```
+//!
+//! Each line of a recording holds one transaction: operation, its arguments
+//! and either `=` followed by returned bytes or `!` followed by the error.
+//! Bytes are hexadecimal, words little endian. Combined transfers of a write
+//! followed by a read are recorded as `write_read` with written bytes and
+//! read length as arguments, other combined transfers are not supported.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::io::{BufRead, Write};
+
+use i2cdev::core::{I2CDevice, I2CMessage, I2CTransfer};
+use i2cdev::linux::LinuxI2CError;
+
+use crate::ReplayError;
+
+/// I2C device wrapper writing every transaction of the wrapped device with
+/// its result to `out`
+pub struct I2cRecorder<D, W: Write> {
+    inner: D,
+    out: W,
+}
+
+/// I2C device answering transactions from a recording made by
+/// `I2cRecorder`. Transactions have to come in the recorded order, any other
+/// transaction fails with `std::io::ErrorKind::InvalidData`, as does any
+/// transaction after the recording is exhausted. Recorded failures keep their
+/// OS error code.
+#[derive(Debug, Clone)]
+pub struct I2cReplay {
+    transactions: VecDeque<Transaction>,
+}
+
+/// Message of a combined transfer of `I2cRecorder` and `I2cReplay`
+#[derive(Debug, PartialEq, Eq)]
+pub enum I2cMessage<'a> {
+    /// Read data from device
+    Read(&'a mut [u8]),
+    /// Write data to device
+    Write(&'a [u8]),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Transaction {
+    op: String,
+    args: Vec<u8>,
+    result: Result<Vec<u8>, String>,
+}
+
+const MAGIC: &str = "mpr121-i2c";
+const VERSION: u32 = 1;
+
+impl<D, W> I2cRecorder<D, W>
+where
+    D: I2CDevice,
+    D::Error: From<std::io::Error>,
+    W: Write,
+{
+    /// Writes header and starts recording transactions of `inner`
+    pub fn new(inner: D, mut out: W) -> std::io::Result<Self> {
+        writeln!(out, "{} {}", MAGIC, VERSION)?;
+        Ok(Self { inner, out })
+    }
+
+    /// Flushes recording and returns wrapped device and writer
+    pub fn into_inner(mut self) -> std::io::Result<(D, W)> {
+        self.out.flush()?;
+        Ok((self.inner, self.out))
+    }
+
+    /// Writes a transaction, failure to write is reported only when the
+    /// transaction itself succeeded
+    fn record<T, F>(
+        &mut self,
+        op: &str,
+        args: &[u8],
+        result: Result<T, D::Error>,
+        bytes: F,
+    ) -> Result<T, D::Error>
+    where
+        F: FnOnce(&T) -> Vec<u8>,
+    {
+        let mut line = String::from(op);
+        for a in args {
+            let _ = write!(line, " {:02x}", a);
+        }
+        match &result {
+            Ok(value) => {
+                line.push_str(" =");
+                for b in bytes(value) {
+                    let _ = write!(line, " {:02x}", b);
+                }
+            }
+            Err(e) => {
+                let _ = write!(line, " ! {}", e.to_string().replace('\n', " "));
+            }
+        }
+        match writeln!(self.out, "{}", line) {
+            Err(e) if result.is_ok() => Err(e.into()),
+            _ => result,
+        }
+    }
+}
+
+impl<D, W> I2CDevice for I2cRecorder<D, W>
+where
+    D: I2CDevice,
+    D::Error: From<std::io::Error>,
+    W: Write,
+{
+    type Error = D::Error;
+
+    fn read(&mut self, data: &mut [u8]) -> Result<(), Self::Error> {
+        let result = self.inner.read(data);
+        let read = data.to_vec();
+        self.record("read", &[data.len() as u8], result, |_| read)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        let result = self.inner.write(data);
+        self.record("write", data, result, |_| Vec::new())
+    }
+
+    fn smbus_write_quick(&mut self, bit: bool) -> Result<(), Self::Error> {
+        let result = self.inner.smbus_write_quick(bit);
+        self.record("write_quick", &[bit as u8], result, |_| Vec::new())
+    }
+
+    fn smbus_read_byte(&mut self) -> Result<u8, Self::Error> {
+        let result = self.inner.smbus_read_byte();
+        self.record("read_byte", &[], result, |v| vec![*v])
+    }
+
+    fn smbus_write_byte(&mut self, value: u8) -> Result<(), Self::Error> {
+        let result = self.inner.smbus_write_byte(value);
+        self.record("write_byte", &[value], result, |_| Vec::new())
+    }
+
+    fn smbus_read_byte_data(&mut self, register: u8) -> Result<u8, Self::Error> {
+        let result = self.inner.smbus_read_byte_data(register);
+        self.record("read_byte_data", &[register], result, |v| vec![*v])
+    }
+
+    fn smbus_write_byte_data(&mut self, register: u8, value: u8) -> Result<(), Self::Error> {
+        let result = self.inner.smbus_write_byte_data(register, value);
+        self.record("write_byte_data", &[register, value], result, |_| {
+            Vec::new()
+        })
+    }
+
+    fn smbus_read_word_data(&mut self, register: u8) -> Result<u16, Self::Error> {
+        let result = self.inner.smbus_read_word_data(register);
+        self.record("read_word_data", &[register], result, |v| {
+            v.to_le_bytes().to_vec()
+        })
+    }
+
+    fn smbus_write_word_data(&mut self, register: u8, value: u16) -> Result<(), Self::Error> {
+        let result = self.inner.smbus_write_word_data(register, value);
+        let [lo, hi] = value.to_le_bytes();
+        self.record("write_word_data", &[register, lo, hi], result, |_| {
+            Vec::new()
+        })
+    }
+
+    fn smbus_process_word(&mut self, register: u8, value: u16) -> Result<u16, Self::Error> {
+        let result = self.inner.smbus_process_word(register, value);
+        let [lo, hi] = value.to_le_bytes();
+        self.record("process_word", &[register, lo, hi], result, |v| {
+            v.to_le_bytes().to_vec()
+        })
+    }
+
+    fn smbus_read_block_data(&mut self, register: u8) -> Result<Vec<u8>, Self::Error> {
+        let result = self.inner.smbus_read_block_data(register);
+        self.record("read_block_data", &[register], result, Vec::clone)
+    }
+
+    fn smbus_read_i2c_block_data(&mut self, register: u8, len: u8) -> Result<Vec<u8>, Self::Error> {
+        let result = self.inner.smbus_read_i2c_block_data(register, len);
+        self.record("read_i2c_block_data", &[register, len], result, Vec::clone)
+    }
+
+    fn smbus_write_block_data(&mut self, register: u8, values: &[u8]) -> Result<(), Self::Error> {
+        let result = self.inner.smbus_write_block_data(register, values);
+        let args: Vec<u8> = std::iter::once(register)
+            .chain(values.iter().copied())
+            .collect();
+        self.record("write_block_data", &args, result, |_| Vec::new())
+    }
+
+    fn smbus_write_i2c_block_data(
+        &mut self,
+        register: u8,
+        values: &[u8],
+    ) -> Result<(), Self::Error> {
+        let result = self.inner.smbus_write_i2c_block_data(register, values);
+        let args: Vec<u8> = std::iter::once(register)
+            .chain(values.iter().copied())
+            .collect();
+        self.record("write_i2c_block_data", &args, result, |_| Vec::new())
+    }
+
+    fn smbus_process_block(&mut self, register: u8, values: &[u8]) -> Result<Vec<u8>, Self::Error> {
+        let result = self.inner.smbus_process_block(register, values);
+        let args: Vec<u8> = std::iter::once(register)
+            .chain(values.iter().copied())
+            .collect();
+        self.record("process_block", &args, result, Vec::clone)
+    }
+}
+
+impl<'a, D, W> I2CTransfer<'a> for I2cRecorder<D, W>
+where
+    D: I2CDevice + for<'b> I2CTransfer<'b, Error = <D as I2CDevice>::Error>,
+    <D as I2CDevice>::Error: From<std::io::Error>,
+    W: Write,
+{
+    type Error = <D as I2CDevice>::Error;
+    type Message = I2cMessage<'a>;
+
+    fn transfer(&mut self, msgs: &'a mut [Self::Message]) -> Result<u32, Self::Error> {
+        let (written, read) = match msgs {
+            [I2cMessage::Write(written), I2cMessage::Read(read)] => (written, read),
+            _ => return Err(I2cMessage::unsupported().into()),
+        };
+        let result = {
+            // Messages stay borrowed by `transfer` until dropped, so they are
+            // not dropped at all. Neither owns anything.
+            let mut inner =
+                std::mem::ManuallyDrop::new([I2CMessage::write(written), I2CMessage::read(read)]);
+            self.inner.transfer(&mut inner[..])
+        };
+        let args: Vec<u8> = written.iter().copied().chain([read.len() as u8]).collect();
+        let bytes = read.to_vec();
+        self.record("write_read", &args, result, |_| bytes)
+    }
+}
+
+impl I2cMessage<'_> {
+    fn unsupported() -> std::io::Error {
+        std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "only a write followed by a read can be combined",
+        )
+    }
+}
+
+impl<'a> I2CMessage<'a> for I2cMessage<'a> {
+    fn read(data: &'a mut [u8]) -> Self {
+        I2cMessage::Read(data)
+    }
+
+    fn write(data: &'a [u8]) -> Self {
+        I2cMessage::Write(data)
+    }
+}
+
+impl I2cReplay {
+    /// Reads the whole recording, failing on the first malformed line
+    pub fn new<R: BufRead>(input: R) -> Result<Self, ReplayError> {
+        let mut lines = input.lines();
+        let header = match lines.next() {
+            Some(header) => header.map_err(ReplayError::Io)?,
+            None => return Err(ReplayError::BadHeader),
+        };
+        let mut fields = header.split(' ');
+        if fields.next() != Some(MAGIC) {
+            return Err(ReplayError::BadHeader);
+        }
+        match fields.next().map(str::parse::<u32>) {
+            Some(Ok(VERSION)) => {}
+            Some(Ok(version)) => return Err(ReplayError::UnsupportedVersion(version)),
+            _ => return Err(ReplayError::BadHeader),
+        }
+        let mut transactions = VecDeque::new();
+        for (line, text) in (2..).zip(lines) {
+            let text = text.map_err(ReplayError::Io)?;
+            let transaction = I2cReplay::parse(&text).ok_or(ReplayError::Corrupt { line })?;
+            transactions.push_back(transaction);
+        }
+        Ok(Self { transactions })
+    }
+
+    /// Number of transactions not replayed yet
+    pub fn remaining(&self) -> usize {
+        self.transactions.len()
+    }
+
+    fn parse(line: &str) -> Option<Transaction> {
+        let hex = |s: &str| u8::from_str_radix(s, 16).ok();
+        let mut fields = line.split(' ');
+        let op = fields.next().filter(|op| !op.is_empty())?.to_string();
+        let mut args = Vec::new();
+        loop {
+            match fields.next()? {
+                "=" => {
+                    let bytes = fields.map(hex).collect::<Option<Vec<u8>>>()?;
+                    return Some(Transaction {
+                        op,
+                        args,
+                        result: Ok(bytes),
+                    });
+                }
+                "!" => {
+                    return Some(Transaction {
+                        op,
+                        args,
+                        result: Err(fields.collect::<Vec<_>>().join(" ")),
+                    })
+                }
+                arg => args.push(hex(arg)?),
+            }
+        }
+    }
+
+    /// Replays next transaction, which has to match `op` and `args`, and
+    /// returns its result of exactly `len` bytes, if given
+    fn replay(
+        &mut self,
+        op: &str,
+        args: &[u8],
+        len: Option<usize>,
+    ) -> Result<Vec<u8>, LinuxI2CError> {
+        let invalid = |msg: String| {
+            LinuxI2CError::from(std::io::Error::new(std::io::ErrorKind::InvalidData, msg))
+        };
+        let next = match self.transactions.front() {
+            Some(next) => next,
+            None => return Err(invalid(format!("replay exhausted at {} {:02x?}", op, args))),
+        };
+        if next.op != op || next.args != args {
+            return Err(invalid(format!(
+                "replay expected {} {:02x?}, got {} {:02x?}",
+                next.op, next.args, op, args
+            )));
+        }
+        let next = self
+            .transactions
+            .pop_front()
+            .unwrap_or_else(|| unreachable!());
+        match next.result {
+            Ok(bytes) if len.is_some_and(|len| len != bytes.len()) => Err(invalid(format!(
+                "replay of {} returned {} bytes",
+                op,
+                bytes.len()
+            ))),
+            Ok(bytes) => Ok(bytes),
+            Err(msg) => Err(LinuxI2CError::from(I2cReplay::recorded_error(msg))),
+        }
+    }
+
+    /// Error of a recorded failure, with OS error code when the message ends
+    /// with one, as in `Remote I/O error (os error 121)`
+    fn recorded_error(msg: String) -> std::io::Error {
+        let code = msg
+            .strip_suffix(')')
+            .and_then(|m| m.rsplit_once("(os error "))
+            .and_then(|(_, code)| code.parse().ok());
+        match code {
+            Some(code) => std::io::Error::from_raw_os_error(code),
+            None => std::io::Error::other(msg),
+        }
+    }
+
+    fn replay_word(&mut self, op: &str, args: &[u8]) -> Result<u16, LinuxI2CError> {
+        let bytes = self.replay(op, args, Some(2))?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+}
+
+impl I2CDevice for I2cReplay {
+    type Error = LinuxI2CError;
+
+    fn read(&mut self, data: &mut [u8]) -> Result<(), Self::Error> {
+        let bytes = self.replay("read", &[data.len() as u8], Some(data.len()))?;
+        data.copy_from_slice(&bytes);
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        self.replay("write", data, Some(0)).map(drop)
+    }
+
+    fn smbus_write_quick(&mut self, bit: bool) -> Result<(), Self::Error> {
+        self.replay("write_quick", &[bit as u8], Some(0)).map(drop)
+    }
+
+    fn smbus_read_byte(&mut self) -> Result<u8, Self::Error> {
+        Ok(self.replay("read_byte", &[], Some(1))?[0])
+    }
+
+    fn smbus_write_byte(&mut self, value: u8) -> Result<(), Self::Error> {
+        self.replay("write_byte", &[value], Some(0)).map(drop)
+    }
+
+    fn smbus_read_byte_data(&mut self, register: u8) -> Result<u8, Self::Error> {
+        Ok(self.replay("read_byte_data", &[register], Some(1))?[0])
+    }
+
+    fn smbus_write_byte_data(&mut self, register: u8, value: u8) -> Result<(), Self::Error> {
+        self.replay("write_byte_data", &[register, value], Some(0))
+            .map(drop)
+    }
+
+    fn smbus_read_word_data(&mut self, register: u8) -> Result<u16, Self::Error> {
+        self.replay_word("read_word_data", &[register])
+    }
+
+    fn smbus_write_word_data(&mut self, register: u8, value: u16) -> Result<(), Self::Error> {
+        let [lo, hi] = value.to_le_bytes();
+        self.replay("write_word_data", &[register, lo, hi], Some(0))
+            .map(drop)
+    }
+
+    fn smbus_process_word(&mut self, register: u8, value: u16) -> Result<u16, Self::Error> {
+        let [lo, hi] = value.to_le_bytes();
+        self.replay_word("process_word", &[register, lo, hi])
+    }
+
+    fn smbus_read_block_data(&mut self, register: u8) -> Result<Vec<u8>, Self::Error> {
+        self.replay("read_block_data", &[register], None)
+    }
+
+    fn smbus_read_i2c_block_data(&mut self, register: u8, len: u8) -> Result<Vec<u8>, Self::Error> {
+        self.replay("read_i2c_block_data", &[register, len], None)
+    }
+
+    fn smbus_write_block_data(&mut self, register: u8, values: &[u8]) -> Result<(), Self::Error> {
+        let args: Vec<u8> = std::iter::once(register)
+            .chain(values.iter().copied())
+            .collect();
+        self.replay("write_block_data", &args, Some(0)).map(drop)
+    }
+
+    fn smbus_write_i2c_block_data(
+        &mut self,
+        register: u8,
+        values: &[u8],
+    ) -> Result<(), Self::Error> {
+        let args: Vec<u8> = std::iter::once(register)
+            .chain(values.iter().copied())
+            .collect();
+        self.replay("write_i2c_block_data", &args, Some(0))
+            .map(drop)
+    }
+
+    fn smbus_process_block(&mut self, register: u8, values: &[u8]) -> Result<Vec<u8>, Self::Error> {
+        let args: Vec<u8> = std::iter::once(register)
+            .chain(values.iter().copied())
+            .collect();
+        self.replay("process_block", &args, None)
+    }
+}
+
+impl<'a> I2CTransfer<'a> for I2cReplay {
+    type Error = LinuxI2CError;
+    type Message = I2cMessage<'a>;
+
+    fn transfer(&mut self, msgs: &'a mut [Self::Message]) -> Result<u32, Self::Error> {
+        let (written, read) = match msgs {
+            [I2cMessage::Write(written), I2cMessage::Read(read)] => (written, read),
+            _ => return Err(I2cMessage::unsupported().into()),
+        };
+        let args: Vec<u8> = written.iter().copied().chain([read.len() as u8]).collect();
+        let bytes = self.replay("write_read", &args, Some(read.len()))?;
+        read.copy_from_slice(&bytes);
+        Ok(2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Mpr121, Mpr121Error, TouchEvent};
+
+    const SESSION: &str = "mpr121-i2c 1
+read_byte_data 5e = 8f
+read_word_data 00 = 00 00
+read_word_data 00 = 05 00
+read_word_data 00 = 04 00
+read_word_data 00 ! Remote I/O error (os error 121)
+read_i2c_block_data 04 04 = 10 02 20 02
+";
+
+    #[test]
+    fn replay_session() {
+        let replay = I2cReplay::new(SESSION.as_bytes()).unwrap();
+        let recorder = I2cRecorder::new(replay, Vec::new()).unwrap();
+        let mut mpr121 = Mpr121::from_device(recorder, "replay", 0x5A);
+        assert_eq!(mpr121.poll_events().unwrap(), vec![]);
+        assert_eq!(
+            mpr121.poll_events().unwrap(),
+            vec![
+                TouchEvent::Touched { pin: 0 },
+                TouchEvent::Touched { pin: 2 }
+            ]
+        );
+        assert_eq!(
+            mpr121.poll_events().unwrap(),
+            vec![TouchEvent::Released { pin: 0 }]
+        );
+        match mpr121.poll_events() {
+            Err(Mpr121Error::I2c(e)) => assert!(e.to_string().contains("os error 121")),
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        }
+        assert_eq!(
+            mpr121.read_block(0x04, 4).unwrap(),
+            vec![0x10, 0x02, 0x20, 0x02]
+        );
+        // Nothing more was recorded
+        assert!(mpr121.read_block(0x04, 4).is_err());
+        let (replay, recording) = mpr121.into_device().into_inner().unwrap();
+        assert_eq!(replay.remaining(), 0);
+        // Recording of the replay is the same session, followed by the
+        // transaction failed past the end of replay
+        let recording = String::from_utf8(recording).unwrap();
+        assert!(recording.starts_with(SESSION));
+        assert!(recording[SESSION.len()..].starts_with("read_i2c_block_data 04 04 ! "));
+    }
+
+    #[test]
+    fn replay_transfer() {
+        let data = ["00"; 0x2A].join(" ");
+        let session = format!(
+            "mpr121-i2c 1\nread_byte_data 5e = 8f\nwrite_read 00 2a = 01 {}\n",
+            &data[3..]
+        );
+        let replay = I2cReplay::new(session.as_bytes()).unwrap();
+        let recorder = I2cRecorder::new(replay, Vec::new()).unwrap();
+        let mut mpr121 = Mpr121::from_device(recorder, "replay", 0x5A);
+        let mut out = crate::PollOutput::new(true);
+        mpr121.poll_into(&mut out).unwrap();
+        assert_eq!(out.status, 0x0001);
+        let (mut replay, recording) = mpr121.into_device().into_inner().unwrap();
+        assert_eq!(String::from_utf8(recording).unwrap(), session);
+
+        // Only a write followed by a read can be replayed
+        let mut data = [0u8; 2];
+        let mut msgs = [I2cMessage::Read(&mut data)];
+        assert!(replay.transfer(&mut msgs).is_err());
+    }
+
+    #[test]
+    fn replay_mismatch() {
+        let mut replay = I2cReplay::new(SESSION.as_bytes()).unwrap();
+        assert!(replay.smbus_read_byte_data(0x5D).is_err());
+        assert!(replay.smbus_write_byte_data(0x5E, 0x8F).is_err());
+        // Mismatched transactions are not consumed
+        assert_eq!(replay.smbus_read_byte_data(0x5E).unwrap(), 0x8F);
+        assert!(replay.smbus_read_byte_data(0x00).is_err());
+        assert_eq!(replay.remaining(), 5);
+
+        assert!(matches!(
+            I2cReplay::new(&b"mpr121-i2c 2\n"[..]),
+            Err(ReplayError::UnsupportedVersion(2))
+        ));
+        assert!(matches!(
+            I2cReplay::new(&b"mpr121-events 1\n"[..]),
+            Err(ReplayError::BadHeader)
+        ));
+        for (input, bad_line) in [
+            (&b"mpr121-i2c 1\nread_byte_data 5e\n"[..], 2),
+            (
+                b"mpr121-i2c 1\nread_byte_data 5e = 8f\nread_byte_data 5g = 00\n",
+                3,
+            ),
+            (b"mpr121-i2c 1\n\n", 2),
+            (b"mpr121-i2c 1\nread_byte_data = 100\n", 2),
+        ] {
+            match I2cReplay::new(input) {
+                Err(ReplayError::Corrupt { line }) => assert_eq!(line, bad_line),
+                other => panic!("unexpected {:?}", other.map(|_| ())),
+            }
+        }
+        // Result of wrong size is rejected on replay
+        let mut replay = I2cReplay::new(&b"mpr121-i2c 1\nread_word_data 00 = 01\n"[..]).unwrap();
+        assert!(replay.smbus_read_word_data(0x00).is_err());
+    }
+}
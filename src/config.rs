@@ -0,0 +1,1298 @@
+//! Configuration images, tuning profiles and threshold calibration
+
+use i2cdev::{core::I2CDevice, linux::LinuxI2CError};
+
+use crate::{
+    Mpr121, Mpr121Error, Mpr121TouchStatus, ProximityMode, MPR121_RELEASE_THRESHOLD_DEFAULT,
+    MPR121_TOUCH_THRESHOLD_DEFAULT,
+};
+
+/// Phase of a threshold sweep the user is prompted for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SweepPhase {
+    /// Touch and hold the electrode
+    Touch { threshold: u8 },
+    /// Release the electrode
+    Release { threshold: u8 },
+}
+
+/// Result of a single threshold sweep step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SweepStep {
+    /// Touch threshold used in this step
+    pub threshold: u8,
+    /// Touch was detected during touch phase
+    pub touch_detected: bool,
+    /// Electrode was reported as released at the end of release phase
+    pub release_detected: bool,
+}
+
+/// Outcome of `Mpr121::threshold_sweep`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SweepReport {
+    /// All performed steps, in sweep order
+    pub steps: Vec<SweepStep>,
+    /// Widest contiguous range of thresholds where both touch and release
+    /// were detected reliably
+    pub reliable: Option<std::ops::RangeInclusive<u8>>,
+    /// Recommended touch threshold, in the middle of reliable range
+    pub recommended: Option<u8>,
+}
+
+/// Copy of all writable configuration registers of a chip, excluding
+/// volatile status, data, baseline and GPIO registers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigImage {
+    pub(crate) registers: Vec<(u8, u8)>,
+}
+
+/// Configuration image together with where and when it was captured, see
+/// `Mpr121::snapshot`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigSnapshot {
+    /// Origin of the image
+    pub metadata: SnapshotMetadata,
+    /// Captured configuration
+    pub image: ConfigImage,
+}
+
+/// Origin of a `ConfigSnapshot`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotMetadata {
+    /// Version of this crate which captured the snapshot
+    pub crate_version: String,
+    /// Cargo features of the crate which captured the snapshot, see
+    /// `features`
+    pub features: Vec<String>,
+    /// Serialization format version, `ConfigSnapshot::SCHEMA_VERSION` when
+    /// captured
+    pub schema_version: u8,
+    /// When the snapshot was captured
+    pub captured_at: std::time::SystemTime,
+    /// I2C bus device path
+    pub path: std::path::PathBuf,
+    /// I2C address of the chip
+    pub address: u16,
+}
+
+/// Register whose value differs from expected configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ConfigDrift {
+    pub register: u8,
+    pub expected: u8,
+    pub actual: u8,
+}
+
+/// Reasons for rejecting a serialized `ConfigImage`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigImageError {
+    /// Data does not start with configuration image header
+    BadMagic,
+    /// Image was produced with unsupported older format version
+    UnsupportedVersion(u8),
+    /// Image was produced by a newer crate with a newer format version
+    NewerVersion(u8),
+    /// Snapshot metadata is not valid UTF-8 or out of range
+    BadMetadata,
+    /// Data ends before all announced registers
+    Truncated,
+    /// Image contains register which is not a configuration register
+    UnknownRegister(u8),
+}
+
+/// Complete tuning of a chip, captured after auto configuration and
+/// baseline settling, to be reapplied on next boot without the slow settle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TuningProfile {
+    /// Charge current of each electrode in uA (0 to 63)
+    pub charge_current: [u8; 12],
+    /// Charge time code of each electrode (0 to 7), see CDT in datasheet
+    pub charge_time: [u8; 12],
+    /// Touch and release threshold of each electrode
+    pub thresholds: [(u8, u8); 12],
+    /// Raw baseline register of each electrode (8 MSB of 10 bit baseline)
+    pub baseline: [u8; 12],
+    /// Baseline filtering registers MHDR to FDLT
+    pub filters: [u8; 11],
+    /// Debounce register
+    pub debounce: u8,
+    /// Filter and global charge current configuration register
+    pub config1: u8,
+    /// Filter and global charge time configuration register
+    pub config2: u8,
+    /// Electrode configuration register
+    pub ecr: u8,
+}
+
+/// Suspicious combination of settings found by `TuningProfile::warnings`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ConfigWarning {
+    /// Release threshold is not below touch threshold, so the electrode
+    /// may never be released
+    ReleaseNotBelowTouch { electrode: u8 },
+    /// Touch threshold is zero, noise alone triggers touches
+    ZeroTouchThreshold { electrode: u8 },
+    /// High debounce combined with slow sampling makes response sluggish
+    SlowResponse { response_ms: u32 },
+    /// No electrode and no proximity detection is enabled
+    NothingEnabled,
+}
+
+/// Options for `Mpr121::reset_with_options`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResetOptions {
+    /// Touch threshold of all electrodes
+    pub touch: u8,
+    /// Release threshold of all electrodes
+    pub release: u8,
+    /// How many times the whole reset sequence is retried after I2C error
+    pub retries: u8,
+    /// Delay before the first retry, doubled for each following retry
+    pub backoff: std::time::Duration,
+}
+
+/// Delays the driver waits for the chip, see `Mpr121::set_timing_policy`.
+/// Delays requested by the caller (reset retry backoff, sample and poll
+/// intervals) are not part of the policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimingPolicy {
+    /// Wait after soft reset before configuring the chip
+    pub post_reset: std::time::Duration,
+    /// Interval of touch status polls while waiting for the user to touch or
+    /// release pads, e.g. in `Mpr121::guided_wiring_check`
+    pub touch_poll: std::time::Duration,
+    /// Interval of reads while waiting for baselines to settle
+    pub settle_poll: std::time::Duration,
+    /// Wait before retrying a read which failed because another master held
+    /// the bus
+    pub contention_retry: std::time::Duration,
+    /// Wait for the chip to reload baselines, at least one sample period,
+    /// see `Mpr121::recalibrate_baseline`
+    pub baseline_reload: std::time::Duration,
+    /// Whether `Mpr121::identify` waits on and off times of its blink
+    /// pattern. Pins are toggled either way.
+    pub blink: bool,
+}
+
+/// Configuration changes recorded by setters and written to the chip at
+/// once by `commit`, with a single stop/run transition of the electrodes.
+/// Setting the same field twice keeps the last value. Registers are written
+/// in ascending order, electrode configuration register last.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigTxn {
+    writes: std::collections::BTreeMap<u8, (u8, u8)>,
+    verify: bool,
+}
+
+/// Configuration changes recorded for `Mpr121::reconfigure`
+pub type ConfigWriter = ConfigTxn;
+
+/// Sampling and polling settings trading latency for power, see
+/// `Mpr121::suggest_power_config`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerConfig {
+    /// Electrode sample interval code (ESI bits of CONFIG2), interval is
+    /// `1 << esi` ms
+    pub esi: u8,
+    /// Second filter iterations code (SFI bits of CONFIG2)
+    pub sfi: u8,
+    /// How often the application should read touch status
+    pub poll_interval: std::time::Duration,
+}
+
+impl PowerConfig {
+    /// Suggests the longest sample interval whose touch response (sample
+    /// interval times 4 second filter samples) fits into half of
+    /// `max_latency`, leaving the other half for polling. Chip current grows
+    /// roughly with sample rate, so this is the most power efficient choice
+    /// which meets the latency. Latencies below 8ms get the fastest sampling
+    /// and cannot be met. Assumes no touch debounce.
+    pub fn suggest(max_latency: std::time::Duration) -> Self {
+        let max_latency_ms = max_latency.as_millis();
+        let esi = (0..=7u8)
+            .rev()
+            .find(|esi| (1u128 << esi) * 4 * 2 <= max_latency_ms)
+            .unwrap_or(0);
+        let config = PowerConfig {
+            esi,
+            sfi: 0,
+            poll_interval: std::time::Duration::from_millis(0),
+        };
+        PowerConfig {
+            poll_interval: max_latency
+                .saturating_sub(config.response())
+                .max(config.sample_interval()),
+            ..config
+        }
+    }
+
+    /// Interval between electrode samples
+    pub fn sample_interval(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(1 << (self.esi & 0x07))
+    }
+
+    /// Worst case touch response time of the chip, without polling
+    pub fn response(&self) -> std::time::Duration {
+        let samples = [4, 6, 10, 18][(self.sfi & 0x03) as usize];
+        self.sample_interval() * samples
+    }
+}
+
+impl ConfigTxn {
+    /// Creates empty transaction, same as `Mpr121::begin_config`
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes `commit` read every written register back, see
+    /// `Mpr121::set_threshold_verified`
+    pub fn verify_writes(&mut self, verify: bool) -> &mut Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Sets touch and release thresholds of a single electrode
+    pub fn set_threshold(
+        &mut self,
+        electrode: u8,
+        touch: u8,
+        release: u8,
+    ) -> Result<&mut Self, Mpr121Error> {
+        Mpr121::check_electrode(electrode)?;
+        self.set(Mpr121::REG_TOUCHTH_0 + 2 * electrode, 0xFF, touch);
+        self.set(Mpr121::REG_RELEASETH_0 + 2 * electrode, 0xFF, release);
+        Ok(self)
+    }
+
+    /// Sets touch and release debounce, number of samples (0 to 7) a change
+    /// has to persist
+    pub fn set_debounce(&mut self, touch: u8, release: u8) -> &mut Self {
+        self.set(Mpr121::REG_DEBOUNCE, 0x07, touch & 0x07);
+        self.set(Mpr121::REG_DEBOUNCE, 0x70, (release & 0x07) << 4)
+    }
+
+    /// Sets global charge current in uA (0 to 63)
+    pub fn set_charge_current(&mut self, current: u8) -> &mut Self {
+        self.set(Mpr121::REG_CONFIG1, 0x3F, current & 0x3F)
+    }
+
+    /// Sets global charge time code (1 to 7, time is `0.5us << (code - 1)`),
+    /// 0 disables charging
+    pub fn set_charge_time(&mut self, code: u8) -> &mut Self {
+        self.set(Mpr121::REG_CONFIG2, 0xE0, (code & 0x07) << 5)
+    }
+
+    /// Sets sample interval and second filter, see
+    /// `Mpr121::apply_power_config`
+    pub fn set_power_config(&mut self, config: &PowerConfig) -> &mut Self {
+        self.set(
+            Mpr121::REG_CONFIG2,
+            0x1F,
+            (config.sfi & 0x03) << 3 | config.esi & 0x07,
+        )
+    }
+
+    /// Enables touch detection only for given electrodes, see
+    /// `Mpr121::set_active_electrodes`
+    pub fn set_active_electrodes(&mut self, electrodes: &[u8]) -> Result<&mut Self, Mpr121Error> {
+        let count = Mpr121::contiguous_count(electrodes)?;
+        Ok(self.set(Mpr121::REG_ECR, 0x0F, count))
+    }
+
+    /// More sensitive settings for electrodes behind an overlay of given
+    /// thickness in um, see `Mpr121::configure_for_overlay`. Thresholds go
+    /// down from 12/6 to 4/2 and charge current up from 16 to 48 uA over the
+    /// first 3 mm, charge time doubles from 0.5 us at 1 and 2 mm.
+    pub fn overlay(overlay_um: u32) -> Self {
+        let um = overlay_um.min(3000);
+        let touch = (12 - um * 8 / 3000) as u8;
+        let release = (touch / 2).max(2);
+        let charge_time = match overlay_um {
+            0..=999 => 1,
+            1000..=1999 => 2,
+            _ => 3,
+        };
+        let mut txn = ConfigTxn::new();
+        for electrode in Mpr121TouchStatus::first()..=Mpr121TouchStatus::last() {
+            txn.set(Mpr121::REG_TOUCHTH_0 + 2 * electrode, 0xFF, touch);
+            txn.set(Mpr121::REG_RELEASETH_0 + 2 * electrode, 0xFF, release);
+        }
+        txn.set_charge_current((16 + um * 32 / 3000) as u8)
+            .set_charge_time(charge_time);
+        txn
+    }
+
+    /// Applies all changes, see `Mpr121::commit_config`
+    pub fn commit<D>(&self, mpr121: &mut Mpr121<D>) -> Result<(), Mpr121Error>
+    where
+        D: I2CDevice<Error = LinuxI2CError>,
+    {
+        mpr121.commit_config(self)
+    }
+
+    /// Writes of all registers of `image`, see
+    /// `Mpr121::import_config_registers`
+    pub(crate) fn from_image(image: &ConfigImage) -> Self {
+        let mut txn = ConfigTxn::new();
+        for (reg, value) in image.registers.iter() {
+            txn.set(*reg, 0xFF, *value);
+        }
+        txn
+    }
+
+    /// Writes of complete tuning profile, see `Mpr121::apply_profile`
+    pub(crate) fn from_profile(profile: &TuningProfile) -> Self {
+        let mut txn = ConfigTxn::new();
+        for (reg, value) in (Mpr121::REG_MHDR..).zip(profile.filters.iter()) {
+            txn.set(reg, 0xFF, *value);
+        }
+        for (i, (touch, release)) in (0u8..).zip(profile.thresholds.iter()) {
+            txn.set(Mpr121::REG_TOUCHTH_0 + 2 * i, 0xFF, *touch);
+            txn.set(Mpr121::REG_RELEASETH_0 + 2 * i, 0xFF, *release);
+        }
+        txn.set(Mpr121::REG_DEBOUNCE, 0xFF, profile.debounce);
+        txn.set(Mpr121::REG_CONFIG1, 0xFF, profile.config1);
+        txn.set(Mpr121::REG_CONFIG2, 0xFF, profile.config2);
+        for (reg, current) in (Mpr121::REG_CHARGECURR_0..).zip(profile.charge_current.iter()) {
+            txn.set(reg, 0xFF, current & 0x3F);
+        }
+        let time = TuningProfile::pack_charge_time(&profile.charge_time);
+        for (reg, value) in (Mpr121::REG_CHARGETIME_1..).zip(time.iter()) {
+            txn.set(reg, 0xFF, *value);
+        }
+        for (reg, value) in (Mpr121::REG_BASELINE_0..).zip(profile.baseline.iter()) {
+            txn.set(reg, 0xFF, *value);
+        }
+        txn.set(Mpr121::REG_ECR, 0xFF, profile.ecr & 0x3F);
+        txn
+    }
+
+    /// Whether `commit` verifies writes
+    pub(crate) fn verify(&self) -> bool {
+        self.verify
+    }
+
+    /// Changes as (register, mask, value) in write order, ECR last
+    pub(crate) fn writes(&self) -> impl Iterator<Item = (u8, u8, u8)> + '_ {
+        let ecr = self.writes.get_key_value(&Mpr121::REG_ECR);
+        self.writes
+            .iter()
+            .filter(|(reg, _)| **reg != Mpr121::REG_ECR)
+            .chain(ecr)
+            .map(|(reg, (mask, value))| (*reg, *mask, *value))
+    }
+
+    /// Records change of bits in `mask` of register, overriding previously
+    /// recorded change of the same bits
+    fn set(&mut self, reg: u8, mask: u8, value: u8) -> &mut Self {
+        let (old_mask, old_value) = self.writes.get(&reg).copied().unwrap_or((0, 0));
+        if old_mask & mask != 0 {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(reg, "field set twice in transaction, last value wins");
+        }
+        self.writes
+            .insert(reg, (old_mask | mask, old_value & !mask | value & mask));
+        self
+    }
+}
+
+impl Default for ResetOptions {
+    /// Default thresholds, no retries
+    fn default() -> Self {
+        Self {
+            touch: MPR121_TOUCH_THRESHOLD_DEFAULT,
+            release: MPR121_RELEASE_THRESHOLD_DEFAULT,
+            retries: 0,
+            backoff: std::time::Duration::from_millis(10),
+        }
+    }
+}
+
+impl TimingPolicy {
+    /// Policy without any delays, for replayed devices in tests
+    pub fn zero() -> Self {
+        Self {
+            post_reset: std::time::Duration::ZERO,
+            touch_poll: std::time::Duration::ZERO,
+            settle_poll: std::time::Duration::ZERO,
+            contention_retry: std::time::Duration::ZERO,
+            baseline_reload: std::time::Duration::ZERO,
+            blink: false,
+        }
+    }
+}
+
+impl Default for TimingPolicy {
+    /// Datasheet allows configuring the chip right after soft reset, 1 ms is
+    /// waited to be safe. Polls run at the fastest sample interval. Contended
+    /// reads are retried after the time of a few bytes at 100 kHz. Baseline
+    /// reload waits for the longest sample interval (128 ms). Blink patterns
+    /// are waited.
+    fn default() -> Self {
+        Self {
+            post_reset: std::time::Duration::from_millis(1),
+            touch_poll: std::time::Duration::from_millis(5),
+            settle_poll: std::time::Duration::from_millis(10),
+            contention_retry: std::time::Duration::from_micros(250),
+            baseline_reload: std::time::Duration::from_millis(128),
+            blink: true,
+        }
+    }
+}
+
+impl SweepReport {
+    /// Finds the widest range of successful steps and recommends a threshold
+    pub fn from_steps(steps: Vec<SweepStep>) -> Self {
+        let mut best: Option<(u8, u8)> = None;
+        let mut current: Option<(u8, u8)> = None;
+        for step in steps.iter() {
+            let ok = step.touch_detected && step.release_detected;
+            let t = u16::from(step.threshold);
+            current = match current {
+                // Continue only if thresholds are adjacent
+                Some((lo, hi)) if ok && (t + 1 == u16::from(lo) || t == u16::from(hi) + 1) => {
+                    Some((lo.min(step.threshold), hi.max(step.threshold)))
+                }
+                _ if ok => Some((step.threshold, step.threshold)),
+                _ => None,
+            };
+            if let Some((lo, hi)) = current {
+                let wider = match best {
+                    Some((blo, bhi)) => hi - lo > bhi - blo,
+                    None => true,
+                };
+                if wider {
+                    best = current;
+                }
+            }
+        }
+        SweepReport {
+            steps,
+            reliable: best.map(|(lo, hi)| lo..=hi),
+            recommended: best.map(|(lo, hi)| lo + (hi - lo) / 2),
+        }
+    }
+}
+
+impl ConfigImage {
+    /// Current version of serialized format
+    pub const VERSION: u8 = 1;
+    const MAGIC: &'static [u8; 3] = b"MPR";
+    // Baseline filters, thresholds, debounce, CONFIG1/2, charge current and
+    // time, auto configuration. ECR is handled separately.
+    pub(crate) const RANGES: [std::ops::RangeInclusive<u8>; 3] =
+        [0x2B..=0x5D, 0x5F..=0x72, 0x7B..=0x7F];
+
+    /// Version of `test_config`, bumped whenever any of its values change
+    pub const TEST_CONFIG_VERSION: u8 = 1;
+
+    /// Known good configuration for reproducible tests, covering every
+    /// configuration register (version `TEST_CONFIG_VERSION`):
+    ///
+    /// | Registers | Value |
+    /// |-----------|-------|
+    /// | MHDR, NHDR, NCLR, FDLR (0x2B-0x2E) | 0x01, 0x01, 0x0E, 0x00 |
+    /// | MHDF, NHDF, NCLF, FDLF (0x2F-0x32) | 0x01, 0x05, 0x01, 0x00 |
+    /// | NHDT, NCLT, FDLT (0x33-0x35) | 0x00 |
+    /// | Proximity filters (0x36-0x40) | 0x00 |
+    /// | Touch / release thresholds (0x41-0x58) | 12 / 6 |
+    /// | Proximity thresholds (0x59-0x5A) | 0x00 |
+    /// | Debounce (0x5B) | 0x00, no debounce |
+    /// | CONFIG1 (0x5C) | 0x10, 6 samples first filter, 16uA |
+    /// | CONFIG2 (0x5D) | 0x20, 0.5us, 4 samples second filter, 1ms period |
+    /// | Charge current and time (0x5F-0x72) | 0x00, global values used |
+    /// | Auto configuration (0x7B-0x7F) | 0x00, disabled |
+    /// | ECR (0x5E) | 0x8F, all electrodes, baseline from first sample |
+    pub fn test_config() -> ConfigImage {
+        const FILTERS: [u8; 11] = [
+            0x01, 0x01, 0x0E, 0x00, 0x01, 0x05, 0x01, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let value = |reg: u8| match reg {
+            Mpr121::REG_MHDR..=Mpr121::REG_FDLT => FILTERS[(reg - Mpr121::REG_MHDR) as usize],
+            Mpr121::REG_TOUCHTH_0..=0x58 if reg % 2 == 1 => 12,
+            Mpr121::REG_RELEASETH_0..=0x58 => 6,
+            Mpr121::REG_CONFIG1 => 0x10,
+            Mpr121::REG_CONFIG2 => 0x20,
+            _ => 0x00,
+        };
+        let mut registers: Vec<(u8, u8)> = ConfigImage::RANGES
+            .iter()
+            .flat_map(|r| r.clone())
+            .map(|reg| (reg, value(reg)))
+            .collect();
+        registers.push((Mpr121::REG_ECR, 0x8F));
+        ConfigImage { registers }
+    }
+
+    fn is_config_register(reg: u8) -> bool {
+        reg == Mpr121::REG_ECR || ConfigImage::RANGES.iter().any(|r| r.contains(&reg))
+    }
+
+    /// Returns value of a register stored in the image
+    pub fn get(&self, reg: u8) -> Option<u8> {
+        self.registers
+            .iter()
+            .find(|(r, _)| *r == reg)
+            .map(|(_, v)| *v)
+    }
+
+    /// Returns image with only given registers, e.g. to audit thresholds and
+    /// ECR without reading everything
+    pub fn subset(&self, registers: &[u8]) -> ConfigImage {
+        ConfigImage {
+            registers: self
+                .registers
+                .iter()
+                .filter(|(r, _)| registers.contains(r))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Returns registers of this (expected) image whose value differs in
+    /// `actual` image. Registers missing from `actual` are not reported.
+    pub fn drift(&self, actual: &ConfigImage) -> Vec<ConfigDrift> {
+        self.registers
+            .iter()
+            .filter_map(|(reg, expected)| match actual.get(*reg) {
+                Some(a) if a != *expected => Some(ConfigDrift {
+                    register: *reg,
+                    expected: *expected,
+                    actual: a,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// All stored registers and their values, in write order
+    pub fn registers(&self) -> &[(u8, u8)] {
+        &self.registers
+    }
+
+    /// Serializes image as header (magic, version, register count) followed
+    /// by register and value pairs
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(5 + 2 * self.registers.len());
+        bytes.extend_from_slice(ConfigImage::MAGIC);
+        bytes.push(ConfigImage::VERSION);
+        bytes.push(self.registers.len() as u8);
+        for (reg, value) in self.registers.iter() {
+            bytes.push(*reg);
+            bytes.push(*value);
+        }
+        bytes
+    }
+
+    /// CRC32 (IEEE 802.3) of serialized image, for telling apart units with
+    /// different configuration
+    pub fn crc32(&self) -> u32 {
+        crc32(&self.to_bytes())
+    }
+
+    /// Deserializes image produced by `to_bytes`
+    #[deny(clippy::indexing_slicing)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ConfigImageError> {
+        let (version, count, data) = match bytes {
+            [m0, m1, m2, version, count, data @ ..] if [*m0, *m1, *m2] == *ConfigImage::MAGIC => {
+                (*version, *count as usize, data)
+            }
+            _ => return Err(ConfigImageError::BadMagic),
+        };
+        if version > ConfigImage::VERSION {
+            return Err(ConfigImageError::NewerVersion(version));
+        }
+        if version != ConfigImage::VERSION {
+            return Err(ConfigImageError::UnsupportedVersion(version));
+        }
+        if data.len() < 2 * count {
+            return Err(ConfigImageError::Truncated);
+        }
+        let mut registers = Vec::with_capacity(count);
+        for pair in data.chunks_exact(2).take(count) {
+            if let [reg, value] = *pair {
+                if !ConfigImage::is_config_register(reg) {
+                    return Err(ConfigImageError::UnknownRegister(reg));
+                }
+                registers.push((reg, value));
+            }
+        }
+        Ok(ConfigImage { registers })
+    }
+}
+
+impl std::fmt::Display for ConfigImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigImageError::BadMagic => write!(f, "missing configuration image header"),
+            ConfigImageError::UnsupportedVersion(v) => write!(
+                f,
+                "format version {} is not supported, expected {}",
+                v,
+                ConfigImage::VERSION
+            ),
+            ConfigImageError::NewerVersion(v) => write!(
+                f,
+                "produced by newer crate, format version {} is newer than {}",
+                v,
+                ConfigImage::VERSION
+            ),
+            ConfigImageError::BadMetadata => write!(f, "snapshot metadata is malformed"),
+            ConfigImageError::Truncated => write!(f, "image is truncated"),
+            ConfigImageError::UnknownRegister(r) => {
+                write!(f, "register 0x{:02X} is not a configuration register", r)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigImageError {}
+
+impl SnapshotMetadata {
+    /// Metadata of a snapshot of chip at `path` and `address` captured now
+    /// by this crate
+    pub fn new<P: AsRef<std::path::Path>>(path: P, address: u16) -> Self {
+        Self {
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            features: crate::features().iter().map(|f| f.to_string()).collect(),
+            schema_version: ConfigSnapshot::SCHEMA_VERSION,
+            captured_at: std::time::SystemTime::now(),
+            path: path.as_ref().to_path_buf(),
+            address,
+        }
+    }
+}
+
+impl ConfigSnapshot {
+    const MAGIC: &'static [u8; 3] = b"MPS";
+    /// Version of serialized snapshot layout, bumped whenever the layout of
+    /// the snapshot or of `ConfigImage` changes
+    pub const SCHEMA_VERSION: u8 = 2;
+
+    /// Serializes snapshot as header (magic, schema version), crate version,
+    /// features, capture time, address and path, followed by `ConfigImage::to_bytes`.
+    /// Capture times before 1970 are stored as 1970.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let m = &self.metadata;
+        let captured = m
+            .captured_at
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let path = m.path.to_string_lossy();
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(ConfigSnapshot::MAGIC);
+        bytes.push(ConfigSnapshot::SCHEMA_VERSION);
+        bytes.push(m.crate_version.len().min(255) as u8);
+        bytes.extend(m.crate_version.bytes().take(255));
+        bytes.push(m.features.len().min(255) as u8);
+        for feature in m.features.iter().take(255) {
+            bytes.push(feature.len().min(255) as u8);
+            bytes.extend(feature.bytes().take(255));
+        }
+        bytes.extend_from_slice(&captured.as_secs().to_le_bytes());
+        bytes.extend_from_slice(&captured.subsec_nanos().to_le_bytes());
+        bytes.extend_from_slice(&m.address.to_le_bytes());
+        bytes.extend_from_slice(&(path.len().min(0xFFFF) as u16).to_le_bytes());
+        bytes.extend(path.bytes().take(0xFFFF));
+        bytes.extend(self.image.to_bytes());
+        bytes
+    }
+
+    /// Deserializes snapshot produced by `to_bytes`. Snapshots of newer
+    /// schema fail with `ConfigImageError::NewerVersion`.
+    #[deny(clippy::indexing_slicing)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ConfigImageError> {
+        fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], ConfigImageError> {
+            if bytes.len() < len {
+                return Err(ConfigImageError::Truncated);
+            }
+            let (head, tail) = bytes.split_at(len);
+            *bytes = tail;
+            Ok(head)
+        }
+        fn text(bytes: &[u8]) -> Result<String, ConfigImageError> {
+            String::from_utf8(bytes.to_vec()).map_err(|_| ConfigImageError::BadMetadata)
+        }
+
+        let (schema_version, mut rest) = match bytes {
+            [m0, m1, m2, version, rest @ ..] if [*m0, *m1, *m2] == *ConfigSnapshot::MAGIC => {
+                (*version, rest)
+            }
+            _ => return Err(ConfigImageError::BadMagic),
+        };
+        if schema_version > ConfigSnapshot::SCHEMA_VERSION {
+            return Err(ConfigImageError::NewerVersion(schema_version));
+        }
+        if schema_version != ConfigSnapshot::SCHEMA_VERSION {
+            return Err(ConfigImageError::UnsupportedVersion(schema_version));
+        }
+        let rest = &mut rest;
+        let len = take(rest, 1)?.first().copied().unwrap_or(0);
+        let crate_version = text(take(rest, len as usize)?)?;
+        let count = take(rest, 1)?.first().copied().unwrap_or(0);
+        let mut features = Vec::new();
+        for _ in 0..count {
+            let len = take(rest, 1)?.first().copied().unwrap_or(0);
+            features.push(text(take(rest, len as usize)?)?);
+        }
+        let mut secs = [0; 8];
+        secs.copy_from_slice(take(rest, 8)?);
+        let mut nanos = [0; 4];
+        nanos.copy_from_slice(take(rest, 4)?);
+        let nanos = u32::from_le_bytes(nanos);
+        if nanos >= 1_000_000_000 {
+            return Err(ConfigImageError::BadMetadata);
+        }
+        let captured_at = std::time::UNIX_EPOCH
+            .checked_add(std::time::Duration::new(u64::from_le_bytes(secs), nanos))
+            .ok_or(ConfigImageError::BadMetadata)?;
+        let mut address = [0; 2];
+        address.copy_from_slice(take(rest, 2)?);
+        let mut len = [0; 2];
+        len.copy_from_slice(take(rest, 2)?);
+        let path = text(take(rest, u16::from_le_bytes(len) as usize)?)?;
+        Ok(ConfigSnapshot {
+            metadata: SnapshotMetadata {
+                crate_version,
+                features,
+                schema_version,
+                captured_at,
+                path: path.into(),
+                address: u16::from_le_bytes(address),
+            },
+            image: ConfigImage::from_bytes(rest)?,
+        })
+    }
+}
+
+impl TuningProfile {
+    /// Packs 3 bit charge time codes, two electrodes per register with even
+    /// electrode in lower bits
+    pub(crate) fn pack_charge_time(time: &[u8; 12]) -> [u8; 6] {
+        let mut packed = [0; 6];
+        for (p, t) in packed.iter_mut().zip(time.chunks(2)) {
+            *p = (t[1] & 0x07) << 4 | (t[0] & 0x07);
+        }
+        packed
+    }
+
+    /// Reverse of `pack_charge_time`
+    pub(crate) fn unpack_charge_time(packed: &[u8]) -> [u8; 12] {
+        let mut time = [0; 12];
+        for (t, p) in time.chunks_mut(2).zip(packed) {
+            t[0] = p & 0x07;
+            t[1] = p >> 4 & 0x07;
+        }
+        time
+    }
+}
+
+impl TuningProfile {
+    /// Sample interval in ms, from ESI bits of CONFIG2
+    pub fn sample_interval_ms(&self) -> u32 {
+        1 << (self.config2 & 0x07)
+    }
+
+    /// Number of samples taken by second level filter, from SFI bits of CONFIG2
+    pub fn second_filter_samples(&self) -> u32 {
+        [4, 6, 10, 18][(self.config2 >> 3 & 0x03) as usize]
+    }
+
+    /// Worst case touch response time in ms, including touch debounce
+    pub fn response_ms(&self) -> u32 {
+        let debounce = u32::from(self.debounce & 0x07);
+        self.sample_interval_ms() * self.second_filter_samples() * (1 + debounce)
+    }
+
+    /// Returns suspicious combinations of settings
+    pub fn warnings(&self) -> Vec<ConfigWarning> {
+        let mut warnings = Vec::new();
+        let electrodes = (self.ecr & 0x0F).min(12);
+        for (electrode, (touch, release)) in (0..electrodes).zip(self.thresholds.iter()) {
+            if *touch == 0 {
+                warnings.push(ConfigWarning::ZeroTouchThreshold { electrode });
+            }
+            if release >= touch {
+                warnings.push(ConfigWarning::ReleaseNotBelowTouch { electrode });
+            }
+        }
+        if self.debounce & 0x77 != 0 && self.sample_interval_ms() >= 16 {
+            warnings.push(ConfigWarning::SlowResponse {
+                response_ms: self.response_ms(),
+            });
+        }
+        if self.ecr & 0x3F == 0 {
+            warnings.push(ConfigWarning::NothingEnabled);
+        }
+        warnings
+    }
+
+    /// Describes the profile in plain words, followed by warnings about
+    /// suspicious combinations of settings, one per line
+    pub fn explain(&self) -> String {
+        fn sensitivity(threshold: u8) -> &'static str {
+            match threshold {
+                0..=4 => "very high sensitivity",
+                5..=8 => "high sensitivity",
+                9..=16 => "moderate sensitivity",
+                17..=32 => "low sensitivity",
+                _ => "very low sensitivity",
+            }
+        }
+        fn counts<I: Iterator<Item = u8> + Clone>(values: I) -> (u8, u8) {
+            (values.clone().min().unwrap_or(0), values.max().unwrap_or(0))
+        }
+
+        let electrodes = (self.ecr & 0x0F).min(12) as usize;
+        let mut parts = Vec::new();
+        if electrodes == 0 {
+            parts.push("no touch electrodes enabled".to_string());
+        } else {
+            parts.push(format!("electrodes 0-{} enabled", electrodes - 1));
+            let used = &self.thresholds[..electrodes];
+            let (lo, hi) = counts(used.iter().map(|t| t.0));
+            if lo == hi {
+                parts.push(format!(
+                    "touch threshold {} counts (~{})",
+                    lo,
+                    sensitivity(lo)
+                ));
+            } else {
+                parts.push(format!(
+                    "touch thresholds {}-{} counts (~{} to {})",
+                    lo,
+                    hi,
+                    sensitivity(hi),
+                    sensitivity(lo)
+                ));
+            }
+            let (lo, hi) = counts(used.iter().map(|t| t.1));
+            if lo == hi {
+                parts.push(format!("release threshold {} counts", lo));
+            } else {
+                parts.push(format!("release thresholds {}-{} counts", lo, hi));
+            }
+        }
+        match ProximityMode::from_ecr(self.ecr) {
+            ProximityMode::Disabled => {}
+            ProximityMode::Electrodes0To1 => parts.push("proximity on electrodes 0-1".to_string()),
+            ProximityMode::Electrodes0To3 => parts.push("proximity on electrodes 0-3".to_string()),
+            ProximityMode::Electrodes0To11 => {
+                parts.push("proximity on electrodes 0-11".to_string())
+            }
+        }
+        let (touch_db, release_db) = (self.debounce & 0x07, self.debounce >> 4 & 0x07);
+        if touch_db == 0 && release_db == 0 {
+            parts.push("debounce off".to_string());
+        } else {
+            parts.push(format!(
+                "debounce {} touch / {} release samples",
+                touch_db, release_db
+            ));
+        }
+        parts.push(format!(
+            "{} ms sample interval -> ~{} ms worst-case response",
+            self.sample_interval_ms(),
+            self.response_ms()
+        ));
+        parts.push(format!("charge current {} uA", self.config1 & 0x3F));
+        match self.config2 >> 5 {
+            0 => parts.push("charge time per electrode".to_string()),
+            1 => parts.push("charge time 0.5 us".to_string()),
+            cdt => parts.push(format!("charge time {} us", 1u8 << (cdt - 2))),
+        }
+
+        let mut explanation = parts.join(", ");
+        for w in self.warnings() {
+            explanation.push_str(&format!("\nwarning: {}", w));
+        }
+        explanation
+    }
+}
+
+impl std::fmt::Display for ConfigWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigWarning::ReleaseNotBelowTouch { electrode } => write!(
+                f,
+                "release threshold of electrode {} is not below touch threshold",
+                electrode
+            ),
+            ConfigWarning::ZeroTouchThreshold { electrode } => {
+                write!(f, "touch threshold of electrode {} is zero", electrode)
+            }
+            ConfigWarning::SlowResponse { response_ms } => write!(
+                f,
+                "debounce with slow sample interval gives {} ms response",
+                response_ms
+            ),
+            ConfigWarning::NothingEnabled => write!(f, "nothing is enabled"),
+        }
+    }
+}
+
+/// Bitwise CRC32 with reflected polynomial 0xEDB88320, as used by zlib
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0u32, |crc, b| {
+        (0..8).fold(crc ^ u32::from(*b), |crc, _| {
+            (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sweep_report() {
+        let step = |threshold, touch_detected, release_detected| SweepStep {
+            threshold,
+            touch_detected,
+            release_detected,
+        };
+        let report = SweepReport::from_steps(vec![]);
+        assert_eq!(report.reliable, None);
+        assert_eq!(report.recommended, None);
+
+        let report = SweepReport::from_steps(vec![
+            step(20, false, true),
+            step(19, true, true),
+            step(18, false, true),
+            step(17, true, true),
+            step(16, true, true),
+            step(15, true, true),
+            step(14, true, true),
+            step(13, true, false),
+            step(12, true, true),
+        ]);
+        assert_eq!(report.reliable, Some(14..=17));
+        assert_eq!(report.recommended, Some(15));
+        assert_eq!(report.steps.len(), 9);
+
+        // Gaps in thresholds split the range
+        let report = SweepReport::from_steps(vec![
+            step(10, true, true),
+            step(8, true, true),
+            step(7, true, true),
+        ]);
+        assert_eq!(report.reliable, Some(7..=8));
+        assert_eq!(report.recommended, Some(7));
+    }
+
+    #[test]
+    fn config_image() {
+        let image = ConfigImage {
+            registers: vec![
+                (0x41, 12),
+                (0x42, 6),
+                (0x5D, 0x20),
+                (0x7B, 0x0B),
+                (0x5E, 0x8F),
+            ],
+        };
+        let bytes = image.to_bytes();
+        assert_eq!(&bytes[..5], b"MPR\x01\x05");
+        assert_eq!(ConfigImage::from_bytes(&bytes).unwrap(), image);
+        assert_eq!(image.get(0x5D), Some(0x20));
+        assert_eq!(image.get(0x00), None);
+
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(image.crc32(), crc32(&bytes));
+        let mut other = image.clone();
+        other.registers[2].1 = 0x24;
+        assert_ne!(other.crc32(), image.crc32());
+
+        assert_eq!(
+            ConfigImage::from_bytes(b"XYZ\x01\x00"),
+            Err(ConfigImageError::BadMagic)
+        );
+        assert_eq!(
+            ConfigImage::from_bytes(b"MPR"),
+            Err(ConfigImageError::BadMagic)
+        );
+        assert_eq!(
+            ConfigImage::from_bytes(b"MPR\x02\x00"),
+            Err(ConfigImageError::NewerVersion(2))
+        );
+        assert_eq!(
+            ConfigImage::from_bytes(b"MPR\x00\x00"),
+            Err(ConfigImageError::UnsupportedVersion(0))
+        );
+        assert_eq!(
+            ConfigImage::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(ConfigImageError::Truncated)
+        );
+        // Malformed inputs must be rejected without panicking
+        for (input, error) in [
+            (&b""[..], ConfigImageError::BadMagic),
+            (b"MPR\x01", ConfigImageError::BadMagic),
+            (b"MPR\x01\xFF", ConfigImageError::Truncated),
+            (b"MPR\x01\x01\x41", ConfigImageError::Truncated),
+            (
+                b"MPR\x01\x01\x80\x63",
+                ConfigImageError::UnknownRegister(0x80),
+            ),
+        ] {
+            assert_eq!(ConfigImage::from_bytes(input), Err(error));
+        }
+        // Trailing bytes past register count are ignored
+        assert_eq!(
+            ConfigImage::from_bytes(b"MPR\x01\x01\x41\x0C\x42").unwrap(),
+            ConfigImage {
+                registers: vec![(0x41, 12)]
+            }
+        );
+        let actual = ConfigImage {
+            registers: vec![(0x41, 12), (0x42, 4), (0x5E, 0x00)],
+        };
+        assert_eq!(
+            image.drift(&actual),
+            vec![
+                ConfigDrift {
+                    register: 0x42,
+                    expected: 6,
+                    actual: 4
+                },
+                ConfigDrift {
+                    register: 0x5E,
+                    expected: 0x8F,
+                    actual: 0x00
+                },
+            ]
+        );
+        let subset = image.subset(&[0x41, 0x5E, 0x00]);
+        assert_eq!(subset.registers(), &[(0x41, 12), (0x5E, 0x8F)]);
+        assert!(subset.drift(&image).is_empty());
+
+        // Status, baseline and GPIO registers are not configuration
+        for reg in [0x00, 0x1E, 0x73, 0x75, 0x80].iter() {
+            assert_eq!(
+                ConfigImage::from_bytes(&[b'M', b'P', b'R', 1, 1, *reg, 0]),
+                Err(ConfigImageError::UnknownRegister(*reg))
+            );
+        }
+    }
+
+    #[test]
+    fn charge_time_packing() {
+        let time = [1, 2, 3, 4, 5, 6, 7, 0, 1, 7, 2, 5];
+        let packed = TuningProfile::pack_charge_time(&time);
+        assert_eq!(packed, [0x21, 0x43, 0x65, 0x07, 0x71, 0x52]);
+        assert_eq!(TuningProfile::unpack_charge_time(&packed), time);
+        // Reserved bits are ignored
+        assert_eq!(TuningProfile::unpack_charge_time(&[0xFF; 6]), [7; 12]);
+        assert_eq!(TuningProfile::pack_charge_time(&[0xF9; 12]), [0x11; 6]);
+    }
+
+    #[test]
+    fn config_txn() {
+        let mut txn = ConfigTxn::new();
+        txn.set_active_electrodes(&[0, 1, 2])
+            .unwrap()
+            .set_debounce(2, 1)
+            .set_threshold(11, 20, 10)
+            .unwrap()
+            .set_charge_current(16)
+            .set_threshold(0, 12, 6)
+            .unwrap()
+            .set_debounce(3, 1);
+        let writes: Vec<_> = txn.writes().collect();
+        assert_eq!(
+            writes,
+            vec![
+                (0x41, 0xFF, 12),
+                (0x42, 0xFF, 6),
+                (0x57, 0xFF, 20),
+                (0x58, 0xFF, 10),
+                (0x5B, 0x77, 0x13),
+                (0x5C, 0x3F, 16),
+                (0x5E, 0x0F, 3),
+            ]
+        );
+        assert!(!txn.verify());
+        assert!(txn.verify_writes(true).verify());
+
+        assert!(txn.set_threshold(12, 1, 1).is_err());
+        assert!(txn.set_active_electrodes(&[1]).is_err());
+        assert_eq!(txn.writes().count(), 7);
+        assert_eq!(ConfigTxn::new().writes().count(), 0);
+    }
+
+    #[test]
+    fn overlay_config() {
+        let settings = |um| {
+            let writes: Vec<_> = ConfigTxn::overlay(um).writes().collect();
+            assert_eq!(writes.len(), 26);
+            assert!(writes[..24]
+                .chunks(2)
+                .all(|w| (w[0].2, w[1].2) == (writes[0].2, writes[1].2)));
+            (writes[0].2, writes[1].2, writes[24], writes[25])
+        };
+        assert_eq!(settings(0), (12, 6, (0x5C, 0x3F, 16), (0x5D, 0xE0, 0x20)));
+        assert_eq!(settings(1500), (8, 4, (0x5C, 0x3F, 32), (0x5D, 0xE0, 0x40)));
+        assert_eq!(settings(3000), (4, 2, (0x5C, 0x3F, 48), (0x5D, 0xE0, 0x60)));
+        assert_eq!(settings(10_000), settings(3000));
+    }
+
+    #[test]
+    fn config_snapshot() {
+        // Chip exporting test configuration, as recorded on the bus
+        let image = ConfigImage::test_config();
+        let mut session = String::from("mpr121-i2c 1\n");
+        for (reg, len) in [(0x2B, 32), (0x4B, 19), (0x5F, 20), (0x7B, 5)] {
+            session.push_str(&format!("read_i2c_block_data {:02x} {:02x} =", reg, len));
+            for r in reg..reg + len {
+                session.push_str(&format!(" {:02x}", image.get(r).unwrap()));
+            }
+            session.push('\n');
+        }
+        session.push_str("read_byte_data 5e = 8f\n");
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "/dev/i2c-7", 0x5C);
+        let before = std::time::SystemTime::now();
+        let snapshot = mpr121.snapshot().unwrap();
+        assert_eq!(snapshot.image, image);
+        let m = &snapshot.metadata;
+        assert_eq!(m.crate_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(m.features, crate::features());
+        assert_eq!(m.schema_version, ConfigSnapshot::SCHEMA_VERSION);
+        assert_eq!(m.path, std::path::Path::new("/dev/i2c-7"));
+        assert_eq!(m.address, 0x5C);
+        assert!(m.captured_at >= before && m.captured_at <= std::time::SystemTime::now());
+
+        let bytes = snapshot.to_bytes();
+        assert_eq!(ConfigSnapshot::from_bytes(&bytes).unwrap(), snapshot);
+
+        // Produced by a newer crate
+        let mut newer = bytes.clone();
+        newer[3] = ConfigSnapshot::SCHEMA_VERSION + 1;
+        let error = ConfigSnapshot::from_bytes(&newer).unwrap_err();
+        assert_eq!(
+            error,
+            ConfigImageError::NewerVersion(ConfigSnapshot::SCHEMA_VERSION + 1)
+        );
+        assert!(error.to_string().contains("produced by newer crate"));
+        // Newer image layout inside a snapshot
+        let image_start = bytes.len() - image.to_bytes().len();
+        let mut newer = bytes.clone();
+        newer[image_start + 3] = ConfigImage::VERSION + 1;
+        assert_eq!(
+            ConfigSnapshot::from_bytes(&newer),
+            Err(ConfigImageError::NewerVersion(2))
+        );
+
+        for len in 0..image_start {
+            assert!(ConfigSnapshot::from_bytes(&bytes[..len]).is_err());
+        }
+        assert_eq!(
+            ConfigSnapshot::from_bytes(&image.to_bytes()),
+            Err(ConfigImageError::BadMagic)
+        );
+        let mut bad_text = bytes;
+        bad_text[5] = 0xFF;
+        assert_eq!(
+            ConfigSnapshot::from_bytes(&bad_text),
+            Err(ConfigImageError::BadMetadata)
+        );
+    }
+
+    #[test]
+    fn power_config() {
+        let ms = std::time::Duration::from_millis;
+        let c = PowerConfig::suggest(ms(100));
+        assert_eq!((c.esi, c.sfi), (3, 0));
+        assert_eq!(c.sample_interval(), ms(8));
+        assert_eq!(c.response(), ms(32));
+        assert_eq!(c.poll_interval, ms(68));
+        assert_eq!(PowerConfig::suggest(ms(1024)).esi, 7);
+        assert_eq!(PowerConfig::suggest(ms(10_000)).esi, 7);
+        assert_eq!(PowerConfig::suggest(ms(8)).esi, 0);
+        let c = PowerConfig::suggest(ms(2));
+        assert_eq!(c.esi, 0);
+        assert_eq!(c.poll_interval, ms(1));
+    }
+
+    #[test]
+    fn explain_profile() {
+        let mut profile = TuningProfile {
+            charge_current: [16; 12],
+            charge_time: [1; 12],
+            thresholds: [(12, 6); 12],
+            baseline: [0; 12],
+            filters: [0; 11],
+            debounce: 0,
+            config1: 0x10,
+            config2: 0x20,
+            ecr: 0x8F,
+        };
+        assert!(profile.warnings().is_empty());
+        assert_eq!(
+            profile.explain(),
+            "electrodes 0-11 enabled, touch threshold 12 counts (~moderate sensitivity), \
+             release threshold 6 counts, debounce off, 1 ms sample interval -> ~4 ms \
+             worst-case response, charge current 16 uA, charge time 0.5 us"
+        );
+
+        profile.thresholds[3] = (6, 6);
+        profile.thresholds[5] = (0, 0);
+        // Electrodes which are not enabled are not checked
+        profile.thresholds[11] = (1, 2);
+        profile.ecr = 0x86;
+        assert_eq!(
+            profile.warnings(),
+            vec![
+                ConfigWarning::ReleaseNotBelowTouch { electrode: 3 },
+                ConfigWarning::ZeroTouchThreshold { electrode: 5 },
+                ConfigWarning::ReleaseNotBelowTouch { electrode: 5 },
+            ]
+        );
+
+        // Debounce alone is fine, with slow sampling it is not
+        profile.thresholds = [(12, 6); 12];
+        profile.debounce = 0x22;
+        assert!(profile.warnings().is_empty());
+        profile.config2 = 0x24;
+        assert_eq!(
+            profile.warnings(),
+            vec![ConfigWarning::SlowResponse { response_ms: 192 }]
+        );
+        assert!(profile
+            .explain()
+            .contains("16 ms sample interval -> ~192 ms"));
+        assert!(profile
+            .explain()
+            .ends_with("\nwarning: debounce with slow sample interval gives 192 ms response"));
+
+        profile.ecr = 0x80;
+        profile.debounce = 0;
+        assert_eq!(profile.warnings(), vec![ConfigWarning::NothingEnabled]);
+        assert!(profile
+            .explain()
+            .starts_with("no touch electrodes enabled, debounce off"));
+    }
+
+    #[test]
+    fn test_config() {
+        let image = ConfigImage::test_config();
+        assert_eq!(image.registers().len(), 77);
+        assert_eq!(image.registers().last(), Some(&(0x5E, 0x8F)));
+        assert_eq!(image.get(0x2D), Some(0x0E));
+        assert_eq!(image.get(0x30), Some(0x05));
+        assert_eq!(image.get(0x41), Some(12));
+        assert_eq!(image.get(0x58), Some(6));
+        assert_eq!(image.get(0x59), Some(0));
+        assert_eq!(image.get(0x5C), Some(0x10));
+        assert_eq!(image.get(0x5D), Some(0x20));
+        assert_eq!(image.get(0x7B), Some(0));
+        assert_eq!(ConfigImage::from_bytes(&image.to_bytes()).unwrap(), image);
+    }
+}
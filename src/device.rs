@@ -0,0 +1,3847 @@
+//! MPR121 device access over Linux I2C
+
+use std::os::unix::io::AsRawFd;
+
+use i2cdev::{
+    core::*,
+    linux::{LinuxI2CDevice, LinuxI2CError},
+};
+
+use crate::{
+    protocol, Aggregation, BlinkPattern, ConfigDrift, ConfigImage, ConfigSnapshot, ConfigTxn,
+    ConfigWriter, Decimator, ElectrodeInfo, EnableBlanking, Events, GpioState, Mpr121Error,
+    Mpr121TouchStatus, PollOutput, PowerConfig, ProximityMode, ResetOptions, SampleStream,
+    SensorFrame, SettleDetector, SnapshotMetadata, SweepPhase, SweepReport, SweepStep,
+    TimingPolicy, TouchEvent, TuningProfile,
+};
+
+/// Manages adafruit MPR121 capacitive sensor HAT I2C device.
+///
+/// Linux I2C device is used by default, `from_device` accepts any other
+/// `I2CDevice` reporting Linux errors, e.g. the recording and replaying
+/// devices of `bus`.
+///
+/// `Mpr121` is `Send` and `Sync`, as the underlying I2C device only owns a
+/// file descriptor. All operations need `&mut self`, so to share a sensor
+/// between threads put it behind a mutex, e.g. with `SharedMpr121`.
+pub struct Mpr121<D = LinuxI2CDevice> {
+    dev: D,
+    path: std::path::PathBuf,
+    address: u16,
+    read_mode: ReadMode,
+    stale_data_check: bool,
+    allow_stopped_reads: bool,
+    auto_reconfigure: bool,
+    last_reset: Option<ResetOptions>,
+    init_state: InitState,
+    init_resume: Option<InitStage>,
+    timing: TimingPolicy,
+    contention_events: u64,
+    inverted: u16,
+    ecr: Option<u8>,
+    session_ecr: Option<u8>,
+    blanking: EnableBlanking,
+    vdd_mv: u16,
+    velocity_max_delta: u16,
+    previous_status: Mpr121TouchStatus,
+    streaming: Option<StreamingSaved>,
+    #[cfg(feature = "address-registry")]
+    _claim: Option<registry::AddressClaim>,
+}
+
+/// Registers changed by `Mpr121::enter_streaming_mode`, restored on exit
+#[derive(Debug, Clone, Copy)]
+struct StreamingSaved {
+    debounce: u8,
+    config2: u8,
+    ecr: u8,
+}
+
+/// Thread safe, cloneable handle to a `Mpr121` shared behind a mutex
+#[derive(Clone)]
+pub struct SharedMpr121 {
+    inner: std::sync::Arc<std::sync::Mutex<Mpr121>>,
+}
+
+/// Core touch sensor operations, allowing applications to depend on
+/// `Box<dyn TouchSensor>` and run without the hardware (see `NullMpr121`)
+pub trait TouchSensor {
+    /// Reads current touch status of all electrodes
+    fn touch_status(&mut self) -> Result<Mpr121TouchStatus, Mpr121Error>;
+
+    /// Resets sensor into default state ready to detect touch inputs
+    fn reset(&mut self) -> Result<(), Mpr121Error>;
+}
+
+/// Touch sensor stub for machines without MPR121, nothing is ever touched
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NullMpr121;
+
+/// Result of `Mpr121::identify`, telling which unit on which bus was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityReport {
+    /// I2C bus device path
+    pub path: std::path::PathBuf,
+    /// I2C address of the chip
+    pub address: u16,
+    /// CRC32 of exported configuration registers, see `ConfigImage::crc32`
+    pub config_crc: u32,
+    /// Electrode indexed mask of GPIO output pins which were blinked
+    pub blinked: u16,
+}
+
+/// Classification of the chip by its register values after soft reset, see
+/// `Mpr121::chip_variant`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ChipVariant {
+    /// All checked registers reset to datasheet defaults
+    Genuine,
+    /// Soft reset works, but CONFIG1, CONFIG2 or other registers reset to
+    /// values different from the datasheet, so `reset` may refuse the chip
+    CompatibleClone,
+    /// Soft reset did not clear registers, the part may not be MPR121 at all
+    Unknown,
+}
+
+/// Stage of `Mpr121::initialize_with_deadline`, in order of execution
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InitStage {
+    /// Soft reset and stop mode
+    SoftReset,
+    /// Check that CONFIG2 is at its reset value
+    Verify,
+    /// Touch and release thresholds of all electrodes
+    Thresholds,
+    /// Baseline filters, debounce, charge current and time, sample interval
+    Filters,
+    /// Start all electrodes
+    Enable,
+}
+
+/// Result of `Mpr121::initialize_with_deadline`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum InitOutcome {
+    /// All stages completed, the chip is running
+    Complete,
+    /// Deadline passed after `completed_through`, see
+    /// `Mpr121::resume_initialization`
+    Partial { completed_through: InitStage },
+}
+
+/// Whether the chip was configured by the driver, see `Mpr121::state`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InitState {
+    /// Opened but not configured yet, reads fail with
+    /// `Mpr121Error::Uninitialized`
+    Uninitialized,
+    /// Electrodes were started by the driver, or found running when the
+    /// first read checked the chip
+    Initialized,
+    /// Reset of a configured chip (e.g. an automatic recovery) failed, so the
+    /// chip may be partially configured. Reads fail with
+    /// `Mpr121Error::Degraded` until a reset succeeds.
+    Degraded { reason: String },
+}
+
+impl InitStage {
+    /// All stages in order
+    pub const ALL: [InitStage; 5] = [
+        InitStage::SoftReset,
+        InitStage::Verify,
+        InitStage::Thresholds,
+        InitStage::Filters,
+        InitStage::Enable,
+    ];
+
+    /// Stage following this one
+    pub fn next(self) -> Option<InitStage> {
+        InitStage::ALL.get(self as usize + 1).copied()
+    }
+
+    /// Stage preceding this one
+    pub fn previous(self) -> Option<InitStage> {
+        (self as usize).checked_sub(1).map(|i| InitStage::ALL[i])
+    }
+}
+
+/// Selects how the touch status register is fetched from the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReadMode {
+    /// Single SMBus word read of both status bytes (default)
+    #[default]
+    Word,
+    /// Two separate SMBus byte reads, low byte first
+    Bytes,
+}
+
+/// Default I2C address for MPR121
+pub const MPR121_I2CADDR_DEFAULT: u16 = protocol::ADDRESS_FIRST;
+
+/// Default touch threshold set for MPR121
+pub const MPR121_TOUCH_THRESHOLD_DEFAULT: u8 = 12;
+
+/// Default release threshold set for MPR121
+pub const MPR121_RELEASE_THRESHOLD_DEFAULT: u8 = 6;
+
+impl Mpr121 {
+    // Register addresses.
+    pub(crate) const REG_TOUCHSTATUS_L: u8 = 0x00;
+    pub(crate) const REG_TOUCHSTATUS_H: u8 = 0x01;
+    pub(crate) const REG_FILTDATA_0L: u8 = 0x04;
+    // const REG_FILTDATA_0H: u8 = 0x05;
+    pub(crate) const REG_BASELINE_0: u8 = 0x1E;
+    pub(crate) const REG_MHDR: u8 = 0x2B;
+    pub(crate) const REG_NHDR: u8 = 0x2C;
+    pub(crate) const REG_NCLR: u8 = 0x2D;
+    pub(crate) const REG_FDLR: u8 = 0x2E;
+    pub(crate) const REG_MHDF: u8 = 0x2F;
+    pub(crate) const REG_NHDF: u8 = 0x30;
+    pub(crate) const REG_NCLF: u8 = 0x31;
+    pub(crate) const REG_FDLF: u8 = 0x32;
+    pub(crate) const REG_NHDT: u8 = 0x33;
+    pub(crate) const REG_NCLT: u8 = 0x34;
+    pub(crate) const REG_FDLT: u8 = 0x35;
+    pub(crate) const REG_MHDPROXR: u8 = 0x36;
+    pub(crate) const REG_NHDPROXR: u8 = 0x37;
+    pub(crate) const REG_NCLPROXR: u8 = 0x38;
+    pub(crate) const REG_FDLPROXR: u8 = 0x39;
+    pub(crate) const REG_MHDPROXF: u8 = 0x3A;
+    pub(crate) const REG_NHDPROXF: u8 = 0x3B;
+    pub(crate) const REG_NCLPROXF: u8 = 0x3C;
+    pub(crate) const REG_FDLPROXF: u8 = 0x3D;
+    pub(crate) const REG_NHDPROXT: u8 = 0x3E;
+    pub(crate) const REG_NCLPROXT: u8 = 0x3F;
+    pub(crate) const REG_FDLPROXT: u8 = 0x40;
+    pub(crate) const REG_TOUCHTH_0: u8 = 0x41;
+    pub(crate) const REG_RELEASETH_0: u8 = 0x42;
+    pub(crate) const REG_PROXTOUCHTH: u8 = 0x59;
+    pub(crate) const REG_PROXRELEASETH: u8 = 0x5A;
+    pub(crate) const REG_DEBOUNCE: u8 = 0x5B;
+    pub(crate) const REG_CONFIG1: u8 = 0x5C;
+    pub(crate) const REG_CONFIG2: u8 = protocol::CONFIG2_REGISTER;
+    pub(crate) const REG_CHARGECURR_0: u8 = 0x5F;
+    pub(crate) const REG_CHARGETIME_1: u8 = 0x6C;
+    pub(crate) const REG_ECR: u8 = protocol::ECR_REGISTER;
+    // const REG_AUTOCONFIG0: u8 = 0x7B;
+    // const REG_AUTOCONFIG1: u8 = 0x7C;
+    // const REG_UPLIMIT: u8 = 0x7D;
+    // const REG_LOWLIMIT: u8 = 0x7E;
+    // const REG_TARGETLIMIT: u8 = 0x7F;
+    pub(crate) const REG_GPIOCTL0: u8 = 0x73;
+    pub(crate) const REG_GPIOCTL1: u8 = 0x74;
+    pub(crate) const REG_GPIODATA: u8 = 0x75;
+    pub(crate) const REG_GPIODIR: u8 = 0x76;
+    pub(crate) const REG_GPIOEN: u8 = 0x77;
+    // const REG_GPIOSET: u8 = 0x78;
+    // const REG_GPIOCLR: u8 = 0x79;
+    pub(crate) const REG_GPIOTOGGLE: u8 = 0x7A;
+    pub(crate) const REG_SOFTRESET: u8 = protocol::SOFT_RESET_REGISTER;
+
+    /// Retries of a read failing because another master holds the bus
+    const CONTENTION_RETRIES: u8 = 3;
+
+    /// Written to touch threshold of electrode 0 by `chip_variant`, soft reset
+    /// clears it
+    const RESET_MARKER: u8 = 0xA5;
+
+    /// Opens MPR121 with default I2C address (see `MPR121_I2CADDR_DEFAULT`)
+    pub fn new_default(device_id: u8) -> Result<Self, Mpr121Error> {
+        Mpr121::new(device_id, MPR121_I2CADDR_DEFAULT)
+    }
+
+    /// Opens MPR121 with given I2C address on `/dev/i2c-<device_id>`.
+    ///
+    /// With `address-registry` feature enabled, returns
+    /// `Mpr121Error::AddressInUse` if another `Mpr121` in this process already
+    /// uses the same bus and address.
+    pub fn new(device_id: u8, slave_addr: u16) -> Result<Self, Mpr121Error> {
+        Mpr121::new_with_path(format!("/dev/i2c-{}", device_id), slave_addr)
+    }
+
+    /// Same as `new`, but with full path to the I2C bus device
+    pub fn new_with_path<P: AsRef<std::path::Path>>(
+        path: P,
+        slave_addr: u16,
+    ) -> Result<Self, Mpr121Error> {
+        #[cfg(feature = "address-registry")]
+        {
+            let claim = registry::AddressClaim::claim(path.as_ref(), slave_addr)?;
+            let mut mpr121 = Mpr121::new_unchecked(path, slave_addr)?;
+            mpr121._claim = Some(claim);
+            Ok(mpr121)
+        }
+        #[cfg(not(feature = "address-registry"))]
+        Mpr121::new_unchecked(path, slave_addr)
+    }
+
+    /// Probes all four addresses MPR121 can use (`protocol::ADDRESSES`) on
+    /// `/dev/i2c-<device_id>` and returns handles of responding chips with
+    /// their addresses. Addresses which cannot be opened (e.g. already open
+    /// with `address-registry`), do not respond or answer unlike MPR121 are
+    /// skipped, chips are not reset.
+    pub fn scan_bus(device_id: u8) -> Result<Vec<(u16, Mpr121)>, Mpr121Error> {
+        Ok(Mpr121::scan_with(|addr| Mpr121::new(device_id, addr)))
+    }
+
+    /// Probes devices opened by `open` for every address, see `scan_bus`
+    fn scan_with<D, F>(mut open: F) -> Vec<(u16, Mpr121<D>)>
+    where
+        D: I2CDevice<Error = LinuxI2CError>,
+        F: FnMut(u16) -> Result<Mpr121<D>, Mpr121Error>,
+    {
+        protocol::ADDRESSES
+            .filter_map(|addr| open(addr).ok().map(|mpr121| (addr, mpr121)))
+            .filter_map(|(addr, mut mpr121)| match mpr121.probe() {
+                true => Some((addr, mpr121)),
+                false => None,
+            })
+            .collect()
+    }
+
+    /// Opens MPR121 without checking the address registry, allowing several
+    /// handles to the same device
+    pub fn new_unchecked<P: AsRef<std::path::Path>>(
+        path: P,
+        slave_addr: u16,
+    ) -> Result<Self, Mpr121Error> {
+        let dev = LinuxI2CDevice::new(path.as_ref(), slave_addr)?;
+        Ok(Mpr121::from_device(dev, path, slave_addr))
+    }
+
+    /// Sets how long the I2C adapter waits for the bus before a transfer fails,
+    /// a longer timeout helps with long buses. Kernel uses 10ms resolution, so
+    /// timeout is rounded up to the next multiple of 10ms.
+    pub fn set_timeout(&mut self, timeout: std::time::Duration) -> Result<(), Mpr121Error> {
+        // From linux/i2c-dev.h
+        const I2C_TIMEOUT: libc::c_ulong = 0x0702;
+        let ticks = timeout.as_millis().div_ceil(10);
+        let ticks = ticks.min(libc::c_ulong::MAX as u128) as libc::c_ulong;
+        // SAFETY: fd is owned by the open device, I2C_TIMEOUT takes the
+        // timeout by value and does not access memory.
+        let result = unsafe { libc::ioctl(self.dev.as_raw_fd(), I2C_TIMEOUT as _, ticks) };
+        if result < 0 {
+            return Err(Mpr121Error::I2c(std::io::Error::last_os_error().into()));
+        }
+        Ok(())
+    }
+
+    /// Returns endless iterator sampling filtered data every `interval` and
+    /// emitting one frame per `factor` samples aggregated with `policy`.
+    /// Sampling cadence is kept steady regardless of how quickly frames are
+    /// consumed, as long as the consumer keeps up.
+    pub fn sample_stream(
+        &mut self,
+        interval: std::time::Duration,
+        factor: usize,
+        policy: Aggregation,
+    ) -> SampleStream<'_> {
+        SampleStream {
+            mpr121: self,
+            interval,
+            next: std::time::Instant::now(),
+            decimator: Decimator::new(factor, policy),
+        }
+    }
+
+    /// Capacitance in fF of electrode charged by `current` uA (CDC) for
+    /// charge time code `time` (CDT, 0.5us * 2^(time-1)) to voltage measured
+    /// as 10 bit `counts` of `vdd_mv`, from C = I * t / V, rounded to
+    /// nearest. Returns zero when charging is disabled and saturates at
+    /// `u32::MAX`, also for zero counts.
+    pub fn capacitance_ff(counts: u16, current: u8, time: u8, vdd_mv: u16) -> u32 {
+        if time == 0 {
+            return 0;
+        }
+        let time_ns = 500u64 << (time.min(7) - 1);
+        let voltage = u64::from(counts) * u64::from(vdd_mv);
+        if voltage == 0 {
+            return u32::MAX;
+        }
+        // uA * ns / mV = pF, scaled by 1000 to fF and by 1024 for counts
+        let charge = u64::from(current) * time_ns * 1000 * 1024;
+        ((charge + voltage / 2) / voltage).min(u64::from(u32::MAX)) as u32
+    }
+
+    /// Same as `capacitance_ff` in pF with `vdd` in V, returns infinity for
+    /// zero counts
+    #[cfg(feature = "float")]
+    pub fn capacitance_pf(counts: u16, current: u8, time: u8, vdd: f32) -> f32 {
+        if time == 0 {
+            return 0.0;
+        }
+        let time_us = 0.5 * f32::from(1u8 << (time.min(7) - 1));
+        let voltage = f32::from(counts) * vdd / 1024.0;
+        // uA * us = pC, pC / V = pF
+        f32::from(current) * time_us / voltage
+    }
+
+    /// Rejects status with reserved bits 13 and 14 set, which are always zero
+    /// on a healthy read, e.g. when adapter returns garbage as high byte
+    fn validate_status(raw: u16) -> Result<u16, Mpr121Error> {
+        if raw & 0x6000 != 0 {
+            return Err(Mpr121Error::CorruptRead { raw });
+        }
+        Ok(raw)
+    }
+
+    /// Flips inverted electrode bits of raw status register value, the only
+    /// place where polarity correction happens
+    fn correct_polarity(raw: u16, inverted: u16) -> u16 {
+        raw ^ (inverted & 0x0FFF)
+    }
+
+    /// Returns number of electrodes if they form a range starting at 0
+    pub(crate) fn contiguous_count(electrodes: &[u8]) -> Result<u8, Mpr121Error> {
+        let mut mask = 0u16;
+        for e in electrodes {
+            Mpr121::check_electrode(*e)?;
+            mask |= 1 << e;
+        }
+        let count = mask.count_ones() as u8;
+        if mask != (1 << count) - 1 {
+            return Err(Mpr121Error::NonContiguousElectrodes { mask });
+        }
+        Ok(count)
+    }
+
+    /// Time each electrode is sampled after electrode 0 within one scan,
+    /// from CONFIG1 (first filter iterations), CONFIG2 (global charge time),
+    /// charge time registers 0x6C-0x71 (per electrode charge time, 0 uses
+    /// global) and number of enabled electrodes. Each enabled electrode takes
+    /// FFI charge and discharge cycles of its charge time, electrodes are
+    /// scanned from 0 up. Disabled electrodes get the offset of the scan end.
+    pub fn sample_offsets(
+        config1: u8,
+        config2: u8,
+        charge_times: &[u8; 6],
+        enabled: u8,
+    ) -> [std::time::Duration; 12] {
+        let ffi = [6u64, 10, 18, 34][(config1 >> 6) as usize];
+        let charge_ns = |code: u8| match code & 0x07 {
+            0 => 0,
+            code => 500u64 << (code - 1),
+        };
+        let mut offsets = [std::time::Duration::ZERO; 12];
+        let mut offset = 0;
+        for (i, o) in offsets.iter_mut().enumerate() {
+            *o = std::time::Duration::from_nanos(offset);
+            if i < enabled.min(12) as usize {
+                let own = charge_times[i / 2] >> (4 * (i % 2)) & 0x07;
+                let code = if own != 0 { own } else { config2 >> 5 };
+                offset += ffi * 2 * charge_ns(code);
+            }
+        }
+        offsets
+    }
+
+    /// Least squares slope of baseline samples taken at given offsets, in
+    /// milli-counts per second. Zero for less than two distinct offsets.
+    pub fn drift_slope(samples: &[(std::time::Duration, u16)]) -> i64 {
+        let n = samples.len() as i128;
+        let (mut st, mut sb, mut stt, mut stb) = (0i128, 0i128, 0i128, 0i128);
+        for (t, b) in samples {
+            let t = t.as_micros() as i128;
+            let b = i128::from(*b);
+            st += t;
+            sb += b;
+            stt += t * t;
+            stb += t * b;
+        }
+        let denominator = n * stt - st * st;
+        if denominator == 0 {
+            return 0;
+        }
+        ((n * stb - st * sb) * 1_000_000_000 / denominator) as i64
+    }
+
+    /// Scales `delta` linearly to 0-255, reaching 255 at `max_delta`
+    pub(crate) fn velocity(delta: i32, max_delta: u16) -> u8 {
+        let max = i32::from(max_delta.max(1));
+        (delta.clamp(0, max) * 255 / max) as u8
+    }
+
+    /// Decodes 10 bit little endian filtered data of 12 electrodes
+    pub(crate) fn decode_filtered(data: &[u8]) -> [u16; 12] {
+        let mut filtered = [0; 12];
+        for (f, d) in filtered.iter_mut().zip(data.chunks(2)) {
+            *f = (u16::from(d[1]) << 8 | u16::from(d[0])) & 0x3FF;
+        }
+        filtered
+    }
+
+    pub(crate) fn check_electrode(electrode: u8) -> Result<(), Mpr121Error> {
+        if electrode > Mpr121TouchStatus::last() {
+            return Err(Mpr121Error::InvalidElectrode(electrode));
+        }
+        Ok(())
+    }
+
+    /// Classifies chip by values of registers 0x2B to 0x5D and ECR read
+    /// right after soft reset, see `chip_variant`
+    pub(crate) fn classify_reset_state(registers: &[u8], ecr: u8) -> ChipVariant {
+        let reg = |r: u8| registers.get((r - Mpr121::REG_MHDR) as usize).copied();
+        if registers.len() != 51 || reg(Mpr121::REG_TOUCHTH_0) != Some(0) || ecr != 0 {
+            return ChipVariant::Unknown;
+        }
+        let zeros = (Mpr121::REG_MHDR..=Mpr121::REG_DEBOUNCE).all(|r| reg(r) == Some(0));
+        if zeros
+            && reg(Mpr121::REG_CONFIG1) == Some(0x10)
+            && reg(Mpr121::REG_CONFIG2) == Some(protocol::CONFIG2_RESET_VALUE)
+        {
+            ChipVariant::Genuine
+        } else {
+            ChipVariant::CompatibleClone
+        }
+    }
+
+    /// Records `result` as `outcome` of the current span when built with the
+    /// `tracing` feature, returns it as is
+    fn traced<T>(result: Result<T, Mpr121Error>) -> Result<T, Mpr121Error> {
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("outcome", if result.is_ok() { "ok" } else { "error" });
+        result
+    }
+
+    /// Touch and release thresholds of each electrode as percentage of its
+    /// 10 bit baseline, rounded down and kept within 1 to 255 for touch and
+    /// below touch for release
+    pub fn adaptive_thresholds(
+        baseline: &[u16; 12],
+        touch_percent: u8,
+        release_percent: u8,
+    ) -> [(u8, u8); 12] {
+        let scale = |b: u16, percent: u8| (u32::from(b) * u32::from(percent) / 100).min(255) as u8;
+        let mut thresholds = [(0, 0); 12];
+        for (t, b) in thresholds.iter_mut().zip(baseline.iter()) {
+            let touch = scale(*b, touch_percent).max(1);
+            *t = (touch, scale(*b, release_percent).min(touch - 1));
+        }
+        thresholds
+    }
+}
+
+impl<D: I2CDevice<Error = LinuxI2CError>> Mpr121<D> {
+    /// Whether the device answers like MPR121: CONFIG2 at its reset value, or
+    /// reserved bits 13 and 14 of touch status reading as zero
+    fn probe(&mut self) -> bool {
+        match self.dev.smbus_read_byte_data(Mpr121::REG_CONFIG2) {
+            Ok(protocol::CONFIG2_RESET_VALUE) => true,
+            Ok(_) => match self.dev.smbus_read_byte_data(Mpr121::REG_TOUCHSTATUS_H) {
+                Ok(high) => high & 0x60 == 0,
+                Err(_) => false,
+            },
+            Err(_) => false,
+        }
+    }
+
+    /// Wraps an already opened I2C device, e.g. one recording or replaying
+    /// transactions (see `bus`). `path` and `address` are only reported back
+    /// by `path` and `address`.
+    pub fn from_device<P: AsRef<std::path::Path>>(dev: D, path: P, address: u16) -> Self {
+        Mpr121 {
+            dev,
+            path: path.as_ref().to_path_buf(),
+            address,
+            read_mode: ReadMode::default(),
+            stale_data_check: false,
+            allow_stopped_reads: false,
+            auto_reconfigure: false,
+            last_reset: None,
+            init_state: InitState::Uninitialized,
+            init_resume: None,
+            timing: TimingPolicy::default(),
+            contention_events: 0,
+            inverted: 0,
+            ecr: None,
+            session_ecr: None,
+            blanking: EnableBlanking::default(),
+            vdd_mv: 3300,
+            velocity_max_delta: 100,
+            previous_status: Mpr121TouchStatus::new(0),
+            streaming: None,
+            #[cfg(feature = "address-registry")]
+            _claim: None,
+        }
+    }
+
+    /// Returns the wrapped I2C device
+    pub fn into_device(self) -> D {
+        self.dev
+    }
+
+    /// Path of the I2C bus device
+    pub fn path(&self) -> &std::path::Path {
+        &self.path
+    }
+
+    /// I2C address of the chip
+    pub fn address(&self) -> u16 {
+        self.address
+    }
+
+    /// Selects how `touch_status` reads the status register. Some I2C adapters
+    /// do not handle SMBus word reads well, in which case `ReadMode::Bytes` helps.
+    pub fn set_read_mode(&mut self, mode: ReadMode) {
+        self.read_mode = mode;
+    }
+
+    /// Returns currently used read mode for the touch status
+    pub fn read_mode(&self) -> ReadMode {
+        self.read_mode
+    }
+
+    /// Enables rejection of stale data. After an internal reset (e.g. a
+    /// brownout) the chip reports nothing touched, which looks like all
+    /// electrodes were released. When enabled, each all-zero status is
+    /// double-checked against the electrode configuration register and
+    /// `touch_status` returns `Mpr121Error::NotReady` if electrodes are stopped.
+    pub fn set_stale_data_check(&mut self, enabled: bool) {
+        self.stale_data_check = enabled;
+    }
+
+    /// While electrodes are stopped (e.g. right after power-up or a soft
+    /// reset) the chip reports no touches and zero data. To not mistake that
+    /// for nothing being touched, `touch_status`, `touch_status_low`,
+    /// `filtered_data`, `sensor_frame` and methods built on them return
+    /// `Mpr121Error::NotRunning` when the cached electrode configuration shows
+    /// electrodes stopped. Enabling this restores reading whatever the chip
+    /// reports in stop mode, also before initialization, see `state`.
+    pub fn set_allow_stopped_reads(&mut self, allowed: bool) {
+        self.allow_stopped_reads = allowed;
+    }
+
+    /// Sets delays the driver waits for the chip, e.g. `TimingPolicy::zero`
+    /// for replayed devices. Zero delays never sleep.
+    pub fn set_timing_policy(&mut self, timing: TimingPolicy) {
+        self.timing = timing;
+    }
+
+    /// Returns delays the driver waits for the chip
+    pub fn timing_policy(&self) -> &TimingPolicy {
+        &self.timing
+    }
+
+    /// Number of reads which failed because another master held the bus, see
+    /// `Mpr121Error::is_bus_contention`. Such reads are retried up to 3 times
+    /// after `TimingPolicy::contention_retry`, before any other error handling.
+    pub fn contention_count(&self) -> u64 {
+        self.contention_events
+    }
+
+    /// Returns initialization state of the chip, e.g. for logging
+    pub fn state(&self) -> &InitState {
+        &self.init_state
+    }
+
+    /// Makes `touch_status` recover from internal resets (e.g. a brownout) on
+    /// its own. When enabled, each `touch_status` first reads the electrode
+    /// configuration register and if the chip stopped electrodes which the
+    /// driver left running, re-runs the last `reset_with_options` (or
+    /// `reset`) before reading the status. Configuration changed after that
+    /// reset is not restored. Does nothing until the first reset.
+    pub fn set_auto_reconfigure(&mut self, enabled: bool) {
+        self.auto_reconfigure = enabled;
+    }
+
+    /// Inverts touch state of electrode whose status bit reads inverted, e.g.
+    /// due to board wiring. Correction is applied to every status read by
+    /// `touch_status`, `touch_status_low` and `sensor_frame`, and survives
+    /// resets.
+    pub fn set_pin_inverted(&mut self, pin: u8, inverted: bool) -> Result<(), Mpr121Error> {
+        Mpr121::check_electrode(pin)?;
+        if inverted {
+            self.inverted |= 1 << pin;
+        } else {
+            self.inverted &= !(1 << pin);
+        }
+        Ok(())
+    }
+
+    /// Returns mask of electrodes with inverted touch state
+    pub fn inverted_pins(&self) -> u16 {
+        self.inverted
+    }
+
+    /// End of blanking window after electrodes were last started by this
+    /// driver, while touch status and filtered data are not reliable yet.
+    /// `None` once the window passed. See `EnableBlanking`.
+    pub fn settling_until(&self) -> Option<std::time::Instant> {
+        self.blanking.settling_until(std::time::Instant::now())
+    }
+
+    /// Changes length of blanking window after electrodes are started,
+    /// `EnableBlanking::DEFAULT_WINDOW` by default
+    pub fn set_blanking_window(&mut self, window: std::time::Duration) {
+        self.blanking.set_window(window);
+    }
+
+    /// Tracker of the last electrode start, e.g. to mark data captured at a
+    /// given time as unreliable
+    pub fn blanking(&self) -> &EnableBlanking {
+        &self.blanking
+    }
+
+    /// Reset the MPR121 into a default state ready to detect touch inputs, with
+    /// default thresholds for touch and release
+    pub fn reset(&mut self) -> Result<(), Mpr121Error> {
+        self.reset_with_thresholds(
+            MPR121_TOUCH_THRESHOLD_DEFAULT,
+            MPR121_RELEASE_THRESHOLD_DEFAULT,
+        )
+    }
+
+    /// Reset the MPR121 into a default state ready to detect touch inputs
+    pub fn reset_with_thresholds(&mut self, touch: u8, release: u8) -> Result<(), Mpr121Error> {
+        self.reset_with_options(&ResetOptions {
+            touch,
+            release,
+            ..ResetOptions::default()
+        })
+    }
+
+    /// Reset the MPR121 into a default state ready to detect touch inputs,
+    /// retrying the whole sequence from the start if any transfer fails, so
+    /// that a transient bus error does not leave the chip half-configured.
+    /// Fails with `Mpr121Error::WriteVerifyFailed` when CONFIG2 does not hold
+    /// its reset value after the soft reset, e.g. when the device is not
+    /// MPR121.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            skip_all,
+            fields(
+                bus = %self.path.display(),
+                address = self.address,
+                outcome = tracing::field::Empty
+            ),
+            err
+        )
+    )]
+    pub fn reset_with_options(&mut self, options: &ResetOptions) -> Result<(), Mpr121Error> {
+        Mpr121::traced(self.reset_with_retries(options))
+    }
+
+    fn reset_with_retries(&mut self, options: &ResetOptions) -> Result<(), Mpr121Error> {
+        let mut backoff = options.backoff;
+        let mut attempt = 0;
+        loop {
+            match self.reset_sequence(options.touch, options.release) {
+                Err(Mpr121Error::I2c(_)) if attempt < options.retries => {
+                    attempt += 1;
+                    self.sleep(backoff);
+                    backoff *= 2;
+                }
+                Ok(()) => {
+                    self.last_reset = Some(*options);
+                    self.init_state = InitState::Initialized;
+                    return Ok(());
+                }
+                Err(e) => {
+                    if self.init_state != InitState::Uninitialized {
+                        self.init_state = InitState::Degraded {
+                            reason: e.to_string(),
+                        };
+                    }
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    fn reset_sequence(&mut self, touch: u8, release: u8) -> Result<(), Mpr121Error> {
+        self.init_resume = None;
+        for stage in InitStage::ALL.iter() {
+            self.init_stage(*stage, touch, release)?;
+        }
+        Ok(())
+    }
+
+    /// Resets and configures the chip like `reset`, in stages reported to
+    /// `progress` as they complete. When `deadline` passes between stages,
+    /// returns `InitOutcome::Partial` and `resume_initialization` continues
+    /// with the next stage, without another soft reset. The first stage
+    /// always runs. A stage which fails is retried on resume. Unexpected
+    /// CONFIG2 after reset fails with `Mpr121Error::WriteVerifyFailed`.
+    pub fn initialize_with_deadline<F>(
+        &mut self,
+        deadline: std::time::Instant,
+        progress: F,
+    ) -> Result<InitOutcome, Mpr121Error>
+    where
+        F: FnMut(InitStage),
+    {
+        self.run_init_stages(InitStage::SoftReset, deadline, progress)
+    }
+
+    /// Continues initialization interrupted by the deadline or an error of
+    /// `initialize_with_deadline`. Without one pending, starts a new one
+    /// unless the chip is initialized, e.g. after a failed `reset`.
+    pub fn resume_initialization<F>(
+        &mut self,
+        deadline: std::time::Instant,
+        progress: F,
+    ) -> Result<InitOutcome, Mpr121Error>
+    where
+        F: FnMut(InitStage),
+    {
+        match self.init_resume {
+            Some(stage) => self.run_init_stages(stage, deadline, progress),
+            None if self.init_state == InitState::Initialized => Ok(InitOutcome::Complete),
+            None => self.run_init_stages(InitStage::SoftReset, deadline, progress),
+        }
+    }
+
+    fn run_init_stages<F>(
+        &mut self,
+        first: InitStage,
+        deadline: std::time::Instant,
+        mut progress: F,
+    ) -> Result<InitOutcome, Mpr121Error>
+    where
+        F: FnMut(InitStage),
+    {
+        let options = ResetOptions::default();
+        let mut stage = first;
+        loop {
+            self.init_resume = Some(stage);
+            self.init_stage(stage, options.touch, options.release)?;
+            progress(stage);
+            stage = match stage.next() {
+                Some(next) => next,
+                None => break,
+            };
+            self.init_resume = Some(stage);
+            if std::time::Instant::now() >= deadline {
+                return Ok(InitOutcome::Partial {
+                    completed_through: stage.previous().unwrap_or(InitStage::SoftReset),
+                });
+            }
+        }
+        self.init_resume = None;
+        self.last_reset = Some(options);
+        self.init_state = InitState::Initialized;
+        Ok(InitOutcome::Complete)
+    }
+
+    /// Runs a single stage of reset and configuration, see `InitStage`
+    fn init_stage(&mut self, stage: InitStage, touch: u8, release: u8) -> Result<(), Mpr121Error> {
+        match stage {
+            InitStage::SoftReset => {
+                // Write to the reset register.
+                self.ecr = None;
+                self.streaming = None;
+                self.dev
+                    .smbus_write_byte_data(Mpr121::REG_SOFTRESET, protocol::SOFT_RESET_COMMAND)?;
+                // This delay here probably isn't necessary but can't hurt.
+                self.sleep(self.timing.post_reset);
+                // Set electrode configuration to default values.
+                self.stop()?;
+            }
+            InitStage::Verify => {
+                // Check CDT, SFI, ESI configuration is at default values.
+                let got = self.dev.smbus_read_byte_data(Mpr121::REG_CONFIG2)?;
+                if got != protocol::CONFIG2_RESET_VALUE {
+                    return Err(Mpr121Error::WriteVerifyFailed {
+                        reg: Mpr121::REG_CONFIG2,
+                        expected: protocol::CONFIG2_RESET_VALUE,
+                        got,
+                    });
+                }
+            }
+            InitStage::Thresholds => {
+                // Default touch and release thresholds
+                for i in 0..12 {
+                    self.dev
+                        .smbus_write_byte_data(Mpr121::REG_TOUCHTH_0 + 2 * i, touch)?;
+                    self.dev
+                        .smbus_write_byte_data(Mpr121::REG_RELEASETH_0 + 2 * i, release)?;
+                }
+            }
+            InitStage::Filters => {
+                // Configure baseline filtering control registers.
+                self.dev.smbus_write_byte_data(Mpr121::REG_MHDR, 0x01)?;
+                self.dev.smbus_write_byte_data(Mpr121::REG_NHDR, 0x01)?;
+                self.dev.smbus_write_byte_data(Mpr121::REG_NCLR, 0x0E)?;
+                self.dev.smbus_write_byte_data(Mpr121::REG_FDLR, 0x00)?;
+                self.dev.smbus_write_byte_data(Mpr121::REG_MHDF, 0x01)?;
+                self.dev.smbus_write_byte_data(Mpr121::REG_NHDF, 0x05)?;
+                self.dev.smbus_write_byte_data(Mpr121::REG_NCLF, 0x01)?;
+                self.dev.smbus_write_byte_data(Mpr121::REG_FDLF, 0x00)?;
+                self.dev.smbus_write_byte_data(Mpr121::REG_NHDT, 0x00)?;
+                self.dev.smbus_write_byte_data(Mpr121::REG_NCLT, 0x00)?;
+                self.dev.smbus_write_byte_data(Mpr121::REG_FDLT, 0x00)?;
+                // Set other configuration registers.
+                self.dev.smbus_write_byte_data(Mpr121::REG_DEBOUNCE, 0)?;
+                // default, 16uA charge current
+                self.dev.smbus_write_byte_data(Mpr121::REG_CONFIG1, 0x10)?;
+                // 0.5uS encoding, 1ms period
+                self.dev.smbus_write_byte_data(Mpr121::REG_CONFIG2, 0x20)?;
+            }
+            InitStage::Enable => {
+                // Enable all electrodes.
+                self.write_ecr(protocol::ECR_RUN)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Configures the chip for low power wake on approach: all 12 electrodes
+    /// are combined into a single proximity electrode, touch detection of
+    /// individual electrodes is disabled, electrodes are sampled every 32ms
+    /// and sensitive proximity thresholds are used. Use
+    /// `Mpr121TouchStatus::proximity` as the wake signal.
+    pub fn configure_wake_on_approach(&mut self) -> Result<(), Mpr121Error> {
+        self.config_session(|s| {
+            // Proximity baseline filtering as recommended by AN3893.
+            s.dev.smbus_write_byte_data(Mpr121::REG_MHDPROXR, 0xFF)?;
+            s.dev.smbus_write_byte_data(Mpr121::REG_NHDPROXR, 0xFF)?;
+            s.dev.smbus_write_byte_data(Mpr121::REG_NCLPROXR, 0x00)?;
+            s.dev.smbus_write_byte_data(Mpr121::REG_FDLPROXR, 0x00)?;
+            s.dev.smbus_write_byte_data(Mpr121::REG_MHDPROXF, 0x01)?;
+            s.dev.smbus_write_byte_data(Mpr121::REG_NHDPROXF, 0x01)?;
+            s.dev.smbus_write_byte_data(Mpr121::REG_NCLPROXF, 0xFF)?;
+            s.dev.smbus_write_byte_data(Mpr121::REG_FDLPROXF, 0xFF)?;
+            s.dev.smbus_write_byte_data(Mpr121::REG_NHDPROXT, 0x00)?;
+            s.dev.smbus_write_byte_data(Mpr121::REG_NCLPROXT, 0x00)?;
+            s.dev.smbus_write_byte_data(Mpr121::REG_FDLPROXT, 0x00)?;
+            s.dev.smbus_write_byte_data(Mpr121::REG_PROXTOUCHTH, 4)?;
+            s.dev.smbus_write_byte_data(Mpr121::REG_PROXRELEASETH, 2)?;
+            // 0.5uS encoding, 32ms period
+            s.dev.smbus_write_byte_data(Mpr121::REG_CONFIG2, 0x25)?;
+            // Baseline tracking, ELE0-11 proximity, no touch electrodes.
+            s.write_ecr(0xB0)
+        })
+    }
+
+    /// Re-seeds baselines of enabled electrodes from current filtered data
+    /// without touching thresholds or charge configuration. Electrodes are
+    /// briefly stopped and started again with calibration lock bits set to
+    /// load baseline from 5 MSB of filtered data, then, after
+    /// `TimingPolicy::baseline_reload`, previous electrode configuration is
+    /// restored. Fails with `Mpr121Error::InConfigSession` inside
+    /// `config_session`, where electrodes cannot run.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            skip_all,
+            fields(
+                bus = %self.path.display(),
+                address = self.address,
+                outcome = tracing::field::Empty
+            ),
+            err
+        )
+    )]
+    pub fn recalibrate_baseline(&mut self) -> Result<(), Mpr121Error> {
+        Mpr121::traced(self.reload_baseline())
+    }
+
+    fn reload_baseline(&mut self) -> Result<(), Mpr121Error> {
+        if self.session_ecr.is_some() {
+            return Err(Mpr121Error::InConfigSession);
+        }
+        let ecr = self.read_ecr()?;
+        if ecr & 0x3F == 0 {
+            // Nothing is running, baseline is initialized on next start anyway.
+            return Ok(());
+        }
+        self.stop()?;
+        let reload = ecr & 0x3F | 0x80;
+        self.write_ecr(reload)?;
+        if reload != ecr {
+            // Baseline is loaded on the next sample.
+            self.sleep(self.timing.baseline_reload);
+            self.write_ecr(ecr)?;
+        }
+        Ok(())
+    }
+
+    /// Enables touch detection only for given electrodes. MPR121 can only
+    /// enable a contiguous range of electrodes starting at 0, any other set
+    /// is rejected with `Mpr121Error::NonContiguousElectrodes`.
+    pub fn set_active_electrodes(&mut self, electrodes: &[u8]) -> Result<(), Mpr121Error> {
+        let count = Mpr121::contiguous_count(electrodes)?;
+        let ecr = self.read_ecr()?;
+        self.stop()?;
+        self.write_ecr(ecr & 0xF0 | count)?;
+        Ok(())
+    }
+
+    /// Reads which proximity detection mode is enabled on the chip
+    pub fn proximity_mode(&mut self) -> Result<ProximityMode, Mpr121Error> {
+        Ok(ProximityMode::from_ecr(self.read_ecr()?))
+    }
+
+    /// Runs `f` with all electrodes stopped, as MPR121 only accepts writes to
+    /// most of its configuration registers in stop mode. Electrodes are
+    /// started again when `f` returns, even if it fails.
+    ///
+    /// Sessions nest: inner sessions and other configuration methods called
+    /// from `f` do not stop and start electrodes themselves, so the chip goes
+    /// through a single stop/run transition. Electrodes are started with the
+    /// last configuration requested inside the session, or the previous one.
+    pub fn config_session<T, F>(&mut self, f: F) -> Result<T, Mpr121Error>
+    where
+        F: FnOnce(&mut Self) -> Result<T, Mpr121Error>,
+    {
+        if self.session_ecr.is_some() {
+            return f(self);
+        }
+        let ecr = self.cached_ecr()?;
+        self.write_ecr_now(0x00)?;
+        self.session_ecr = Some(ecr);
+        let result = f(self);
+        let ecr = self.session_ecr.take().unwrap_or(ecr);
+        let restored = self.write_ecr_now(ecr);
+        if result.is_ok() {
+            restored?;
+        }
+        result
+    }
+
+    /// Starts recording configuration changes to be applied at once, see
+    /// `ConfigTxn`
+    pub fn begin_config(&self) -> ConfigTxn {
+        ConfigTxn::new()
+    }
+
+    /// Writes all changes of `txn` inside a single configuration session.
+    /// Registers changed only partially are read first. If any write fails,
+    /// registers written so far are restored to the values read before
+    /// writing and electrodes are started with the previous configuration.
+    pub fn commit_config(&mut self, txn: &ConfigTxn) -> Result<(), Mpr121Error> {
+        self.config_session(|s| {
+            let mut written = Vec::new();
+            let mut result = Ok(());
+            for (reg, mask, value) in txn.writes() {
+                if reg == Mpr121::REG_ECR {
+                    let ecr = s.read_ecr()?;
+                    s.write_ecr(ecr & !mask | value)?;
+                    continue;
+                }
+                let old = match s.dev.smbus_read_byte_data(reg) {
+                    Ok(old) => old,
+                    Err(e) => {
+                        result = Err(e.into());
+                        break;
+                    }
+                };
+                written.push((reg, old));
+                let new = old & !mask | value;
+                result = if txn.verify() {
+                    s.write_verified(reg, new)
+                } else {
+                    s.dev
+                        .smbus_write_byte_data(reg, new)
+                        .map_err(Mpr121Error::from)
+                };
+                if result.is_err() {
+                    break;
+                }
+            }
+            if result.is_err() {
+                for (reg, old) in written.into_iter().rev() {
+                    let _ = s.dev.smbus_write_byte_data(reg, old);
+                }
+            }
+            result
+        })
+    }
+
+    /// Replaces configuration as a whole, e.g. when switching between
+    /// presets: `f` records changes into a `ConfigWriter`, which are then
+    /// written like `commit_config`, in stop mode with a single stop/run
+    /// transition. On any error the registers written so far are restored
+    /// and electrodes run again with the previous configuration, so the chip
+    /// is never left half configured.
+    pub fn reconfigure<F>(&mut self, f: F) -> Result<(), Mpr121Error>
+    where
+        F: FnOnce(&mut ConfigWriter),
+    {
+        let mut writer = ConfigWriter::new();
+        f(&mut writer);
+        self.commit_config(&writer)
+    }
+
+    /// Prepares the chip for streaming filtered data at high rate, e.g. for
+    /// calibration: all 12 electrodes are enabled, sampled every 1ms with the
+    /// shortest second filter, debounce is disabled and baseline tracking is
+    /// frozen so that the baseline does not follow test stimulus. Proximity
+    /// detection and charge time are kept. Does nothing when already
+    /// streaming.
+    pub fn enter_streaming_mode(&mut self) -> Result<(), Mpr121Error> {
+        if self.streaming.is_some() {
+            return Ok(());
+        }
+        self.config_session(|s| {
+            let saved = StreamingSaved {
+                debounce: s.dev.smbus_read_byte_data(Mpr121::REG_DEBOUNCE)?,
+                config2: s.dev.smbus_read_byte_data(Mpr121::REG_CONFIG2)?,
+                ecr: s.read_ecr()?,
+            };
+            s.dev.smbus_write_byte_data(Mpr121::REG_DEBOUNCE, 0x00)?;
+            s.dev
+                .smbus_write_byte_data(Mpr121::REG_CONFIG2, saved.config2 & 0xE0)?;
+            // Baseline tracking disabled (CL = 01), all electrodes enabled
+            s.write_ecr(0x40 | saved.ecr & 0x30 | 0x0C)?;
+            s.streaming = Some(saved);
+            Ok(())
+        })
+    }
+
+    /// Restores debounce, sampling and electrode configuration changed by
+    /// `enter_streaming_mode`. Does nothing when not streaming.
+    pub fn exit_streaming_mode(&mut self) -> Result<(), Mpr121Error> {
+        let saved = match self.streaming {
+            Some(saved) => saved,
+            None => return Ok(()),
+        };
+        self.config_session(|s| {
+            s.dev
+                .smbus_write_byte_data(Mpr121::REG_DEBOUNCE, saved.debounce)?;
+            s.dev
+                .smbus_write_byte_data(Mpr121::REG_CONFIG2, saved.config2)?;
+            s.write_ecr(saved.ecr)?;
+            s.streaming = None;
+            Ok(())
+        })
+    }
+
+    /// Whether the chip was put into streaming mode
+    pub fn is_streaming(&self) -> bool {
+        self.streaming.is_some()
+    }
+
+    /// Suggests sample interval and polling interval meeting `max_latency`
+    /// with the least power, see `PowerConfig::suggest`
+    pub fn suggest_power_config(&self, max_latency: std::time::Duration) -> PowerConfig {
+        PowerConfig::suggest(max_latency)
+    }
+
+    /// Writes sample interval and second filter settings of `config`,
+    /// keeping global charge time. Polling interval is up to the application.
+    pub fn apply_power_config(&mut self, config: &PowerConfig) -> Result<(), Mpr121Error> {
+        self.config_session(|s| {
+            let config2 = s.dev.smbus_read_byte_data(Mpr121::REG_CONFIG2)?;
+            let config2 = config2 & 0xE0 | (config.sfi & 0x03) << 3 | config.esi & 0x07;
+            s.dev.smbus_write_byte_data(Mpr121::REG_CONFIG2, config2)?;
+            Ok(())
+        })
+    }
+
+    /// Blinks all GPIO pins configured as outputs according to `pattern` and
+    /// reports bus path, address and checksum of configuration. Output levels
+    /// are toggled, so pins end up in their original state. Without output
+    /// pins only the report is produced. Blinks are not waited without
+    /// `TimingPolicy::blink`.
+    pub fn identify(&mut self, pattern: BlinkPattern) -> Result<IdentityReport, Mpr121Error> {
+        self.blink_and_report(pattern, |s, d| {
+            if s.timing.blink {
+                s.sleep(d)
+            }
+        })
+    }
+
+    /// Same as `identify`, with `delay` called instead of sleeping
+    pub fn identify_with<F>(
+        &mut self,
+        pattern: BlinkPattern,
+        mut delay: F,
+    ) -> Result<IdentityReport, Mpr121Error>
+    where
+        F: FnMut(std::time::Duration),
+    {
+        self.blink_and_report(pattern, |_, d| delay(d))
+    }
+
+    fn blink_and_report<F>(
+        &mut self,
+        pattern: BlinkPattern,
+        mut delay: F,
+    ) -> Result<IdentityReport, Mpr121Error>
+    where
+        F: FnMut(&Self, std::time::Duration),
+    {
+        let blinked = self.gpio_state()?.outputs();
+        let toggle = (blinked >> GpioState::FIRST_PIN) as u8;
+        if toggle != 0 {
+            for _ in 0..pattern.count {
+                self.dev
+                    .smbus_write_byte_data(Mpr121::REG_GPIOTOGGLE, toggle)?;
+                delay(self, pattern.on);
+                self.dev
+                    .smbus_write_byte_data(Mpr121::REG_GPIOTOGGLE, toggle)?;
+                delay(self, pattern.off);
+            }
+        }
+        Ok(IdentityReport {
+            path: self.path.clone(),
+            address: self.address,
+            config_crc: self.export_config_registers()?.crc32(),
+            blinked,
+        })
+    }
+
+    /// Sets touch and release thresholds of a single electrode
+    pub fn set_threshold(
+        &mut self,
+        electrode: u8,
+        touch: u8,
+        release: u8,
+    ) -> Result<(), Mpr121Error> {
+        Mpr121::check_electrode(electrode)?;
+        self.config_session(|s| {
+            s.dev
+                .smbus_write_byte_data(Mpr121::REG_TOUCHTH_0 + 2 * electrode, touch)?;
+            s.dev
+                .smbus_write_byte_data(Mpr121::REG_RELEASETH_0 + 2 * electrode, release)?;
+            Ok(())
+        })
+    }
+
+    /// Makes electrodes sensitive enough to be touched through an overlay
+    /// (e.g. acrylic panel or gloves) `overlay_um` thick, by lowering
+    /// thresholds and raising global charge current and time, see
+    /// `ConfigTxn::overlay`. Per electrode charge current and time, when set,
+    /// take precedence. This is a starting point for tuning, not a
+    /// calibration.
+    pub fn configure_for_overlay_um(&mut self, overlay_um: u32) -> Result<(), Mpr121Error> {
+        self.commit_config(&ConfigTxn::overlay(overlay_um))
+    }
+
+    /// Same as `configure_for_overlay_um` with thickness in mm, negative
+    /// thickness is taken as no overlay
+    #[cfg(feature = "float")]
+    pub fn configure_for_overlay(&mut self, overlay_mm: f32) -> Result<(), Mpr121Error> {
+        self.configure_for_overlay_um((overlay_mm * 1000.0).max(0.0) as u32)
+    }
+
+    /// Sets touch and release thresholds of electrodes given as
+    /// `(electrode, touch, release)`, leaving the other electrodes alone. All
+    /// electrodes are checked before anything is written, then thresholds are
+    /// written in a single stop/run transition.
+    pub fn set_thresholds_iter<I>(&mut self, items: I) -> Result<(), Mpr121Error>
+    where
+        I: IntoIterator<Item = (u8, u8, u8)>,
+    {
+        let items: Vec<_> = items.into_iter().collect();
+        for (electrode, _, _) in items.iter() {
+            Mpr121::check_electrode(*electrode)?;
+        }
+        if items.is_empty() {
+            return Ok(());
+        }
+        self.config_session(|s| {
+            for (electrode, touch, release) in items {
+                s.dev
+                    .smbus_write_byte_data(Mpr121::REG_TOUCHTH_0 + 2 * electrode, touch)?;
+                s.dev
+                    .smbus_write_byte_data(Mpr121::REG_RELEASETH_0 + 2 * electrode, release)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Same as `set_threshold`, but reads both registers back after writing and
+    /// returns `Mpr121Error::WriteVerifyFailed` if they do not match
+    pub fn set_threshold_verified(
+        &mut self,
+        electrode: u8,
+        touch: u8,
+        release: u8,
+    ) -> Result<(), Mpr121Error> {
+        Mpr121::check_electrode(electrode)?;
+        self.config_session(|s| {
+            s.write_verified(Mpr121::REG_TOUCHTH_0 + 2 * electrode, touch)?;
+            s.write_verified(Mpr121::REG_RELEASETH_0 + 2 * electrode, release)
+        })
+    }
+
+    /// Sets thresholds of every electrode relative to its current baseline,
+    /// see `Mpr121::adaptive_thresholds`. Larger electrodes have higher
+    /// baselines and larger deltas on touch, so this gives similar
+    /// sensitivity to electrodes of mixed sizes. Baselines should be settled,
+    /// enabled electrodes with zero baseline fail with
+    /// `Mpr121Error::NotSettled` and nothing is written.
+    pub fn set_adaptive_thresholds(
+        &mut self,
+        touch_percent: u8,
+        release_percent: u8,
+    ) -> Result<(), Mpr121Error> {
+        self.check_running()?;
+        let enabled = (self.cached_ecr()? & 0x0F).min(12);
+        let data = self.read_block(Mpr121::REG_BASELINE_0, 12)?;
+        let mut baseline = [0; 12];
+        let mut unsettled = 0u16;
+        for (i, (b, d)) in baseline.iter_mut().zip(data.iter()).enumerate() {
+            *b = u16::from(*d) << 2;
+            if *b == 0 && i < usize::from(enabled) {
+                unsettled |= 1 << i;
+            }
+        }
+        if unsettled != 0 {
+            return Err(Mpr121Error::NotSettled {
+                electrodes: unsettled,
+            });
+        }
+        let thresholds = Mpr121::adaptive_thresholds(&baseline, touch_percent, release_percent);
+        self.config_session(|s| {
+            for (i, (touch, release)) in (0u8..).zip(thresholds.iter()) {
+                s.dev
+                    .smbus_write_byte_data(Mpr121::REG_TOUCHTH_0 + 2 * i, *touch)?;
+                s.dev
+                    .smbus_write_byte_data(Mpr121::REG_RELEASETH_0 + 2 * i, *release)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Configures all GPIO pins at once. Masks are indexed by electrode number,
+    /// so only bits 4 to 11 may be set. Outputs are push-pull, inputs are high
+    /// impedance, and pins in neither mask are disabled. Pins used as GPIO
+    /// must not be enabled for touch sensing.
+    pub fn configure_gpio(&mut self, outputs: u16, inputs: u16) -> Result<(), Mpr121Error> {
+        let (dir, en) = GpioState::direction_and_enable(outputs, inputs)?;
+        self.dev.smbus_write_byte_data(Mpr121::REG_GPIOEN, 0x00)?;
+        self.dev.smbus_write_byte_data(Mpr121::REG_GPIOCTL0, 0x00)?;
+        self.dev.smbus_write_byte_data(Mpr121::REG_GPIOCTL1, 0x00)?;
+        self.dev.smbus_write_byte_data(Mpr121::REG_GPIODIR, dir)?;
+        self.dev.smbus_write_byte_data(Mpr121::REG_GPIOEN, en)?;
+        Ok(())
+    }
+
+    /// Reads levels of all GPIO pins in a single read. Result is indexed by
+    /// electrode number like `configure_gpio` masks, so bits 0 to 3 are zero.
+    pub fn gpio_read_all(&mut self) -> Result<u16, Mpr121Error> {
+        let data = self.dev.smbus_read_byte_data(Mpr121::REG_GPIODATA)?;
+        Ok(u16::from(data) << GpioState::FIRST_PIN)
+    }
+
+    /// Reads all GPIO registers and returns decoded mode and level of each
+    /// GPIO capable pin
+    pub fn gpio_state(&mut self) -> Result<GpioState, Mpr121Error> {
+        let en = self.dev.smbus_read_byte_data(Mpr121::REG_GPIOEN)?;
+        let dir = self.dev.smbus_read_byte_data(Mpr121::REG_GPIODIR)?;
+        let ctl0 = self.dev.smbus_read_byte_data(Mpr121::REG_GPIOCTL0)?;
+        let ctl1 = self.dev.smbus_read_byte_data(Mpr121::REG_GPIOCTL1)?;
+        let data = self.dev.smbus_read_byte_data(Mpr121::REG_GPIODATA)?;
+        Ok(GpioState::from_registers(en, dir, ctl0, ctl1, data))
+    }
+
+    /// Sweeps touch threshold of `pin` downwards over `range` to find thresholds
+    /// at which touches are detected reliably. For each threshold the user is
+    /// asked via `prompt` to touch and then to release the electrode, each
+    /// phase lasting `dwell`. Release threshold is kept at half of the touch
+    /// threshold. Original thresholds are restored afterwards.
+    pub fn threshold_sweep<F>(
+        &mut self,
+        pin: u8,
+        range: std::ops::RangeInclusive<u8>,
+        dwell: std::time::Duration,
+        mut prompt: F,
+    ) -> Result<SweepReport, Mpr121Error>
+    where
+        F: FnMut(SweepPhase),
+    {
+        Mpr121::check_electrode(pin)?;
+        let (touch, release) = self.thresholds()?[pin as usize];
+        let mut steps = Vec::new();
+        let sweep = || -> Result<(), Mpr121Error> {
+            for threshold in range.rev() {
+                self.set_threshold(pin, threshold, threshold / 2)?;
+                prompt(SweepPhase::Touch { threshold });
+                let touched = self.sample_pin(pin, dwell)?;
+                prompt(SweepPhase::Release { threshold });
+                self.sample_pin(pin, dwell)?;
+                let released = !self.touch_status()?.touched(pin);
+                steps.push(SweepStep {
+                    threshold,
+                    touch_detected: touched,
+                    release_detected: released,
+                });
+            }
+            Ok(())
+        };
+        let result = sweep();
+        self.set_threshold(pin, touch, release)?;
+        result.map(|_| SweepReport::from_steps(steps))
+    }
+
+    /// Finds out how pads are wired to electrodes. For each logical pad 0 to
+    /// 11 the user is asked via `prompt` to touch it, then the driver waits
+    /// until all electrodes are released and exactly one electrode not
+    /// assigned yet is touched. Returns physical electrode of each logical
+    /// pad. Blocks until all 12 pads were touched.
+    pub fn guided_wiring_check<F>(&mut self, mut prompt: F) -> Result<[u8; 12], Mpr121Error>
+    where
+        F: FnMut(u8),
+    {
+        let poll = self.timing.touch_poll;
+        let mut layout = [0; 12];
+        let mut assigned = 0u16;
+        for (logical, physical) in (0u8..).zip(layout.iter_mut()) {
+            prompt(logical);
+            while self.touch_status()?.was_touched() {
+                self.sleep(poll);
+            }
+            *physical = loop {
+                let status = self.touch_status()?;
+                let mut touched = status.iter().enumerate().filter(|(_, t)| *t);
+                if let (Some((pin, _)), None) = (touched.next(), touched.next()) {
+                    if assigned & 1 << pin == 0 {
+                        break pin as u8;
+                    }
+                }
+                self.sleep(poll);
+            };
+            assigned |= 1 << *physical;
+        }
+        Ok(layout)
+    }
+
+    /// Polls touch status of `pin` for `dwell` and returns if it was touched
+    /// at any time
+    fn sample_pin(&mut self, pin: u8, dwell: std::time::Duration) -> Result<bool, Mpr121Error> {
+        let start = std::time::Instant::now();
+        let mut touched = false;
+        loop {
+            touched |= self.touch_status()?.touched(pin);
+            if start.elapsed() >= dwell {
+                return Ok(touched);
+            }
+            self.sleep(self.timing.touch_poll);
+        }
+    }
+
+    /// Reads 10 bit filtered data of all electrodes in a single block read
+    pub fn filtered_data(&mut self) -> Result<[u16; 12], Mpr121Error> {
+        self.check_running()?;
+        let data = self.read_block(Mpr121::REG_FILTDATA_0L, 24)?;
+        Ok(Mpr121::decode_filtered(&data))
+    }
+
+    /// Reads 10 bit filtered data of electrodes `start` to `end` inclusive in
+    /// a single block read, fails with `InvalidElectrode` unless
+    /// `start <= end <= 11`
+    pub fn filtered_data_range(&mut self, start: u8, end: u8) -> Result<Vec<u16>, Mpr121Error> {
+        Mpr121::check_electrode(end)?;
+        if start > end {
+            return Err(Mpr121Error::InvalidElectrode(start));
+        }
+        self.check_running()?;
+        let count = end - start + 1;
+        let data = self.read_block(Mpr121::REG_FILTDATA_0L + 2 * start, 2 * count)?;
+        Ok(Mpr121::decode_filtered(&data)[..count as usize].to_vec())
+    }
+
+    /// Reads sampling configuration and returns time each electrode is
+    /// sampled after electrode 0 within one scan, e.g. to correct timing
+    /// between electrodes of a swipe, see `Mpr121::sample_offsets`
+    pub fn electrode_sample_offsets(&mut self) -> Result<[std::time::Duration; 12], Mpr121Error> {
+        let config = self.read_block(Mpr121::REG_CONFIG1, 2)?;
+        let mut charge_times = [0; 6];
+        charge_times.copy_from_slice(&self.read_block(Mpr121::REG_CHARGETIME_1, 6)?[..6]);
+        let enabled = self.cached_ecr()? & 0x0F;
+        Ok(Mpr121::sample_offsets(
+            config[0],
+            config[1],
+            &charge_times,
+            enabled,
+        ))
+    }
+
+    /// Samples baseline of `electrode` for `window` and returns its drift in
+    /// milli-counts per second, see `Mpr121::drift_slope`. Samples are taken
+    /// every `TimingPolicy::settle_poll`.
+    pub fn drift_rate_milli(
+        &mut self,
+        electrode: u8,
+        window: std::time::Duration,
+    ) -> Result<i64, Mpr121Error> {
+        Mpr121::check_electrode(electrode)?;
+        self.check_running()?;
+        let start = std::time::Instant::now();
+        let mut samples = Vec::new();
+        loop {
+            let baseline =
+                self.contended(|dev| dev.smbus_read_byte_data(Mpr121::REG_BASELINE_0 + electrode))?;
+            let elapsed = start.elapsed();
+            samples.push((elapsed, u16::from(baseline) << 2));
+            if elapsed >= window {
+                return Ok(Mpr121::drift_slope(&samples));
+            }
+            self.sleep(self.timing.settle_poll.min(window - elapsed));
+        }
+    }
+
+    /// Samples baseline of `electrode` for `window` and returns its drift in
+    /// counts per second, e.g. to recalibrate when temperature drift exceeds
+    /// a limit
+    #[cfg(feature = "float")]
+    pub fn drift_rate(
+        &mut self,
+        electrode: u8,
+        window: std::time::Duration,
+    ) -> Result<f32, Mpr121Error> {
+        Ok(self.drift_rate_milli(electrode, window)? as f32 / 1000.0)
+    }
+
+    /// Reads touch status, filtered data and baselines of all electrodes using
+    /// two block reads
+    pub fn sensor_frame(&mut self) -> Result<SensorFrame, Mpr121Error> {
+        self.check_running()?;
+        // Touch status, out of range status and filtered data are adjacent.
+        let data = self.read_block(Mpr121::REG_TOUCHSTATUS_L, 28)?;
+        let baseline = self.read_block(Mpr121::REG_BASELINE_0, 12)?;
+        let mut frame = SensorFrame::from_registers(&data, &baseline);
+        frame.status = Mpr121::correct_polarity(frame.status, self.inverted);
+        Ok(frame)
+    }
+
+    /// Returns information about all electrodes, read in as few transactions
+    /// as possible
+    pub fn electrodes(&mut self) -> Result<Vec<ElectrodeInfo>, Mpr121Error> {
+        let frame = self.sensor_frame()?;
+        let thresholds = self.thresholds()?;
+        let ecr = self.read_ecr()?;
+        Ok(ElectrodeInfo::from_frame(&frame, &thresholds, ecr))
+    }
+
+    /// Returns enabled electrode pressed hardest, i.e. with the largest delta
+    /// above its touch threshold, or `None` when no delta is above threshold
+    pub fn dominant_electrode(&mut self) -> Result<Option<u8>, Mpr121Error> {
+        Ok(ElectrodeInfo::dominant(&self.electrodes()?))
+    }
+
+    /// Reads everything about all electrodes and formats it as a table with
+    /// baseline, filtered data, delta, thresholds and touch state, handy for
+    /// diagnosing thresholds set wrong relative to actual deltas
+    pub fn calibration_report(&mut self) -> Result<String, Mpr121Error> {
+        Ok(ElectrodeInfo::report(&self.electrodes()?))
+    }
+
+    /// Captures all writable configuration registers, so they can be cloned
+    /// onto another chip with `import_config_registers`
+    pub fn export_config_registers(&mut self) -> Result<ConfigImage, Mpr121Error> {
+        let mut registers = Vec::new();
+        for range in ConfigImage::RANGES.iter() {
+            let mut reg = *range.start();
+            while reg <= *range.end() {
+                let len = (range.end() - reg + 1).min(32);
+                let data = self.read_block(reg, len)?;
+                registers.extend((reg..).zip(data.into_iter().take(len as usize)));
+                reg += len;
+            }
+        }
+        // ECR goes last, so that electrodes start with complete configuration.
+        registers.push((Mpr121::REG_ECR, self.read_ecr()?));
+        Ok(ConfigImage { registers })
+    }
+
+    /// Soft resets the chip and classifies it by the default values of
+    /// registers 0x2B to 0x5E. A marker is written to a threshold register
+    /// before the reset to tell whether the reset took effect. MPR121 has no
+    /// ID register, so this is only a hint. Leaves the chip in reset state,
+    /// so it has to be configured again.
+    pub fn chip_variant(&mut self) -> Result<ChipVariant, Mpr121Error> {
+        self.stop()?;
+        self.dev
+            .smbus_write_byte_data(Mpr121::REG_TOUCHTH_0, Mpr121::RESET_MARKER)?;
+        self.ecr = None;
+        self.streaming = None;
+        self.init_state = InitState::Uninitialized;
+        self.dev
+            .smbus_write_byte_data(Mpr121::REG_SOFTRESET, protocol::SOFT_RESET_COMMAND)?;
+        self.sleep(self.timing.post_reset);
+        let mut registers = self.read_block(Mpr121::REG_MHDR, 32)?;
+        registers.extend(self.read_block(Mpr121::REG_MHDR + 32, 19)?);
+        let ecr = self.read_ecr()?;
+        Ok(Mpr121::classify_reset_state(&registers, ecr))
+    }
+
+    /// Exports configuration registers together with crate version, capture
+    /// time and device coordinates, see `ConfigSnapshot`
+    pub fn snapshot(&mut self) -> Result<ConfigSnapshot, Mpr121Error> {
+        Ok(ConfigSnapshot {
+            image: self.export_config_registers()?,
+            metadata: SnapshotMetadata::new(&self.path, self.address),
+        })
+    }
+
+    /// Writes all registers of a configuration image in stop mode, electrode
+    /// configuration register is written last. Written like `commit_config`:
+    /// if any write fails, registers written so far are restored and
+    /// electrodes are started with the previous configuration.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            skip_all,
+            fields(
+                bus = %self.path.display(),
+                address = self.address,
+                outcome = tracing::field::Empty
+            ),
+            err
+        )
+    )]
+    pub fn import_config_registers(&mut self, image: &ConfigImage) -> Result<(), Mpr121Error> {
+        Mpr121::traced(self.commit_config(&ConfigTxn::from_image(image)))
+    }
+
+    /// Puts the chip into the fixed configuration of
+    /// `ConfigImage::test_config`, so threshold dependent tests against real
+    /// hardware do not depend on state left by previous users of the chip
+    pub fn apply_test_config(&mut self) -> Result<(), Mpr121Error> {
+        self.import_config_registers(&ConfigImage::test_config())
+    }
+
+    /// Estimates capacitance of electrode in pF from its charge current and
+    /// time (per electrode or global) and filtered data, see
+    /// `Mpr121::capacitance_pf`. Supply voltage is set by `set_vdd_mv`.
+    /// Accuracy is limited by the tolerance of charge current and time and
+    /// by parasitic capacitance of wiring, so this is only a ballpark figure,
+    /// e.g. to compare electrode designs. Returns infinity when filtered data
+    /// is zero.
+    #[cfg(feature = "float")]
+    pub fn capacitance_estimate(&mut self, electrode: u8) -> Result<f32, Mpr121Error> {
+        Mpr121::check_electrode(electrode)?;
+        Ok(self.capacitance_estimate_all()?[electrode as usize])
+    }
+
+    /// Same as `capacitance_estimate` for all 12 electrodes
+    #[cfg(feature = "float")]
+    pub fn capacitance_estimate_all(&mut self) -> Result<[f32; 12], Mpr121Error> {
+        let vdd = f32::from(self.vdd_mv) / 1000.0;
+        let mut capacitance = [0.0; 12];
+        for (c, (counts, current, time)) in capacitance.iter_mut().zip(self.charge_readings()?) {
+            *c = Mpr121::capacitance_pf(counts, current, time, vdd);
+        }
+        Ok(capacitance)
+    }
+
+    /// Same as `capacitance_estimate` in fF, see `Mpr121::capacitance_ff`
+    pub fn capacitance_estimate_ff(&mut self, electrode: u8) -> Result<u32, Mpr121Error> {
+        Mpr121::check_electrode(electrode)?;
+        Ok(self.capacitance_estimate_all_ff()?[electrode as usize])
+    }
+
+    /// Same as `capacitance_estimate_ff` for all 12 electrodes
+    pub fn capacitance_estimate_all_ff(&mut self) -> Result<[u32; 12], Mpr121Error> {
+        let mut capacitance = [0; 12];
+        for (c, (counts, current, time)) in capacitance.iter_mut().zip(self.charge_readings()?) {
+            *c = Mpr121::capacitance_ff(counts, current, time, self.vdd_mv);
+        }
+        Ok(capacitance)
+    }
+
+    /// Filtered data with effective charge current and time code of each
+    /// electrode, per electrode settings taking precedence over global ones
+    fn charge_readings(&mut self) -> Result<[(u16, u8, u8); 12], Mpr121Error> {
+        let config = self.read_block(Mpr121::REG_CONFIG1, 2)?;
+        // Charge currents and packed charge times, with proximity electrode
+        // current in between
+        let charge = self.read_block(Mpr121::REG_CHARGECURR_0, 19)?;
+        let time = TuningProfile::unpack_charge_time(&charge[13..19]);
+        let filtered = self.filtered_data()?;
+        let mut readings = [(0, 0, 0); 12];
+        for (i, r) in readings.iter_mut().enumerate() {
+            let current = match charge[i] & 0x3F {
+                0 => config[0] & 0x3F,
+                c => c,
+            };
+            let time = match time[i] {
+                0 => config[1] >> 5,
+                t => t,
+            };
+            *r = (filtered[i], current, time);
+        }
+        Ok(readings)
+    }
+
+    /// Sets supply voltage of the chip in mV used by capacitance estimates,
+    /// 3300 by default
+    pub fn set_vdd_mv(&mut self, vdd_mv: u16) {
+        self.vdd_mv = vdd_mv;
+    }
+
+    /// Reads delta (baseline - filtered data) of `electrode` and scales it to
+    /// 0-255, e.g. for MIDI velocity. Deltas of at least the maximum set by
+    /// `set_velocity_max_delta` give 255, negative deltas give 0.
+    pub fn touch_velocity(&mut self, electrode: u8) -> Result<u8, Mpr121Error> {
+        Mpr121::check_electrode(electrode)?;
+        self.check_running()?;
+        let data = self.read_block(Mpr121::REG_FILTDATA_0L + 2 * electrode, 2)?;
+        let filtered = Mpr121::decode_filtered(&data)[0];
+        let baseline =
+            self.contended(|dev| dev.smbus_read_byte_data(Mpr121::REG_BASELINE_0 + electrode))?;
+        let delta = i32::from(u16::from(baseline) << 2) - i32::from(filtered);
+        Ok(Mpr121::velocity(delta, self.velocity_max_delta))
+    }
+
+    /// Sets delta which `touch_velocity` maps to full velocity 255, 100 by
+    /// default
+    pub fn set_velocity_max_delta(&mut self, max_delta: u16) {
+        self.velocity_max_delta = max_delta.max(1);
+    }
+
+    /// Reads current tuning of the chip, see `apply_profile`
+    pub fn capture_profile(&mut self) -> Result<TuningProfile, Mpr121Error> {
+        let mut profile = TuningProfile {
+            charge_current: [0; 12],
+            charge_time: [0; 12],
+            thresholds: self.thresholds()?,
+            baseline: [0; 12],
+            filters: [0; 11],
+            debounce: 0,
+            config1: 0,
+            config2: 0,
+            ecr: 0,
+        };
+        let baseline = self.read_block(Mpr121::REG_BASELINE_0, 12)?;
+        profile.baseline.copy_from_slice(&baseline[..12]);
+        let filters = self.read_block(Mpr121::REG_MHDR, 11)?;
+        profile.filters.copy_from_slice(&filters[..11]);
+        // Debounce, CONFIG1, CONFIG2 and ECR are adjacent.
+        let config = self.read_block(Mpr121::REG_DEBOUNCE, 4)?;
+        profile.debounce = config[0];
+        profile.config1 = config[1];
+        profile.config2 = config[2];
+        profile.ecr = config[3];
+        let current = self.read_block(Mpr121::REG_CHARGECURR_0, 12)?;
+        for (c, r) in profile.charge_current.iter_mut().zip(current) {
+            *c = r & 0x3F;
+        }
+        let time = self.read_block(Mpr121::REG_CHARGETIME_1, 6)?;
+        profile.charge_time = TuningProfile::unpack_charge_time(&time);
+        Ok(profile)
+    }
+
+    /// Writes complete tuning profile in stop mode and starts electrodes
+    /// again. Calibration lock bits are set to keep baselines from the
+    /// profile instead of reinitializing them from filtered data. Written
+    /// like `commit_config`: if any write fails, registers written so far are
+    /// restored and electrodes are started with the previous configuration.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            skip_all,
+            fields(
+                bus = %self.path.display(),
+                address = self.address,
+                outcome = tracing::field::Empty
+            ),
+            err
+        )
+    )]
+    pub fn apply_profile(&mut self, profile: &TuningProfile) -> Result<(), Mpr121Error> {
+        Mpr121::traced(self.commit_config(&ConfigTxn::from_profile(profile)))
+    }
+
+    /// Describes current configuration of the chip in plain words, see
+    /// `TuningProfile::explain`
+    pub fn explain_current_config(&mut self) -> Result<String, Mpr121Error> {
+        Ok(self.capture_profile()?.explain())
+    }
+
+    /// Compares registers of `expected` image with the chip and returns all
+    /// differences, e.g. to notice another bus master reconfiguring the chip.
+    /// Only registers present in the image are read, see `ConfigImage::subset`
+    /// for cheap audits. With `restore`, drifted registers are rewritten.
+    pub fn audit_config(
+        &mut self,
+        expected: &ConfigImage,
+        restore: bool,
+    ) -> Result<Vec<ConfigDrift>, Mpr121Error> {
+        let mut actual = Vec::with_capacity(expected.registers.len());
+        for (reg, _) in expected.registers.iter() {
+            actual.push((*reg, self.dev.smbus_read_byte_data(*reg)?));
+        }
+        let drift = expected.drift(&ConfigImage { registers: actual });
+        if restore && !drift.is_empty() {
+            let registers = drift.iter().map(|d| (d.register, d.expected)).collect();
+            self.import_config_registers(&ConfigImage { registers })?;
+        }
+        Ok(drift)
+    }
+
+    /// Waits until baselines of enabled electrodes stabilize after reset, so
+    /// that touches are not reported while baseline tracking converges.
+    /// Returns how long it took, or `Mpr121Error::NotSettled` with electrodes
+    /// still changing when `timeout` expires. Uses default `SettleDetector`.
+    pub fn wait_for_baseline_settle(
+        &mut self,
+        timeout: std::time::Duration,
+    ) -> Result<std::time::Duration, Mpr121Error> {
+        let electrodes = (self.cached_ecr()? & 0x0F).min(12);
+        self.wait_for_settle_with(SettleDetector::new(2, 3, electrodes), timeout)
+    }
+
+    /// Same as `wait_for_baseline_settle` with custom detector
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            skip_all,
+            fields(
+                bus = %self.path.display(),
+                address = self.address,
+                outcome = tracing::field::Empty
+            ),
+            err
+        )
+    )]
+    pub fn wait_for_settle_with(
+        &mut self,
+        mut detector: SettleDetector,
+        timeout: std::time::Duration,
+    ) -> Result<std::time::Duration, Mpr121Error> {
+        Mpr121::traced(self.settle_with(&mut detector, timeout))
+    }
+
+    fn settle_with(
+        &mut self,
+        detector: &mut SettleDetector,
+        timeout: std::time::Duration,
+    ) -> Result<std::time::Duration, Mpr121Error> {
+        let start = std::time::Instant::now();
+        loop {
+            if detector.push(&self.sensor_frame()?) {
+                return Ok(start.elapsed());
+            }
+            if start.elapsed() >= timeout {
+                return Err(Mpr121Error::NotSettled {
+                    electrodes: detector.unsettled(),
+                });
+            }
+            self.sleep(self.timing.settle_poll);
+        }
+    }
+
+    /// Reads touch and release thresholds of all electrodes
+    pub fn thresholds(&mut self) -> Result<[(u8, u8); 12], Mpr121Error> {
+        let data = self.read_block(Mpr121::REG_TOUCHTH_0, 24)?;
+        let mut thresholds = [(0, 0); 12];
+        for (t, d) in thresholds.iter_mut().zip(data.chunks(2)) {
+            *t = (d[0], d[1]);
+        }
+        Ok(thresholds)
+    }
+
+    /// Reads the touch status of MPR121. In order to detect if something was really
+    /// touched, old and new status must be compared. Returns
+    /// `Mpr121Error::NotRunning` while electrodes are stopped,
+    /// `Mpr121Error::Uninitialized` or `Mpr121Error::Degraded` until the chip
+    /// is configured (see `state` and `set_allow_stopped_reads`), and `Mpr121Error::CorruptRead` when reserved
+    /// bits of the status are set, in which case the read can be retried.
+    pub fn touch_status(&mut self) -> Result<Mpr121TouchStatus, Mpr121Error> {
+        if self.auto_reconfigure {
+            self.reconfigure_if_reset()?;
+        }
+        self.check_running()?;
+        let status = match self.read_mode {
+            ReadMode::Word => {
+                self.contended(|dev| dev.smbus_read_word_data(Mpr121::REG_TOUCHSTATUS_L))?
+            }
+            ReadMode::Bytes => {
+                let low =
+                    self.contended(|dev| dev.smbus_read_byte_data(Mpr121::REG_TOUCHSTATUS_L))?;
+                let high =
+                    self.contended(|dev| dev.smbus_read_byte_data(Mpr121::REG_TOUCHSTATUS_H))?;
+                u16::from(high) << 8 | u16::from(low)
+            }
+        };
+        let status = Mpr121::validate_status(status)?;
+        if status == 0 && self.stale_data_check && self.peek_ecr()? & 0x3F == 0 {
+            return Err(Mpr121Error::NotReady);
+        }
+        let proximity = self.cached_ecr()? & 0x30 != 0;
+        Ok(Mpr121TouchStatus::with_proximity(
+            Mpr121::correct_polarity(status, self.inverted),
+            proximity,
+        ))
+    }
+
+    /// Reads touch status and returns touch and release events since the
+    /// previous call, the first call compares against nothing touched.
+    /// Changes within blanking window after electrodes were started (see
+    /// `settling_until`) are not reported, they are taken as the new previous
+    /// status instead.
+    pub fn poll_events(&mut self) -> Result<Vec<TouchEvent>, Mpr121Error> {
+        let status = self.touch_status()?;
+        let events = if self.blanking.is_blanked(std::time::Instant::now()) {
+            Vec::new()
+        } else {
+            TouchEvent::diff(&self.previous_status, &status)
+        };
+        self.previous_status = status;
+        Ok(events)
+    }
+
+    /// Returns endless iterator of touch events, polling `poll_events` every
+    /// `poll_interval` and sleeping in between. Errors are yielded as items,
+    /// so the caller decides whether to stop on them.
+    pub fn events(&mut self, poll_interval: std::time::Duration) -> Events<'_, D> {
+        Events {
+            mpr121: self,
+            interval: poll_interval,
+            next: std::time::Instant::now(),
+            pending: std::collections::VecDeque::new(),
+        }
+    }
+
+    /// Reads touch status into `slot` in place, e.g. to ping-pong between
+    /// previous and current status with `std::mem::swap` without any
+    /// allocation or state kept by the driver. `slot` is left untouched on
+    /// error.
+    pub fn update_status(&mut self, slot: &mut Mpr121TouchStatus) -> Result<(), Mpr121Error> {
+        *slot = self.touch_status()?;
+        Ok(())
+    }
+
+    /// Reads touch status of electrodes 0 to 7 only, with a single SMBus byte
+    /// read. It transfers one byte less than the word read used by
+    /// `touch_status` (about 90us less at 100kHz), for the lowest latency
+    /// when only the first eight electrodes are wired. Use
+    /// `Mpr121TouchStatus::from_low_byte` to turn it into a status.
+    pub fn touch_status_low(&mut self) -> Result<u8, Mpr121Error> {
+        self.check_running()?;
+        let low = self.contended(|dev| dev.smbus_read_byte_data(Mpr121::REG_TOUCHSTATUS_L))?;
+        Ok(Mpr121::correct_polarity(u16::from(low), self.inverted) as u8)
+    }
+
+    /// Measures average duration of `iterations` touch status reads, useful
+    /// for picking a realistic polling interval. Returns zero duration when
+    /// `iterations` is zero.
+    pub fn benchmark_read(
+        &mut self,
+        iterations: usize,
+    ) -> Result<std::time::Duration, Mpr121Error> {
+        if iterations == 0 {
+            return Ok(std::time::Duration::from_secs(0));
+        }
+        let start = std::time::Instant::now();
+        for _ in 0..iterations {
+            self.touch_status()?;
+        }
+        let average = start.elapsed().as_nanos() / iterations as u128;
+        Ok(std::time::Duration::from_nanos(average as u64))
+    }
+
+    /// Runs read `op`, retrying it a few times when another master holds the
+    /// bus, see `contention_count`
+    fn contended<T, F>(&mut self, mut op: F) -> Result<T, Mpr121Error>
+    where
+        F: FnMut(&mut D) -> Result<T, LinuxI2CError>,
+    {
+        let mut attempt = 0;
+        loop {
+            match op(&mut self.dev) {
+                Err(e) if crate::error::is_contention(&e) => {
+                    self.contention_events += 1;
+                    if attempt == Mpr121::CONTENTION_RETRIES {
+                        return Err(e.into());
+                    }
+                    attempt += 1;
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(attempt, "bus busy, retrying read");
+                    self.sleep(self.timing.contention_retry);
+                }
+                result => return Ok(result?),
+            }
+        }
+    }
+
+    /// All waiting of the driver goes through here, zero delays do not sleep
+    fn sleep(&self, delay: std::time::Duration) {
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+    }
+
+    /// Re-runs the last reset when the chip stopped electrodes which were
+    /// left running or a previous reset failed, see `set_auto_reconfigure`
+    fn reconfigure_if_reset(&mut self) -> Result<(), Mpr121Error> {
+        let options = match self.last_reset {
+            Some(options) if self.session_ecr.is_none() => options,
+            _ => return Ok(()),
+        };
+        if let InitState::Degraded { .. } = self.init_state {
+            return self.reset_with_options(&options);
+        }
+        let expected = self.ecr;
+        let ecr = self.read_ecr()?;
+        if expected.is_some_and(|e| e & 0x3F != 0) && ecr & 0x3F == 0 {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(ecr, "electrodes stopped unexpectedly, reconfiguring");
+            self.reset_with_options(&options)?;
+        }
+        Ok(())
+    }
+
+    /// Fails with `NotRunning` if neither electrodes nor proximity detection
+    /// are enabled, or the chip is not initialized, unless stopped reads are
+    /// allowed. Chip found running before its first configuration by the
+    /// driver was configured by someone else and counts as initialized.
+    pub(crate) fn check_running(&mut self) -> Result<(), Mpr121Error> {
+        if self.allow_stopped_reads {
+            return Ok(());
+        }
+        match &self.init_state {
+            InitState::Initialized => {}
+            InitState::Uninitialized => {
+                if self.cached_ecr()? & 0x3F == 0 {
+                    return Err(Mpr121Error::Uninitialized);
+                }
+                self.init_state = InitState::Initialized;
+            }
+            InitState::Degraded { reason } => {
+                return Err(Mpr121Error::Degraded {
+                    reason: reason.clone(),
+                })
+            }
+        }
+        if self.cached_ecr()? & 0x3F != 0 {
+            Ok(())
+        } else {
+            Err(Mpr121Error::NotRunning)
+        }
+    }
+
+    pub(crate) fn read_block(&mut self, reg: u8, len: u8) -> Result<Vec<u8>, Mpr121Error> {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(reg, len, "read block");
+        let data = self.contended(|dev| dev.smbus_read_i2c_block_data(reg, len))?;
+        if data.len() < len as usize {
+            return Err(Mpr121Error::ShortRead {
+                reg,
+                expected: len as usize,
+                got: data.len(),
+            });
+        }
+        Ok(data)
+    }
+
+    /// Sets electrode configuration register. Inside a configuration session
+    /// the value is only remembered and written when the session ends. Writes
+    /// of the value the register already holds are skipped, as every stop to
+    /// run transition restarts baseline tracking.
+    fn write_ecr(&mut self, ecr: u8) -> Result<(), Mpr121Error> {
+        if let Some(pending) = self.session_ecr.as_mut() {
+            *pending = ecr;
+            return Ok(());
+        }
+        self.write_ecr_now(ecr)
+    }
+
+    /// Enters stop mode, unless already stopped or inside a configuration
+    /// session (which is always in stop mode)
+    fn stop(&mut self) -> Result<(), Mpr121Error> {
+        if self.session_ecr.is_some() {
+            return Ok(());
+        }
+        self.write_ecr_now(0x00)
+    }
+
+    fn write_ecr_now(&mut self, ecr: u8) -> Result<(), Mpr121Error> {
+        if self.ecr == Some(ecr) {
+            return Ok(());
+        }
+        #[cfg(feature = "tracing")]
+        tracing::trace!(ecr, "write ECR");
+        let starting = ecr & 0x3F != 0 && self.ecr.is_none_or(|old| old & 0x3F == 0);
+        self.ecr = None;
+        self.dev.smbus_write_byte_data(Mpr121::REG_ECR, ecr)?;
+        self.ecr = Some(ecr);
+        if starting {
+            self.blanking.enabled(std::time::Instant::now());
+            if self.init_state == InitState::Uninitialized {
+                self.init_state = InitState::Initialized;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads electrode configuration register and remembers its value. Inside
+    /// a configuration session returns the value to be set when it ends.
+    fn read_ecr(&mut self) -> Result<u8, Mpr121Error> {
+        if let Some(pending) = self.session_ecr {
+            return Ok(pending);
+        }
+        let ecr = self.contended(|dev| dev.smbus_read_byte_data(Mpr121::REG_ECR))?;
+        self.ecr = Some(ecr);
+        Ok(ecr)
+    }
+
+    /// Reads electrode configuration register without caching it, so that
+    /// electrodes stopped by an internal reset stay unexpected, see
+    /// `set_stale_data_check` and `set_auto_reconfigure`
+    fn peek_ecr(&mut self) -> Result<u8, Mpr121Error> {
+        if let Some(pending) = self.session_ecr {
+            return Ok(pending);
+        }
+        self.contended(|dev| dev.smbus_read_byte_data(Mpr121::REG_ECR))
+    }
+
+    /// Returns last known value of electrode configuration register, reading
+    /// it from the chip only when it is not known yet
+    fn cached_ecr(&mut self) -> Result<u8, Mpr121Error> {
+        if let Some(pending) = self.session_ecr {
+            return Ok(pending);
+        }
+        match self.ecr {
+            Some(ecr) => Ok(ecr),
+            None => self.read_ecr(),
+        }
+    }
+
+    fn write_verified(&mut self, reg: u8, value: u8) -> Result<(), Mpr121Error> {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(reg, value, "write verified");
+        self.dev.smbus_write_byte_data(reg, value)?;
+        let got = self.dev.smbus_read_byte_data(reg)?;
+        if got != value {
+            return Err(Mpr121Error::WriteVerifyFailed {
+                reg,
+                expected: value,
+                got,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<D> Mpr121<D>
+where
+    D: I2CDevice<Error = LinuxI2CError> + for<'a> I2CTransfer<'a, Error = LinuxI2CError>,
+{
+    /// Polls without allocating or locking, e.g. from real-time audio
+    /// threads. Without deltas it is a single SMBus word read of the touch
+    /// status. With `out.with_deltas` it is a single combined transfer, a
+    /// write of the register pointer followed by a plain I2C read of
+    /// registers 0x00 to 0x29 after a repeated start, instead of the SMBus
+    /// block reads limited to 32 bytes. Another master cannot move the
+    /// register pointer in between, and on bus contention the whole transfer
+    /// is retried. Stale data check and automatic reconfiguration are not
+    /// done here.
+    pub fn poll_into(&mut self, out: &mut PollOutput) -> Result<(), Mpr121Error> {
+        self.check_running()?;
+        let raw = if out.with_deltas {
+            let mut data = [0u8; 0x2A];
+            self.contended(|dev| {
+                // Messages stay borrowed by `transfer` until dropped, so they
+                // are not dropped at all. Neither owns anything.
+                let mut msgs = std::mem::ManuallyDrop::new([
+                    I2CMessage::write(&[Mpr121::REG_TOUCHSTATUS_L]),
+                    I2CMessage::read(&mut data),
+                ]);
+                dev.transfer(&mut msgs[..]).map(drop)
+            })?;
+            let filtered = &data[Mpr121::REG_FILTDATA_0L as usize..];
+            let baseline = &data[Mpr121::REG_BASELINE_0 as usize..];
+            for (i, delta) in out.deltas.iter_mut().enumerate() {
+                let f = (u16::from(filtered[2 * i + 1]) << 8 | u16::from(filtered[2 * i])) & 0x3FF;
+                *delta = (u16::from(baseline[i]) << 2) as i16 - f as i16;
+            }
+            u16::from_le_bytes([data[0], data[1]])
+        } else {
+            self.contended(|dev| dev.smbus_read_word_data(Mpr121::REG_TOUCHSTATUS_L))?
+        };
+        out.status = Mpr121::correct_polarity(Mpr121::validate_status(raw)?, self.inverted);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "address-registry")]
+mod registry {
+    use crate::Mpr121Error;
+    use std::collections::HashSet;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Mutex, MutexGuard, OnceLock};
+
+    fn claims() -> MutexGuard<'static, HashSet<(PathBuf, u16)>> {
+        static CLAIMS: OnceLock<Mutex<HashSet<(PathBuf, u16)>>> = OnceLock::new();
+        CLAIMS
+            .get_or_init(|| Mutex::new(HashSet::new()))
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Process wide claim of a bus and address, released on drop
+    #[derive(Debug)]
+    pub(crate) struct AddressClaim {
+        key: (PathBuf, u16),
+    }
+
+    impl AddressClaim {
+        pub(crate) fn claim(path: &Path, address: u16) -> Result<Self, Mpr121Error> {
+            let key = (path.to_path_buf(), address);
+            if !claims().insert(key.clone()) {
+                return Err(Mpr121Error::AddressInUse {
+                    path: key.0,
+                    address,
+                });
+            }
+            Ok(Self { key })
+        }
+    }
+
+    impl Drop for AddressClaim {
+        fn drop(&mut self) {
+            claims().remove(&self.key);
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn address_claims() {
+            let path = Path::new("/dev/i2c-test");
+            let claim = AddressClaim::claim(path, 0x5A).unwrap();
+            match AddressClaim::claim(path, 0x5A) {
+                Err(Mpr121Error::AddressInUse { path: p, address }) => {
+                    assert_eq!(p, path);
+                    assert_eq!(address, 0x5A);
+                }
+                _ => panic!("Expected address conflict"),
+            }
+            let other = AddressClaim::claim(path, 0x5B).unwrap();
+            let other_bus = AddressClaim::claim(Path::new("/dev/i2c-test2"), 0x5A).unwrap();
+            drop(claim);
+            let again = AddressClaim::claim(path, 0x5A);
+            assert!(again.is_ok());
+            drop((other, other_bus, again));
+        }
+    }
+}
+
+impl SharedMpr121 {
+    /// Wraps sensor so it can be shared between threads
+    pub fn new(sensor: Mpr121) -> Self {
+        Self {
+            inner: std::sync::Arc::new(std::sync::Mutex::new(sensor)),
+        }
+    }
+
+    /// Locks the sensor for exclusive access, e.g. to run several operations
+    /// without other threads interleaving. A poisoned lock is recovered, as
+    /// the sensor itself has no state that could be left inconsistent.
+    pub fn lock(&self) -> std::sync::MutexGuard<'_, Mpr121> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// See `Mpr121::touch_status`
+    pub fn touch_status(&self) -> Result<Mpr121TouchStatus, Mpr121Error> {
+        self.lock().touch_status()
+    }
+
+    /// See `Mpr121::reset`
+    pub fn reset(&self) -> Result<(), Mpr121Error> {
+        self.lock().reset()
+    }
+
+    /// See `Mpr121::reset_with_thresholds`
+    pub fn reset_with_thresholds(&self, touch: u8, release: u8) -> Result<(), Mpr121Error> {
+        self.lock().reset_with_thresholds(touch, release)
+    }
+
+    /// See `Mpr121::set_threshold`
+    pub fn set_threshold(&self, electrode: u8, touch: u8, release: u8) -> Result<(), Mpr121Error> {
+        self.lock().set_threshold(electrode, touch, release)
+    }
+
+    /// See `Mpr121::thresholds`
+    pub fn thresholds(&self) -> Result<[(u8, u8); 12], Mpr121Error> {
+        self.lock().thresholds()
+    }
+
+    /// See `Mpr121::sensor_frame`
+    pub fn sensor_frame(&self) -> Result<SensorFrame, Mpr121Error> {
+        self.lock().sensor_frame()
+    }
+}
+
+impl TouchSensor for SharedMpr121 {
+    fn touch_status(&mut self) -> Result<Mpr121TouchStatus, Mpr121Error> {
+        SharedMpr121::touch_status(self)
+    }
+
+    fn reset(&mut self) -> Result<(), Mpr121Error> {
+        SharedMpr121::reset(self)
+    }
+}
+
+impl<D: I2CDevice<Error = LinuxI2CError>> TouchSensor for Mpr121<D> {
+    fn touch_status(&mut self) -> Result<Mpr121TouchStatus, Mpr121Error> {
+        Mpr121::touch_status(self)
+    }
+
+    fn reset(&mut self) -> Result<(), Mpr121Error> {
+        Mpr121::reset(self)
+    }
+}
+
+/// File descriptor of the I2C device, e.g. to register it with a poll based
+/// reactor. Reads and writes on it bypass the driver and its caches.
+impl<D: std::os::unix::io::AsRawFd> std::os::unix::io::AsRawFd for Mpr121<D> {
+    fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        self.dev.as_raw_fd()
+    }
+}
+
+impl TouchSensor for NullMpr121 {
+    fn touch_status(&mut self) -> Result<Mpr121TouchStatus, Mpr121Error> {
+        Ok(Mpr121TouchStatus::new(0))
+    }
+
+    fn reset(&mut self) -> Result<(), Mpr121Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn null_sensor() {
+        let mut sensor: Box<dyn TouchSensor> = Box::new(NullMpr121);
+        assert!(sensor.reset().is_ok());
+        let status = sensor.touch_status().unwrap();
+        assert!(!status.was_touched());
+        assert!(status.iter().all(|t| !t));
+    }
+
+    #[test]
+    fn thread_safety() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Mpr121>();
+        assert_send_sync::<SharedMpr121>();
+    }
+
+    #[test]
+    fn active_electrodes() {
+        assert_eq!(Mpr121::contiguous_count(&[]).unwrap(), 0);
+        assert_eq!(Mpr121::contiguous_count(&[0]).unwrap(), 1);
+        assert_eq!(Mpr121::contiguous_count(&[2, 0, 1, 1]).unwrap(), 3);
+        let all: Vec<u8> = (0..12).collect();
+        assert_eq!(Mpr121::contiguous_count(&all).unwrap(), 12);
+        match Mpr121::contiguous_count(&[0, 2]) {
+            Err(Mpr121Error::NonContiguousElectrodes { mask }) => assert_eq!(mask, 0b101),
+            _ => panic!("Expected non contiguous error"),
+        }
+        assert!(Mpr121::contiguous_count(&[1]).is_err());
+        match Mpr121::contiguous_count(&[0, 12]) {
+            Err(Mpr121Error::InvalidElectrode(12)) => {}
+            _ => panic!("Expected invalid electrode error"),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "float")]
+    fn capacitance() {
+        let close = |c: f32, expected: f32| assert!((c - expected).abs() < 0.01, "{}", c);
+        // 16uA for 1us charging to half of 3.3V is ~9.7pF
+        close(Mpr121::capacitance_pf(512, 16, 2, 3.3), 9.697);
+        // Same charge into the same electrode reaches a higher count with
+        // lower supply voltage
+        close(Mpr121::capacitance_pf(640, 16, 2, 2.64), 9.697);
+        // 63uA for 32us charging to 0.7 Vdd, the recommended level, ~873pF
+        close(Mpr121::capacitance_pf(717, 63, 7, 3.3), 872.484);
+        // 1uA for 0.5us charging to full scale of 1.8V is ~0.28pF
+        close(Mpr121::capacitance_pf(1024, 1, 1, 1.8), 0.278);
+        assert_eq!(Mpr121::capacitance_pf(512, 16, 0, 3.3), 0.0);
+        assert!(Mpr121::capacitance_pf(0, 16, 1, 3.3).is_infinite());
+    }
+
+    #[test]
+    fn fixed_point_capacitance() {
+        assert_eq!(Mpr121::capacitance_ff(512, 16, 2, 3300), 9697);
+        assert_eq!(Mpr121::capacitance_ff(640, 16, 2, 2640), 9697);
+        assert_eq!(Mpr121::capacitance_ff(717, 63, 7, 3300), 872484);
+        assert_eq!(Mpr121::capacitance_ff(1024, 1, 1, 1800), 278);
+        assert_eq!(Mpr121::capacitance_ff(512, 16, 0, 3300), 0);
+        assert_eq!(Mpr121::capacitance_ff(0, 16, 1, 3300), u32::MAX);
+        assert_eq!(Mpr121::capacitance_ff(512, 16, 1, 0), u32::MAX);
+        assert_eq!(Mpr121::capacitance_ff(1, 63, 7, 1), u32::MAX);
+
+        // Fixed point stays within rounding error of floating point
+        for counts in (1..1024).step_by(7) {
+            for (current, time, vdd_mv) in [(1, 1, 1800), (16, 2, 3300), (63, 7, 3600)] {
+                let time_us = 0.5 * f64::from(1u32 << (time - 1));
+                let voltage = f64::from(counts) * f64::from(vdd_mv) / 1024.0;
+                let expected = f64::from(current) * time_us / voltage * 1e6;
+                let c = Mpr121::capacitance_ff(counts, current, time, vdd_mv);
+                assert!((f64::from(c) - expected).abs() <= 0.5, "{} {}", c, expected);
+                #[cfg(feature = "float")]
+                {
+                    let pf =
+                        Mpr121::capacitance_pf(counts, current, time, f32::from(vdd_mv) / 1000.0);
+                    let tolerance = 0.5 + f64::from(c) * 1e-6;
+                    assert!(
+                        (f64::from(pf) * 1000.0 - f64::from(c)).abs() <= tolerance,
+                        "{} {}",
+                        pf,
+                        c
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn capacitance_estimate() {
+        let mut charge = ["00"; 19];
+        // Electrode 0 charged by 32uA for 2us, others by global 16uA for 0.5us
+        charge[0] = "20";
+        charge[13] = "03";
+        let reads = format!(
+            "read_i2c_block_data 5c 02 = 10 24\nread_i2c_block_data 5f 13 = {}\n",
+            charge.join(" ")
+        );
+        let filtered = format!("read_i2c_block_data 04 18 = {}\n", ["00 02"; 12].join(" "));
+        let mut session = format!(
+            "mpr121-i2c 1\n{}read_byte_data 5e = 8f\n{}",
+            reads, filtered
+        );
+        if cfg!(feature = "float") {
+            session.push_str(&reads);
+            session.push_str(&filtered);
+        }
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        let capacitance = mpr121.capacitance_estimate_all_ff().unwrap();
+        assert_eq!(capacitance[0], 38788);
+        assert!(capacitance[1..].iter().all(|c| *c == 4848));
+        #[cfg(feature = "float")]
+        {
+            let capacitance = mpr121.capacitance_estimate_all().unwrap();
+            assert!((capacitance[0] - 38.788).abs() < 0.01, "{}", capacitance[0]);
+            for c in capacitance[1..].iter() {
+                assert!((c - 4.848).abs() < 0.01, "{}", c);
+            }
+        }
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn adaptive_thresholds() {
+        let mut baseline = [700; 12];
+        baseline[1] = 300;
+        baseline[2] = 0;
+        baseline[3] = 1020;
+        let thresholds = Mpr121::adaptive_thresholds(&baseline, 2, 1);
+        assert_eq!(thresholds[0], (14, 7));
+        assert_eq!(thresholds[1], (6, 3));
+        assert_eq!(thresholds[2], (1, 0));
+        assert_eq!(thresholds[3], (20, 10));
+        // Saturated and release never reaching touch
+        let thresholds = Mpr121::adaptive_thresholds(&baseline, 50, 60);
+        assert_eq!(thresholds[0], (255, 254));
+        assert_eq!(thresholds[1], (150, 149));
+    }
+
+    #[test]
+    fn stale_data_check() {
+        let mut session = String::from("mpr121-i2c 1\n");
+        session.push_str(&reset_session(12, 6));
+        session.push_str("read_word_data 00 = 00 00\nread_byte_data 5e = 00\n");
+        session.push_str("read_word_data 00 = 00 00\nread_byte_data 5e = 00\n");
+        // Stopped between the reconfigure check and the status read
+        session.push_str("read_byte_data 5e = 8f\n");
+        session.push_str("read_word_data 00 = 00 00\nread_byte_data 5e = 00\n");
+        session.push_str("read_byte_data 5e = 00\n");
+        session.push_str(&reset_session(12, 6));
+        session.push_str("read_word_data 00 = 01 00\n");
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        mpr121.set_timing_policy(TimingPolicy::zero());
+        mpr121.set_stale_data_check(true);
+        mpr121.reset().unwrap();
+        for _ in 0..2 {
+            assert!(matches!(mpr121.touch_status(), Err(Mpr121Error::NotReady)));
+        }
+        mpr121.set_auto_reconfigure(true);
+        assert!(matches!(mpr121.touch_status(), Err(Mpr121Error::NotReady)));
+        // Stop is still unexpected, so the chip is reconfigured
+        assert!(mpr121.touch_status().unwrap().touched(0));
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn set_adaptive_thresholds() {
+        let mut session = String::from("mpr121-i2c 1\n");
+        session.push_str(&reset_session(12, 6));
+        // Baseline of electrode 2 not tracked yet, 700 elsewhere
+        let mut baseline = ["af"; 12];
+        baseline[2] = "00";
+        session.push_str(&format!(
+            "read_i2c_block_data 1e 0c = {}\n",
+            baseline.join(" ")
+        ));
+        baseline[2] = "4b";
+        session.push_str(&format!(
+            "read_i2c_block_data 1e 0c = {}\n",
+            baseline.join(" ")
+        ));
+        session.push_str("write_byte_data 5e 00 =\n");
+        for i in 0..12 {
+            let (touch, release) = if i == 2 { (6, 3) } else { (14, 7) };
+            session.push_str(&format!(
+                "write_byte_data {:02x} {:02x} =\nwrite_byte_data {:02x} {:02x} =\n",
+                0x41 + 2 * i,
+                touch,
+                0x42 + 2 * i,
+                release
+            ));
+        }
+        session.push_str("write_byte_data 5e 8f =\n");
+        session
+            .push_str("read_byte_data 5e = 8f\nwrite_byte_data 5e 00 =\nwrite_byte_data 5e 80 =\n");
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        mpr121.set_timing_policy(TimingPolicy::zero());
+        mpr121.reset().unwrap();
+        match mpr121.set_adaptive_thresholds(2, 1) {
+            Err(Mpr121Error::NotSettled { electrodes }) => assert_eq!(electrodes, 0x004),
+            r => panic!("unexpected {:?}", r),
+        }
+        mpr121.set_adaptive_thresholds(2, 1).unwrap();
+        // Stopped electrodes have no baseline to scale, nothing is read
+        mpr121.set_active_electrodes(&[]).unwrap();
+        assert!(matches!(
+            mpr121.set_adaptive_thresholds(2, 1),
+            Err(Mpr121Error::NotRunning)
+        ));
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    /// Transactions of `Mpr121::reset_with_thresholds(touch, release)`
+    fn reset_session(touch: u8, release: u8) -> String {
+        let mut session = String::from("write_byte_data 80 63 =\nwrite_byte_data 5e 00 =\n");
+        session.push_str("read_byte_data 5d = 24\n");
+        for i in 0..12 {
+            session.push_str(&format!(
+                "write_byte_data {:02x} {:02x} =\n",
+                0x41 + 2 * i,
+                touch
+            ));
+            session.push_str(&format!(
+                "write_byte_data {:02x} {:02x} =\n",
+                0x42 + 2 * i,
+                release
+            ));
+        }
+        let registers = [
+            (0x2B, 0x01),
+            (0x2C, 0x01),
+            (0x2D, 0x0E),
+            (0x2E, 0x00),
+            (0x2F, 0x01),
+            (0x30, 0x05),
+            (0x31, 0x01),
+            (0x32, 0x00),
+            (0x33, 0x00),
+            (0x34, 0x00),
+            (0x35, 0x00),
+            (0x5B, 0x00),
+            (0x5C, 0x10),
+            (0x5D, 0x20),
+            (0x5E, 0x8F),
+        ];
+        for (reg, value) in registers {
+            session.push_str(&format!("write_byte_data {:02x} {:02x} =\n", reg, value));
+        }
+        session
+    }
+
+    #[test]
+    fn auto_reconfigure() {
+        let mut session = String::from("mpr121-i2c 1\n");
+        // Disabled by default
+        session.push_str(&reset_session(20, 10));
+        session.push_str("read_word_data 00 = 01 00\n");
+        // Running chip is only checked
+        session.push_str("read_byte_data 5e = 8f\nread_word_data 00 = 02 00\n");
+        // Brownout, the same reset is repeated before reading the status
+        session.push_str("read_byte_data 5e = 00\n");
+        session.push_str(&reset_session(20, 10));
+        session.push_str("read_word_data 00 = 04 00\n");
+        // Deliberately stopped electrodes are left alone
+        session.push_str("write_byte_data 5e 00 =\nread_byte_data 5e = 00\n");
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        mpr121.reset_with_thresholds(20, 10).unwrap();
+        assert!(mpr121.touch_status().unwrap().touched(0));
+        mpr121.set_auto_reconfigure(true);
+        assert!(mpr121.touch_status().unwrap().touched(1));
+        assert!(mpr121.touch_status().unwrap().touched(2));
+        mpr121.stop().unwrap();
+        assert!(matches!(
+            mpr121.touch_status(),
+            Err(Mpr121Error::NotRunning)
+        ));
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    /// Counts heap allocations of the current thread, for `poll_into`
+    struct CountingAllocator;
+
+    std::thread_local! {
+        static ALLOCATIONS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+    }
+
+    unsafe impl std::alloc::GlobalAlloc for CountingAllocator {
+        unsafe fn alloc(&self, layout: std::alloc::Layout) -> *mut u8 {
+            let _ = ALLOCATIONS.try_with(|a| a.set(a.get() + 1));
+            std::alloc::System.alloc(layout)
+        }
+
+        unsafe fn dealloc(&self, ptr: *mut u8, layout: std::alloc::Layout) {
+            std::alloc::System.dealloc(ptr, layout)
+        }
+    }
+
+    #[global_allocator]
+    static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+    /// Registers of a chip with auto-incrementing register pointer, without
+    /// any allocation on the read paths used by `poll_into`
+    struct RegisterFile {
+        registers: [u8; 256],
+        pointer: u8,
+        fail_write: Option<u8>,
+        /// Combined transfers, each counted once
+        transfers: usize,
+    }
+
+    impl RegisterFile {
+        fn new(registers: [u8; 256]) -> Self {
+            RegisterFile {
+                registers,
+                pointer: 0,
+                fail_write: None,
+                transfers: 0,
+            }
+        }
+
+        fn with_image(image: &ConfigImage) -> Self {
+            let mut registers = [0u8; 256];
+            for (reg, value) in image.registers.iter() {
+                registers[*reg as usize] = *value;
+            }
+            RegisterFile::new(registers)
+        }
+    }
+
+    impl I2CDevice for RegisterFile {
+        type Error = LinuxI2CError;
+
+        fn read(&mut self, data: &mut [u8]) -> Result<(), Self::Error> {
+            for d in data.iter_mut() {
+                *d = self.registers[self.pointer as usize];
+                self.pointer = self.pointer.wrapping_add(1);
+            }
+            Ok(())
+        }
+
+        fn write(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+            self.pointer = data[0];
+            for d in &data[1..] {
+                self.registers[self.pointer as usize] = *d;
+                self.pointer = self.pointer.wrapping_add(1);
+            }
+            Ok(())
+        }
+
+        fn smbus_write_quick(&mut self, _bit: bool) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn smbus_write_byte_data(&mut self, register: u8, value: u8) -> Result<(), Self::Error> {
+            if self.fail_write == Some(register) {
+                return Err(std::io::Error::from_raw_os_error(121).into());
+            }
+            self.registers[register as usize] = value;
+            Ok(())
+        }
+
+        fn smbus_read_byte_data(&mut self, register: u8) -> Result<u8, Self::Error> {
+            Ok(self.registers[register as usize])
+        }
+
+        fn smbus_read_word_data(&mut self, register: u8) -> Result<u16, Self::Error> {
+            let r = register as usize;
+            Ok(u16::from_le_bytes([
+                self.registers[r],
+                self.registers[r + 1],
+            ]))
+        }
+
+        fn smbus_read_block_data(&mut self, register: u8) -> Result<Vec<u8>, Self::Error> {
+            self.smbus_read_i2c_block_data(register, 32)
+        }
+
+        fn smbus_read_i2c_block_data(
+            &mut self,
+            register: u8,
+            len: u8,
+        ) -> Result<Vec<u8>, Self::Error> {
+            let r = register as usize;
+            Ok(self.registers[r..r + len as usize].to_vec())
+        }
+
+        fn smbus_write_block_data(
+            &mut self,
+            register: u8,
+            values: &[u8],
+        ) -> Result<(), Self::Error> {
+            self.smbus_write_i2c_block_data(register, values)
+        }
+
+        fn smbus_write_i2c_block_data(
+            &mut self,
+            register: u8,
+            values: &[u8],
+        ) -> Result<(), Self::Error> {
+            let r = register as usize;
+            self.registers[r..r + values.len()].copy_from_slice(values);
+            Ok(())
+        }
+
+        fn smbus_process_block(
+            &mut self,
+            register: u8,
+            _values: &[u8],
+        ) -> Result<Vec<u8>, Self::Error> {
+            self.smbus_read_block_data(register)
+        }
+    }
+
+    impl<'a> I2CTransfer<'a> for RegisterFile {
+        type Error = LinuxI2CError;
+        type Message = crate::bus::I2cMessage<'a>;
+
+        fn transfer(&mut self, msgs: &'a mut [Self::Message]) -> Result<u32, Self::Error> {
+            self.transfers += 1;
+            for msg in msgs.iter_mut() {
+                match msg {
+                    crate::bus::I2cMessage::Read(data) => self.read(data)?,
+                    crate::bus::I2cMessage::Write(data) => self.write(data)?,
+                }
+            }
+            Ok(msgs.len() as u32)
+        }
+    }
+
+    #[test]
+    fn poll_into() {
+        let mut registers = [0u8; 256];
+        registers[0x00] = 0b0000_0101;
+        // Filtered data of electrode 2 and 11 (300 and 512), baselines 312
+        // and 504
+        registers[0x08] = 0x2C;
+        registers[0x09] = 0xFD;
+        registers[0x1A] = 0x00;
+        registers[0x1B] = 0x02;
+        registers[0x20] = 0x4E;
+        registers[0x29] = 0x7E;
+        registers[0x5E] = 0x8F;
+        let mut mpr121 = Mpr121::from_device(RegisterFile::new(registers), "registers", 0x5A);
+        mpr121.set_pin_inverted(0, true).unwrap();
+        let mut out = PollOutput::new(false);
+        let mut with_deltas = PollOutput::new(true);
+        // First poll reads and caches electrode configuration
+        mpr121.poll_into(&mut out).unwrap();
+
+        let before = ALLOCATIONS.with(|a| a.get());
+        for _ in 0..10 {
+            mpr121.poll_into(&mut out).unwrap();
+            mpr121.poll_into(&mut with_deltas).unwrap();
+        }
+        assert_eq!(ALLOCATIONS.with(|a| a.get()), before);
+        // Deltas are read in one transfer with a repeated start
+        assert_eq!(mpr121.dev.transfers, 10);
+
+        assert_eq!(out.status, 0b0100);
+        assert_eq!(out.deltas, [0; 12]);
+        assert_eq!(with_deltas.status, 0b0100);
+        assert_eq!(with_deltas.deltas[2], 12);
+        assert_eq!(with_deltas.deltas[11], -8);
+        assert_eq!(with_deltas.deltas[0], 0);
+    }
+
+    #[test]
+    fn identify() {
+        let mut registers = [0; 256];
+        // Pins 4 and 5 push-pull outputs
+        registers[Mpr121::REG_GPIOEN as usize] = 0x03;
+        registers[Mpr121::REG_GPIODIR as usize] = 0x03;
+        registers[Mpr121::REG_ECR as usize] = 0x8F;
+        let mut mpr121 = Mpr121::from_device(RegisterFile::new(registers), "registers", 0x5A);
+        let pattern = BlinkPattern {
+            count: 2,
+            on: std::time::Duration::from_secs(1),
+            off: std::time::Duration::from_secs(2),
+        };
+        let mut delays = Vec::new();
+        let report = mpr121.identify_with(pattern, |d| delays.push(d)).unwrap();
+        assert_eq!(report.blinked, 0x0030);
+        assert_eq!(delays.len(), 4);
+        assert_eq!(delays[0], pattern.on);
+        assert_eq!(delays[1], pattern.off);
+
+        // Zero policy does not wait for blinks
+        mpr121.set_timing_policy(TimingPolicy::zero());
+        let start = std::time::Instant::now();
+        assert_eq!(mpr121.identify(pattern).unwrap(), report);
+        assert!(start.elapsed() < pattern.on);
+    }
+
+    #[test]
+    fn import_config_registers() {
+        let image = ConfigImage::test_config();
+        let mut source = Mpr121::from_device(RegisterFile::with_image(&image), "src", 0x5A);
+        let exported = source.export_config_registers().unwrap();
+        assert_eq!(exported, image);
+
+        // Round trip onto a chip in a different configuration
+        let mut other = ConfigImage::test_config();
+        other.registers.iter_mut().for_each(|(_, v)| *v ^= 0x11);
+        let dev = RegisterFile::with_image(&other);
+        let mut target = Mpr121::from_device(dev, "dst", 0x5A);
+        target.import_config_registers(&exported).unwrap();
+        let imported = target.export_config_registers().unwrap();
+        assert!(exported.drift(&imported).is_empty());
+
+        // Failed write keeps the previous configuration, electrodes running
+        let mut dev = RegisterFile::with_image(&other);
+        dev.fail_write = Some(0x60);
+        let mut target = Mpr121::from_device(dev, "dst", 0x5A);
+        assert!(target.import_config_registers(&exported).is_err());
+        let mut dev = target.into_device();
+        dev.fail_write = None;
+        let mut target = Mpr121::from_device(dev, "dst", 0x5A);
+        assert!(other
+            .drift(&target.export_config_registers().unwrap())
+            .is_empty());
+    }
+
+    #[test]
+    fn apply_profile() {
+        let image = ConfigImage::test_config();
+        let mut dev = RegisterFile::with_image(&image);
+        for (i, b) in dev.registers[0x1E..0x2B].iter_mut().enumerate() {
+            *b = 40 + i as u8;
+        }
+        let mut source = Mpr121::from_device(dev, "src", 0x5A);
+        let profile = source.capture_profile().unwrap();
+
+        let mut other = image.clone();
+        other.registers.iter_mut().for_each(|(_, v)| *v ^= 0x11);
+        let mut target = Mpr121::from_device(RegisterFile::with_image(&other), "dst", 0x5A);
+        let previous = target.capture_profile().unwrap();
+        target.apply_profile(&profile).unwrap();
+        // Calibration lock bits are cleared to keep the baselines
+        let expected = TuningProfile {
+            ecr: profile.ecr & 0x3F,
+            ..profile
+        };
+        assert_eq!(target.capture_profile().unwrap(), expected);
+
+        // Failed write keeps the previous tuning, electrodes running
+        let mut dev = RegisterFile::with_image(&other);
+        dev.fail_write = Some(Mpr121::REG_CONFIG1);
+        let mut target = Mpr121::from_device(dev, "dst", 0x5A);
+        assert!(target.apply_profile(&profile).is_err());
+        let mut dev = target.into_device();
+        dev.fail_write = None;
+        let mut target = Mpr121::from_device(dev, "dst", 0x5A);
+        assert_eq!(target.capture_profile().unwrap(), previous);
+    }
+
+    #[test]
+    fn scan_bus() {
+        let sessions = [
+            // Just reset
+            "read_byte_data 5d = 24\n",
+            // Already open
+            "",
+            // Another kind of device
+            "read_byte_data 5d = ff\nread_byte_data 01 = ff\n",
+            // Configured and running
+            "read_byte_data 5d = 20\nread_byte_data 01 = 90\n",
+        ];
+        let found = Mpr121::scan_with(|addr| {
+            let session = sessions[(addr - protocol::ADDRESS_FIRST) as usize];
+            if session.is_empty() {
+                return Err(Mpr121Error::AddressInUse {
+                    path: "replay".into(),
+                    address: addr,
+                });
+            }
+            let session = format!("mpr121-i2c 1\n{}", session);
+            let replay = crate::bus::I2cReplay::new(std::io::Cursor::new(session)).unwrap();
+            Ok(Mpr121::from_device(replay, "replay", addr))
+        });
+        let addresses: Vec<_> = found.iter().map(|(addr, _)| *addr).collect();
+        assert_eq!(addresses, [0x5A, 0x5D]);
+        for (addr, mpr121) in found {
+            assert_eq!(mpr121.address(), addr);
+            assert_eq!(mpr121.into_device().remaining(), 0);
+        }
+
+        // Not acknowledged
+        let session = "mpr121-i2c 1\nread_byte_data 5d ! Remote I/O error (os error 121)\n";
+        let mut replay = Some(crate::bus::I2cReplay::new(session.as_bytes()).unwrap());
+        let found = Mpr121::scan_with(|addr| match replay.take() {
+            Some(replay) => Ok(Mpr121::from_device(replay, "replay", addr)),
+            None => Err(Mpr121Error::NotReady),
+        });
+        assert!(found.is_empty());
+    }
+
+    #[test]
+    fn poll_into_contention() {
+        let data: Vec<String> = (0..0x2A).map(|_| String::from("00")).collect();
+        let session = format!(
+            "mpr121-i2c 1
+read_byte_data 5e = 8f
+write_read 00 2a ! Device or resource busy (os error 16)
+write_read 00 2a = {}
+",
+            data.join(" ")
+        );
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        mpr121.set_timing_policy(TimingPolicy::zero());
+        let mut out = PollOutput::new(true);
+        // Whole transfer is retried, register pointer included
+        mpr121.poll_into(&mut out).unwrap();
+        assert_eq!(out.status, 0);
+        assert_eq!(mpr121.contention_count(), 1);
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn read_mode() {
+        let session = "mpr121-i2c 1
+read_byte_data 5e = 8f
+read_word_data 00 = 05 01
+read_byte_data 00 = 05
+read_byte_data 01 = 01
+";
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        let word: Vec<bool> = mpr121.touch_status().unwrap().iter().collect();
+        mpr121.set_read_mode(ReadMode::Bytes);
+        let bytes: Vec<bool> = mpr121.touch_status().unwrap().iter().collect();
+        assert_eq!(bytes, word);
+        let touched: Vec<usize> = (0..12).filter(|i| word[*i]).collect();
+        assert_eq!(touched, [0, 2, 8]);
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn set_threshold_verified() {
+        let session = "mpr121-i2c 1
+read_byte_data 5e = 8f
+write_byte_data 5e 00 =
+write_byte_data 47 14 =
+read_byte_data 47 = 14
+write_byte_data 48 0a =
+read_byte_data 48 = 0a
+write_byte_data 5e 8f =
+write_byte_data 5e 00 =
+write_byte_data 47 18 =
+read_byte_data 47 = 18
+write_byte_data 48 0c =
+read_byte_data 48 = 08
+write_byte_data 5e 8f =
+";
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        mpr121.set_threshold_verified(3, 20, 10).unwrap();
+        // Read back differs, electrodes are started again anyway
+        match mpr121.set_threshold_verified(3, 24, 12) {
+            Err(Mpr121Error::WriteVerifyFailed { reg, expected, got }) => {
+                assert_eq!((reg, expected, got), (0x48, 0x0C, 0x08))
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(mpr121.set_threshold_verified(12, 1, 1).is_err());
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn configure_gpio() {
+        let session = "mpr121-i2c 1
+write_byte_data 77 00 =
+write_byte_data 73 00 =
+write_byte_data 74 00 =
+write_byte_data 76 03 =
+write_byte_data 77 c3 =
+";
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        // Outputs 4 and 5, inputs 10 and 11
+        mpr121.configure_gpio(0x0030, 0x0C00).unwrap();
+        // Invalid masks are rejected before any write
+        assert!(mpr121.configure_gpio(0x0001, 0).is_err());
+        assert!(mpr121.configure_gpio(0x0010, 0x0010).is_err());
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn gpio_read_all() {
+        let session = "mpr121-i2c 1
+read_byte_data 75 = a5
+read_byte_data 75 = 00
+";
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        // Bit 0 of the data register is pin 4
+        assert_eq!(mpr121.gpio_read_all().unwrap(), 0x0A50);
+        assert_eq!(mpr121.gpio_read_all().unwrap(), 0);
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn touch_status_low() {
+        let mut session = String::from("mpr121-i2c 1\n");
+        session.push_str(&reset_session(12, 6));
+        let reset_len = session.len();
+        session.push_str("read_byte_data 00 = 81\n");
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        mpr121.set_timing_policy(TimingPolicy::zero());
+        mpr121.reset().unwrap();
+        assert_eq!(session[reset_len..].lines().count(), 1);
+        mpr121.set_pin_inverted(1, true).unwrap();
+        mpr121.set_pin_inverted(9, true).unwrap();
+        // Electrode 1 reads inverted, electrode 9 is not part of the byte
+        assert_eq!(mpr121.touch_status_low().unwrap(), 0x83);
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn update_status() {
+        let mut session = String::from("mpr121-i2c 1\n");
+        session.push_str(&reset_session(12, 6));
+        session.push_str("read_word_data 00 = 05 00\n");
+        session.push_str("read_word_data 00 ! Remote I/O error (os error 121)\n");
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        mpr121.set_timing_policy(TimingPolicy::zero());
+        mpr121.reset().unwrap();
+        let mut slot = Mpr121TouchStatus::new(0);
+        mpr121.update_status(&mut slot).unwrap();
+        assert!(slot.touched(0) && slot.touched(2));
+        // Slot keeps the previous status on error
+        assert!(mpr121.update_status(&mut slot).is_err());
+        assert_eq!(slot.touched_count(), 2);
+        assert!(slot.touched(0) && slot.touched(2));
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn stopped_reads() {
+        let bytes = |n: usize, v: &str| vec![v; n].join(" ");
+        let mut session = String::from("mpr121-i2c 1\n");
+        session.push_str(&reset_session(12, 6));
+        session
+            .push_str("read_byte_data 5e = 8f\nwrite_byte_data 5e 00 =\nwrite_byte_data 5e 80 =\n");
+        session.push_str(&format!(
+            "read_i2c_block_data 04 18 = {}\n",
+            bytes(24, "00")
+        ));
+        session.push_str(&format!(
+            "read_i2c_block_data 00 1c = {}\n",
+            bytes(28, "00")
+        ));
+        session.push_str(&format!(
+            "read_i2c_block_data 1e 0c = {}\n",
+            bytes(12, "40")
+        ));
+        session.push_str("read_byte_data 1e = 40\n");
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        mpr121.set_timing_policy(TimingPolicy::zero());
+        mpr121.reset().unwrap();
+        // Electrodes stopped, ECR 0x80
+        mpr121.set_active_electrodes(&[]).unwrap();
+        let window = std::time::Duration::from_secs(0);
+        assert!(matches!(
+            mpr121.filtered_data(),
+            Err(Mpr121Error::NotRunning)
+        ));
+        assert!(matches!(
+            mpr121.sensor_frame(),
+            Err(Mpr121Error::NotRunning)
+        ));
+        assert!(matches!(
+            mpr121.drift_rate_milli(0, window),
+            Err(Mpr121Error::NotRunning)
+        ));
+
+        mpr121.set_allow_stopped_reads(true);
+        assert_eq!(mpr121.filtered_data().unwrap(), [0; 12]);
+        assert_eq!(mpr121.sensor_frame().unwrap().baseline, [0x100; 12]);
+        assert_eq!(mpr121.drift_rate_milli(0, window).unwrap(), 0);
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn streaming_mode() {
+        let session = "mpr121-i2c 1
+read_byte_data 5e = 96
+write_byte_data 5e 00 =
+read_byte_data 5b = 21
+read_byte_data 5d = 44
+write_byte_data 5b 00 =
+write_byte_data 5d 40 =
+write_byte_data 5e 5c =
+write_byte_data 5e 00 =
+write_byte_data 5b 21 =
+write_byte_data 5d 44 =
+write_byte_data 5e 96 =
+";
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        // Proximity of ELE0-1 is kept, baseline tracking frozen
+        mpr121.enter_streaming_mode().unwrap();
+        mpr121.enter_streaming_mode().unwrap();
+        mpr121.exit_streaming_mode().unwrap();
+        mpr121.exit_streaming_mode().unwrap();
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn wake_on_approach() {
+        let session = "mpr121-i2c 1
+read_byte_data 5e = 8f
+write_byte_data 5e 00 =
+write_byte_data 36 ff =
+write_byte_data 37 ff =
+write_byte_data 38 00 =
+write_byte_data 39 00 =
+write_byte_data 3a 01 =
+write_byte_data 3b 01 =
+write_byte_data 3c ff =
+write_byte_data 3d ff =
+write_byte_data 3e 00 =
+write_byte_data 3f 00 =
+write_byte_data 40 00 =
+write_byte_data 59 04 =
+write_byte_data 5a 02 =
+write_byte_data 5d 25 =
+write_byte_data 5e b0 =
+read_byte_data 5e = b0
+";
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        mpr121.configure_wake_on_approach().unwrap();
+        assert_eq!(
+            mpr121.proximity_mode().unwrap(),
+            ProximityMode::Electrodes0To11
+        );
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn recalibrate_baseline() {
+        let session = "mpr121-i2c 1
+read_byte_data 5e = 4c
+write_byte_data 5e 00 =
+write_byte_data 5e 8c =
+write_byte_data 5e 4c =
+read_byte_data 5e = 8c
+write_byte_data 5e 00 =
+write_byte_data 5e 8c =
+write_byte_data 5e 00 =
+write_byte_data 5e 8c =
+read_byte_data 5e = 00
+";
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        let mut timing = TimingPolicy::zero();
+        timing.baseline_reload = std::time::Duration::from_millis(2);
+        mpr121.set_timing_policy(timing);
+        // Frozen baseline is reloaded, then frozen again
+        let start = std::time::Instant::now();
+        mpr121.recalibrate_baseline().unwrap();
+        assert!(start.elapsed() >= timing.baseline_reload);
+        // Already reloading on start, nothing to restore
+        mpr121.recalibrate_baseline().unwrap();
+        assert!(matches!(
+            mpr121.config_session(|s| s.recalibrate_baseline()),
+            Err(Mpr121Error::InConfigSession)
+        ));
+        // Stopped electrodes are left alone
+        mpr121.recalibrate_baseline().unwrap();
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn nested_config_session() {
+        let mut session = String::from("mpr121-i2c 1\n");
+        session.push_str(&reset_session(12, 6));
+        session.push_str("write_byte_data 5e 00 =\n");
+        session.push_str("write_byte_data 41 14 =\nwrite_byte_data 42 0a =\n");
+        session.push_str("write_byte_data 5e 84 =\n");
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        mpr121.set_timing_policy(TimingPolicy::zero());
+        mpr121.reset().unwrap();
+        mpr121
+            .config_session(|s| {
+                s.config_session(|s| s.set_threshold(0, 20, 10))?;
+                s.set_active_electrodes(&[0, 1, 2, 3])
+            })
+            .unwrap();
+        assert_eq!(mpr121.cached_ecr().unwrap(), 0x84);
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn unchanged_ecr_write() {
+        let mut session = String::from("mpr121-i2c 1\n");
+        session.push_str(&reset_session(12, 6));
+        session.push_str("write_byte_data 5e 80 =\n");
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        mpr121.set_timing_policy(TimingPolicy::zero());
+        mpr121.reset().unwrap();
+        mpr121.write_ecr(0x8F).unwrap();
+        mpr121.write_ecr(0x80).unwrap();
+        mpr121.write_ecr(0x80).unwrap();
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn ecr_after_soft_reset() {
+        let mut session = String::from("mpr121-i2c 1\n");
+        session.push_str(&reset_session(12, 6));
+        session.push_str("write_byte_data 5e 00 =\n");
+        // Cached stop mode is not trusted after the soft reset
+        session.push_str(&reset_session(12, 6));
+        // Nor is cached run mode
+        session.push_str(&reset_session(12, 6));
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        mpr121.set_timing_policy(TimingPolicy::zero());
+        mpr121.reset().unwrap();
+        mpr121.write_ecr(0x00).unwrap();
+        mpr121.reset().unwrap();
+        mpr121.reset().unwrap();
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn failed_ecr_write() {
+        let mut session = String::from("mpr121-i2c 1\n");
+        session.push_str(&reset_session(12, 6));
+        session.push_str("write_byte_data 5e 80 ! Remote I/O error (os error 121)\n");
+        // Chip may or may not have taken the failed write
+        session.push_str("write_byte_data 5e 8f =\n");
+        session.push_str("write_byte_data 5e 80 ! Remote I/O error (os error 121)\n");
+        session.push_str("read_byte_data 5e = 80\n");
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        mpr121.set_timing_policy(TimingPolicy::zero());
+        mpr121.reset().unwrap();
+        assert!(matches!(mpr121.write_ecr(0x80), Err(Mpr121Error::I2c(_))));
+        mpr121.write_ecr(0x8F).unwrap();
+        assert!(matches!(mpr121.write_ecr(0x80), Err(Mpr121Error::I2c(_))));
+        assert_eq!(mpr121.cached_ecr().unwrap(), 0x80);
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    /// Spans opened while installed, with name, parent name and fields
+    #[cfg(feature = "tracing")]
+    #[derive(Clone, Default)]
+    struct SpanLog {
+        spans: std::sync::Arc<std::sync::Mutex<Vec<LoggedSpan>>>,
+    }
+
+    #[cfg(feature = "tracing")]
+    #[derive(Debug, Default)]
+    struct LoggedSpan {
+        id: u64,
+        name: &'static str,
+        parent: Option<&'static str>,
+        fields: std::collections::BTreeMap<String, String>,
+    }
+
+    #[cfg(feature = "tracing")]
+    impl tracing::field::Visit for LoggedSpan {
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            self.fields.insert(field.name().into(), value.into());
+        }
+
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.fields
+                .insert(field.name().into(), format!("{:?}", value));
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    impl<S> tracing_subscriber::Layer<S> for SpanLog
+    where
+        S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+    {
+        fn on_new_span(
+            &self,
+            attrs: &tracing::span::Attributes<'_>,
+            id: &tracing::span::Id,
+            ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let mut span = LoggedSpan {
+                id: id.into_u64(),
+                name: attrs.metadata().name(),
+                parent: ctx.span(id).and_then(|s| s.parent()).map(|p| p.name()),
+                ..LoggedSpan::default()
+            };
+            attrs.record(&mut span);
+            self.spans.lock().unwrap().push(span);
+        }
+
+        fn on_record(
+            &self,
+            id: &tracing::span::Id,
+            values: &tracing::span::Record<'_>,
+            _ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            let mut spans = self.spans.lock().unwrap();
+            if let Some(span) = spans.iter_mut().rev().find(|s| s.id == id.into_u64()) {
+                values.record(span);
+            }
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    fn reset_span() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let mut session = String::from("mpr121-i2c 1\n");
+        session.push_str(&reset_session(12, 6));
+        session.push_str("write_byte_data 80 63 ! Remote I/O error (os error 121)\n");
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "/dev/i2c-1", 0x5A);
+        mpr121.set_timing_policy(TimingPolicy::zero());
+
+        let log = SpanLog::default();
+        let subscriber = tracing_subscriber::registry().with(log.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let _outer = tracing::info_span!("test").entered();
+            mpr121.reset().unwrap();
+            assert!(mpr121.reset().is_err());
+        });
+        assert_eq!(mpr121.into_device().remaining(), 0);
+
+        let spans = log.spans.lock().unwrap();
+        let resets: Vec<_> = spans
+            .iter()
+            .filter(|s| s.name == "reset_with_options")
+            .collect();
+        assert_eq!(resets.len(), 2);
+        for (span, outcome) in resets.iter().zip(["ok", "error"]) {
+            assert_eq!(span.parent, Some("test"));
+            assert_eq!(span.fields["bus"], "/dev/i2c-1");
+            assert_eq!(span.fields["address"], "90");
+            assert_eq!(span.fields["outcome"], outcome);
+        }
+        // Nothing below reset opens spans of its own
+        assert_eq!(spans.len(), 3);
+    }
+
+    #[test]
+    fn bus_contention() {
+        let busy = "! Device or resource busy (os error 16)\n";
+        let again = "! Resource temporarily unavailable (os error 11)\n";
+        let mut session = String::from("mpr121-i2c 1\n");
+        for _ in 0..2 {
+            session.push_str(&format!("read_byte_data 5e {}", busy));
+        }
+        session.push_str("read_byte_data 5e = 8f\n");
+        session.push_str(&format!("read_word_data 00 {}", again));
+        session.push_str("read_word_data 00 = 01 00\n");
+        // Burst longer than the retries
+        for _ in 0..4 {
+            session.push_str(&format!("read_i2c_block_data 04 18 {}", busy));
+        }
+        // Other errors are not retried
+        session.push_str("read_word_data 00 ! Remote I/O error (os error 121)\n");
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        mpr121.set_timing_policy(TimingPolicy::zero());
+        mpr121.set_auto_reconfigure(true);
+        assert!(mpr121.touch_status().unwrap().touched(0));
+        assert_eq!(mpr121.contention_count(), 3);
+        assert_eq!(mpr121.state(), &InitState::Initialized);
+        match mpr121.filtered_data() {
+            Err(e) => assert!(e.is_bus_contention()),
+            Ok(_) => panic!("read succeeded"),
+        }
+        assert_eq!(mpr121.contention_count(), 7);
+        match mpr121.touch_status() {
+            Err(e) => assert!(!e.is_bus_contention()),
+            Ok(_) => panic!("read succeeded"),
+        }
+        assert_eq!(mpr121.contention_count(), 7);
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn filtered_data_range() {
+        let session = "mpr121-i2c 1
+read_byte_data 5e = 8f
+read_i2c_block_data 08 06 = 2c fd 00 02 ff 03
+read_i2c_block_data 1a 02 = 10 00
+";
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        for (start, end, invalid) in [(0, 12, 12), (5, 4, 5), (12, 12, 12)] {
+            assert!(matches!(
+                mpr121.filtered_data_range(start, end),
+                Err(Mpr121Error::InvalidElectrode(e)) if e == invalid
+            ));
+        }
+        assert_eq!(
+            mpr121.filtered_data_range(2, 4).unwrap(),
+            vec![0x12C, 0x200, 0x3FF]
+        );
+        assert_eq!(mpr121.filtered_data_range(11, 11).unwrap(), vec![0x10]);
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn sample_offsets() {
+        let us = std::time::Duration::from_micros;
+        // Reset defaults, 6 samples of 0.5 us charge and discharge take 6 us
+        let offsets = Mpr121::sample_offsets(0x10, 0x24, &[0; 6], 12);
+        assert_eq!(offsets[0], us(0));
+        assert_eq!(offsets[1], us(6));
+        assert_eq!(offsets[11], us(66));
+        // Slowest configuration, 34 samples of 32 us charge take 2176 us, so a
+        // scan of 12 electrodes takes longer than the fastest sample interval
+        let offsets = Mpr121::sample_offsets(0xC0, 0xE0, &[0; 6], 12);
+        assert_eq!(offsets[11], us(11 * 2176));
+        // Electrode 1 charging 2 us (code 3) with 10 samples, only 3 enabled
+        let offsets = Mpr121::sample_offsets(0x40, 0x20, &[0x30, 0, 0, 0, 0, 0], 3);
+        assert_eq!(offsets[1], us(10));
+        assert_eq!(offsets[2], us(50));
+        assert_eq!(offsets[3], us(60));
+        assert_eq!(offsets[11], us(60));
+        // ELE_EN above 12 enables all electrodes
+        assert_eq!(Mpr121::sample_offsets(0x10, 0x20, &[0; 6], 15)[11], us(66));
+
+        let session = "mpr121-i2c 1
+read_i2c_block_data 5c 02 = 10 24
+read_i2c_block_data 6c 06 = 00 00 00 00 00 00
+read_byte_data 5e = 8f
+";
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        assert_eq!(mpr121.electrode_sample_offsets().unwrap()[11], us(66));
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn drift_rate() {
+        let s = std::time::Duration::from_secs;
+        let ms = std::time::Duration::from_millis;
+        assert_eq!(Mpr121::drift_slope(&[]), 0);
+        assert_eq!(Mpr121::drift_slope(&[(s(1), 500)]), 0);
+        assert_eq!(Mpr121::drift_slope(&[(s(1), 500), (s(1), 520)]), 0);
+        assert_eq!(Mpr121::drift_slope(&[(s(0), 500), (s(2), 504)]), 2000);
+        // Quantized baseline rising by 4 counts every 2 s, close to 2 counts/s
+        let samples: Vec<_> = (0..10u16)
+            .map(|i| (ms(500 * u64::from(i)), 500 + i / 4 * 4))
+            .collect();
+        assert_eq!(Mpr121::drift_slope(&samples), 1939);
+        let falling = [(ms(0), 600), (ms(100), 596), (ms(200), 592)];
+        assert_eq!(Mpr121::drift_slope(&falling), -40_000);
+
+        let session = "mpr121-i2c 1
+read_byte_data 5e = 8f
+read_byte_data 1e = 7d
+";
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        assert!(mpr121.drift_rate_milli(12, s(1)).is_err());
+        assert_eq!(mpr121.drift_rate_milli(0, s(0)).unwrap(), 0);
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn touch_velocity() {
+        assert_eq!(Mpr121::velocity(-5, 100), 0);
+        assert_eq!(Mpr121::velocity(0, 100), 0);
+        assert_eq!(Mpr121::velocity(50, 100), 127);
+        assert_eq!(Mpr121::velocity(100, 100), 255);
+        assert_eq!(Mpr121::velocity(400, 100), 255);
+        assert_eq!(Mpr121::velocity(1, 0), 255);
+
+        // Baseline 0x4E << 2 = 312, filtered 0x12C = 300
+        let session = "mpr121-i2c 1
+read_byte_data 5e = 8f
+read_i2c_block_data 08 02 = 2c fd
+read_byte_data 20 = 4e
+read_i2c_block_data 08 02 = 2c 01
+read_byte_data 20 = 4e
+";
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        assert!(matches!(
+            mpr121.touch_velocity(12),
+            Err(Mpr121Error::InvalidElectrode(12))
+        ));
+        assert_eq!(mpr121.touch_velocity(2).unwrap(), 30);
+        mpr121.set_velocity_max_delta(24);
+        assert_eq!(mpr121.touch_velocity(2).unwrap(), 127);
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn chip_variant() {
+        let mut session = String::from("mpr121-i2c 1\nwrite_byte_data 5e 00 =\n");
+        session.push_str("write_byte_data 41 a5 =\nwrite_byte_data 80 63 =\n");
+        session.push_str(&format!(
+            "read_i2c_block_data 2b 20 ={}\n",
+            " 00".repeat(32)
+        ));
+        session.push_str(&format!(
+            "read_i2c_block_data 4b 13 ={} 10 24\n",
+            " 00".repeat(17)
+        ));
+        session.push_str("read_byte_data 5e = 00\n");
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        mpr121.set_timing_policy(TimingPolicy::zero());
+        assert_eq!(mpr121.chip_variant().unwrap(), ChipVariant::Genuine);
+        assert_eq!(mpr121.state(), &InitState::Uninitialized);
+        assert_eq!(mpr121.into_device().remaining(), 0);
+
+        let mut registers = [0u8; 51];
+        registers[49] = 0x10;
+        registers[50] = 0x24;
+        assert_eq!(
+            Mpr121::classify_reset_state(&registers, 0),
+            ChipVariant::Genuine
+        );
+        // Electrodes still running or marker left, reset did not happen
+        assert_eq!(
+            Mpr121::classify_reset_state(&registers, 0x8F),
+            ChipVariant::Unknown
+        );
+        let mut marker = registers;
+        marker[0x41 - 0x2B] = 0xA5;
+        assert_eq!(
+            Mpr121::classify_reset_state(&marker, 0),
+            ChipVariant::Unknown
+        );
+        assert_eq!(
+            Mpr121::classify_reset_state(&registers[..50], 0),
+            ChipVariant::Unknown
+        );
+        let mut clone = registers;
+        clone[50] = 0x20;
+        assert_eq!(
+            Mpr121::classify_reset_state(&clone, 0),
+            ChipVariant::CompatibleClone
+        );
+        let mut clone = registers;
+        clone[0] = 0x01;
+        assert_eq!(
+            Mpr121::classify_reset_state(&clone, 0),
+            ChipVariant::CompatibleClone
+        );
+    }
+
+    #[test]
+    fn timing_policy() {
+        let resets = 20;
+        let mut session = String::from("mpr121-i2c 1\n");
+        for _ in 0..resets {
+            session.push_str(&reset_session(12, 6));
+        }
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        assert_eq!(mpr121.timing_policy(), &TimingPolicy::default());
+        mpr121.set_timing_policy(TimingPolicy::zero());
+        let start = std::time::Instant::now();
+        for _ in 0..resets {
+            mpr121.reset().unwrap();
+        }
+        // Default policy would sleep at least 1 ms per reset
+        assert!(start.elapsed() < TimingPolicy::default().post_reset * resets);
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn thresholds_iter() {
+        let session = "mpr121-i2c 1
+read_byte_data 5e = 8f
+write_byte_data 5e 00 =
+write_byte_data 45 14 =
+write_byte_data 46 0a =
+write_byte_data 57 6e =
+write_byte_data 58 37 =
+write_byte_data 5e 8f =
+";
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        // Invalid electrode rejected before anything is written
+        assert!(matches!(
+            mpr121.set_thresholds_iter(vec![(2, 20, 10), (12, 1, 1)]),
+            Err(Mpr121Error::InvalidElectrode(12))
+        ));
+        mpr121.set_thresholds_iter(None).unwrap();
+        let recalibrated = [2u8, 11].iter().map(|e| (*e, 10 * e, 5 * e));
+        mpr121.set_thresholds_iter(recalibrated).unwrap();
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn reconfigure() {
+        let session = "mpr121-i2c 1
+read_byte_data 5e = 8f
+write_byte_data 5e 00 =
+read_byte_data 41 = 0c
+write_byte_data 41 14 =
+read_byte_data 42 = 06
+write_byte_data 42 0a =
+read_byte_data 5b = 00
+write_byte_data 5b 12 =
+write_byte_data 5e 83 =
+write_byte_data 5e 00 =
+read_byte_data 41 = 14
+write_byte_data 41 08 =
+read_byte_data 42 = 0a
+write_byte_data 42 04 ! Remote I/O error (os error 121)
+write_byte_data 41 14 =
+write_byte_data 5e 83 =
+";
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        mpr121
+            .reconfigure(|w| {
+                w.set_threshold(0, 20, 10).unwrap().set_debounce(2, 1);
+                w.set_active_electrodes(&[0, 1, 2]).unwrap();
+            })
+            .unwrap();
+        // Failed preset keeps the previous one, including active electrodes
+        assert!(mpr121
+            .reconfigure(|w| {
+                w.set_threshold(0, 8, 4).unwrap();
+                w.set_active_electrodes(&[0]).unwrap();
+            })
+            .is_err());
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn initialize_with_deadline() {
+        let reset = reset_session(12, 6);
+        let verify = reset.find("read_byte_data 5d").unwrap();
+        let mut session = String::from("mpr121-i2c 1\n");
+        // Deadline at every stage boundary
+        session.push_str(&reset);
+        // All at once, verification fails and is retried on resume
+        session.push_str(&reset[..verify]);
+        session.push_str("read_byte_data 5d = 20\n");
+        session.push_str(&reset[verify..]);
+        session.push_str("read_word_data 00 = 01 00\n");
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        mpr121.set_timing_policy(TimingPolicy::zero());
+
+        let past = std::time::Instant::now();
+        let mut stages = Vec::new();
+        let mut outcome = mpr121.initialize_with_deadline(past, |s| stages.push(s));
+        for stage in InitStage::ALL[..4].iter() {
+            assert_eq!(
+                outcome.unwrap(),
+                InitOutcome::Partial {
+                    completed_through: *stage
+                }
+            );
+            assert_eq!(mpr121.state(), &InitState::Uninitialized);
+            outcome = mpr121.resume_initialization(past, |s| stages.push(s));
+        }
+        assert_eq!(outcome.unwrap(), InitOutcome::Complete);
+        assert_eq!(stages, InitStage::ALL);
+        assert_eq!(mpr121.state(), &InitState::Initialized);
+        // Nothing pending on initialized chip
+        assert_eq!(
+            mpr121.resume_initialization(past, |_| panic!()).unwrap(),
+            InitOutcome::Complete
+        );
+
+        let future = std::time::Instant::now() + std::time::Duration::from_secs(60);
+        let mut stages = Vec::new();
+        assert!(matches!(
+            mpr121.initialize_with_deadline(future, |s| stages.push(s)),
+            Err(Mpr121Error::WriteVerifyFailed {
+                reg: 0x5D,
+                expected: 0x24,
+                got: 0x20
+            })
+        ));
+        assert_eq!(stages, [InitStage::SoftReset]);
+        assert_eq!(
+            mpr121
+                .resume_initialization(future, |s| stages.push(s))
+                .unwrap(),
+            InitOutcome::Complete
+        );
+        assert_eq!(stages, InitStage::ALL);
+        assert!(mpr121.touch_status().unwrap().touched(0));
+        assert_eq!(mpr121.into_device().remaining(), 0);
+
+        assert_eq!(InitStage::SoftReset.previous(), None);
+        assert_eq!(InitStage::Enable.next(), None);
+        assert_eq!(InitStage::Verify.next(), Some(InitStage::Thresholds));
+    }
+
+    #[test]
+    fn resume_after_failed_reset() {
+        let reset = reset_session(12, 6);
+        let verify = reset.find("read_byte_data 5d").unwrap();
+        let mut session = String::from("mpr121-i2c 1\n");
+        session.push_str(&reset);
+        // Unexpected CONFIG2 fails reset without panicking
+        session.push_str(&reset[..verify]);
+        session.push_str("read_byte_data 5d = 20\n");
+        session.push_str(&reset);
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        mpr121.set_timing_policy(TimingPolicy::zero());
+        mpr121.reset().unwrap();
+        assert!(matches!(
+            mpr121.reset(),
+            Err(Mpr121Error::WriteVerifyFailed {
+                reg: 0x5D,
+                expected: 0x24,
+                got: 0x20
+            })
+        ));
+        assert!(matches!(mpr121.state(), InitState::Degraded { .. }));
+
+        // Degraded chip is initialized again from the soft reset
+        let future = std::time::Instant::now() + std::time::Duration::from_secs(60);
+        let mut stages = Vec::new();
+        assert_eq!(
+            mpr121
+                .resume_initialization(future, |s| stages.push(s))
+                .unwrap(),
+            InitOutcome::Complete
+        );
+        assert_eq!(stages, InitStage::ALL);
+        assert_eq!(mpr121.state(), &InitState::Initialized);
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn init_state() {
+        let reset = reset_session(12, 6);
+        let partial = &reset[..reset.find("write_byte_data 41").unwrap()];
+        let failed = "write_byte_data 41 0c ! Remote I/O error (os error 121)\n";
+        let mut session = String::from("mpr121-i2c 1\nread_byte_data 5e = 00\n");
+        // Reset failing halfway leaves the chip uninitialized
+        session.push_str(partial);
+        session.push_str(failed);
+        session.push_str(&reset);
+        // Brownout and failed recovery
+        session.push_str("read_byte_data 5e = 00\n");
+        session.push_str(partial);
+        session.push_str(failed);
+        // Recovery is retried
+        session.push_str(&reset);
+        session.push_str("read_word_data 00 = 01 00\n");
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+
+        assert_eq!(mpr121.state(), &InitState::Uninitialized);
+        assert!(matches!(
+            mpr121.touch_status(),
+            Err(Mpr121Error::Uninitialized)
+        ));
+        assert!(mpr121.reset().is_err());
+        assert_eq!(mpr121.state(), &InitState::Uninitialized);
+        assert!(matches!(
+            mpr121.touch_status(),
+            Err(Mpr121Error::Uninitialized)
+        ));
+        mpr121.reset().unwrap();
+        assert_eq!(mpr121.state(), &InitState::Initialized);
+
+        mpr121.set_auto_reconfigure(true);
+        assert!(matches!(mpr121.touch_status(), Err(Mpr121Error::I2c(_))));
+        assert!(matches!(mpr121.state(), InitState::Degraded { .. }));
+        mpr121.set_auto_reconfigure(false);
+        match mpr121.touch_status() {
+            Err(Mpr121Error::Degraded { reason }) => assert!(reason.contains("os error 121")),
+            other => panic!("unexpected {:?}", other.map(|_| ())),
+        }
+        mpr121.set_auto_reconfigure(true);
+        assert!(mpr121.touch_status().unwrap().touched(0));
+        assert_eq!(mpr121.state(), &InitState::Initialized);
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn wiring_check() {
+        let mut session = String::from("mpr121-i2c 1\nread_byte_data 5e = 8f\n");
+        let mut word = |status: u16| {
+            let [lo, hi] = status.to_le_bytes();
+            session.push_str(&format!("read_word_data 00 = {:02x} {:02x}\n", lo, hi));
+        };
+        for logical in 0..12u16 {
+            let physical = (logical + 5) % 12;
+            // Previous pad still held, then released
+            if logical > 0 {
+                word(1 << ((logical + 4) % 12));
+            }
+            word(0);
+            // Two pads at once, an already assigned pad, then the right one
+            if logical > 0 {
+                word(1 << physical | 1 << 5);
+                word(1 << 5);
+            }
+            word(1 << physical);
+        }
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        let mut prompts = Vec::new();
+        let layout = mpr121.guided_wiring_check(|pad| prompts.push(pad)).unwrap();
+        assert_eq!(layout, [5, 6, 7, 8, 9, 10, 11, 0, 1, 2, 3, 4]);
+        assert_eq!(prompts, (0..12).collect::<Vec<u8>>());
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn corrupt_status() {
+        assert_eq!(Mpr121::validate_status(0x9FFF).unwrap(), 0x9FFF);
+        for raw in [0x2000, 0x4001, 0xFFFF] {
+            match Mpr121::validate_status(raw) {
+                Err(Mpr121Error::CorruptRead { raw: r }) => assert_eq!(r, raw),
+                _ => panic!("Expected corrupt read of 0x{:04X}", raw),
+            }
+        }
+    }
+
+    #[test]
+    fn polarity_correction() {
+        assert_eq!(Mpr121::correct_polarity(0x0000, 0), 0x0000);
+        assert_eq!(Mpr121::correct_polarity(0x0000, 0b100), 0b100);
+        assert_eq!(Mpr121::correct_polarity(0b101, 0b100), 0b001);
+        // Proximity and over current bits are never inverted
+        assert_eq!(Mpr121::correct_polarity(0x9000, 0xFFFF), 0x9FFF);
+    }
+}
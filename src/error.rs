@@ -0,0 +1,158 @@
+//! Errors reported by the driver
+
+use i2cdev::linux::LinuxI2CError;
+
+use crate::ConfigImageError;
+
+/// Basic error type, mostly I2C errors
+#[derive(Debug)]
+pub enum Mpr121Error {
+    /// Error reported by the underlying I2C device
+    I2c(LinuxI2CError),
+    /// Electrode number outside of 0..=11 range
+    InvalidElectrode(u8),
+    /// Value read back from register differs from the one just written
+    WriteVerifyFailed { reg: u8, expected: u8, got: u8 },
+    /// Another `Mpr121` in this process already uses this bus and address
+    AddressInUse {
+        path: std::path::PathBuf,
+        address: u16,
+    },
+    /// Requested electrodes do not form a contiguous range starting at 0,
+    /// which is the only configuration the chip supports
+    NonContiguousElectrodes { mask: u16 },
+    /// Chip is not running (e.g. it was reset by a brownout), so read data
+    /// is not valid and the chip needs to be configured again
+    NotReady,
+    /// Electrodes are stopped, so status and data would read as zeros
+    NotRunning,
+    /// Chip was not configured yet, see `Mpr121::state`
+    Uninitialized,
+    /// Reset of a configured chip failed, so the chip may be partially
+    /// configured, see `Mpr121::state`
+    Degraded { reason: String },
+    /// GPIO pin mask uses electrodes 0-3 or above 11, or a pin is requested
+    /// as both input and output
+    InvalidGpioPins { mask: u16 },
+    /// Baselines of electrodes in the mask did not settle before timeout
+    NotSettled { electrodes: u16 },
+    /// Configuration image could not be decoded or applied
+    ConfigImage(ConfigImageError),
+    /// Block read returned less data than requested
+    ShortRead {
+        reg: u8,
+        expected: usize,
+        got: usize,
+    },
+    /// Touch status has reserved bits set, so the read was corrupted
+    CorruptRead { raw: u16 },
+    /// Operation needs running electrodes, so it cannot be done inside
+    /// `Mpr121::config_session`
+    InConfigSession,
+}
+
+impl Mpr121Error {
+    /// Whether the error is caused by another master holding the bus (EBUSY
+    /// or EAGAIN), so the transaction can be retried right away
+    pub fn is_bus_contention(&self) -> bool {
+        match self {
+            Mpr121Error::I2c(e) => is_contention(e),
+            _ => false,
+        }
+    }
+}
+
+/// Whether I2C transaction failed due to bus contention, see
+/// `Mpr121Error::is_bus_contention`
+pub(crate) fn is_contention(e: &LinuxI2CError) -> bool {
+    let errno = match e {
+        LinuxI2CError::Io(e) => e.raw_os_error(),
+        LinuxI2CError::Nix(e) => Some(*e as i32),
+    };
+    matches!(errno, Some(libc::EBUSY) | Some(libc::EAGAIN))
+}
+
+impl From<LinuxI2CError> for Mpr121Error {
+    fn from(e: LinuxI2CError) -> Self {
+        Mpr121Error::I2c(e)
+    }
+}
+
+/// Same text as `Display`, I2C errors and paths go through `core::fmt`
+#[cfg(feature = "defmt")]
+impl defmt::Format for Mpr121Error {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        defmt::write!(f, "{}", defmt::Display2Format(self));
+    }
+}
+
+impl std::fmt::Display for Mpr121Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mpr121Error::I2c(e) => write!(f, "I2C error: {}", e),
+            Mpr121Error::InvalidElectrode(e) => write!(f, "Invalid electrode number: {}", e),
+            Mpr121Error::WriteVerifyFailed { reg, expected, got } => write!(
+                f,
+                "Write verification of register 0x{:02X} failed: expected 0x{:02X}, got 0x{:02X}",
+                reg, expected, got
+            ),
+            Mpr121Error::AddressInUse { path, address } => write!(
+                f,
+                "Address 0x{:02X} on {} is already in use",
+                address,
+                path.display()
+            ),
+            Mpr121Error::NonContiguousElectrodes { mask } => write!(
+                f,
+                "Electrodes 0b{:012b} are not a contiguous range starting at 0, \
+                 rewire them to electrodes 0..n or mask unused ones in software",
+                mask
+            ),
+            Mpr121Error::NotReady => write!(f, "Chip is not running, reconfiguration needed"),
+            Mpr121Error::NotRunning => write!(f, "Electrodes are stopped, call reset first"),
+            Mpr121Error::Uninitialized => write!(f, "Chip is not configured, call reset first"),
+            Mpr121Error::Degraded { reason } => write!(
+                f,
+                "Chip may be partially configured after failed reset ({}), call reset again",
+                reason
+            ),
+            Mpr121Error::InvalidGpioPins { mask } => write!(
+                f,
+                "Invalid GPIO pins 0b{:016b}, only electrodes 4 to 11 can be used as GPIO",
+                mask
+            ),
+            Mpr121Error::NotSettled { electrodes } => write!(
+                f,
+                "Baselines of electrodes 0b{:012b} did not settle",
+                electrodes
+            ),
+            Mpr121Error::ConfigImage(e) => write!(f, "Invalid configuration image: {}", e),
+            Mpr121Error::ShortRead { reg, expected, got } => write!(
+                f,
+                "Block read from register 0x{:02X} returned {} bytes instead of {}",
+                reg, got, expected
+            ),
+            Mpr121Error::CorruptRead { raw } => {
+                write!(f, "Corrupt touch status 0x{:04X}, reserved bits set", raw)
+            }
+            Mpr121Error::InConfigSession => {
+                write!(f, "Not possible inside a configuration session")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Mpr121Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Mpr121Error::I2c(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ConfigImageError> for Mpr121Error {
+    fn from(e: ConfigImageError) -> Self {
+        Mpr121Error::ConfigImage(e)
+    }
+}
@@ -0,0 +1,2248 @@
+//! Processing of touch status and electrode data over time
+
+use i2cdev::{
+    core::I2CDevice,
+    linux::{LinuxI2CDevice, LinuxI2CError},
+};
+
+use crate::{Mpr121, Mpr121Error, Mpr121TouchStatus, SensorFrame};
+
+/// Change of electrode state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum TouchEvent {
+    /// Electrode became touched
+    Touched { pin: u8 },
+    /// Electrode was released
+    Released { pin: u8 },
+    /// Electrode is still held, `count` starts at 1 for the first repeat
+    Repeat { pin: u8, count: u32 },
+}
+
+/// Keyboard style autorepeat, emits `TouchEvent::Repeat` for electrodes held
+/// longer than initial delay. Time is passed in by the caller, so any clock
+/// can be used.
+#[derive(Debug, Clone)]
+pub struct Repeater {
+    delay: std::time::Duration,
+    interval: std::time::Duration,
+    min_interval: std::time::Duration,
+    held: [Option<HeldPin>; 12],
+}
+
+#[derive(Debug, Clone, Copy)]
+struct HeldPin {
+    next: std::time::Instant,
+    interval: std::time::Duration,
+    count: u32,
+}
+
+/// Touch event of one of several sensors, see `MergedEvents`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ArrayTouchEvent {
+    /// Index of the sensor which reported the event
+    pub sensor: usize,
+    /// Electrode of that sensor
+    pub local_pin: u8,
+    /// Electrode numbered across all sensors, `sensor * 12 + local_pin`
+    pub global_pin: u8,
+    /// Event as reported by the sensor
+    pub kind: TouchEvent,
+    /// When the event happened
+    pub timestamp: std::time::Instant,
+}
+
+/// Merges events of several sensors into one stream ordered by timestamp.
+/// Events are held back for up to `max_skew` to wait for late events of
+/// other sensors, so a stalled sensor delays the stream by at most that.
+/// Events arriving later than `max_skew` are emitted as soon as possible.
+#[derive(Debug, Clone)]
+pub struct MergedEvents {
+    max_skew: std::time::Duration,
+    pending: std::collections::VecDeque<ArrayTouchEvent>,
+}
+
+/// Direction of a step on a touch wheel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Rotation {
+    /// Towards the next electrode of the ring
+    Clockwise,
+    /// Towards the previous electrode of the ring
+    CounterClockwise,
+}
+
+/// Decodes rotation on electrodes arranged in a ring (a touch wheel) from
+/// the sequence of touched electrodes, either from touch status or from
+/// deltas gated by software `Hysteresis` of each ring electrode
+#[derive(Debug, Clone)]
+pub struct RotaryDecoder {
+    ring: Vec<u8>,
+    gates: Vec<Hysteresis>,
+    position: Option<usize>,
+}
+
+/// Groups of electrodes acting as single zones, a zone is pressed while any
+/// of its electrodes is touched. Zones are debounced on their own, so
+/// touch moving between electrodes of one zone does not release it.
+#[derive(Debug, Clone)]
+pub struct Zones {
+    zones: Vec<Vec<u8>>,
+    press_debounce: u8,
+    release_debounce: u8,
+    pressed: Vec<bool>,
+    pending: Vec<u8>,
+}
+
+/// Tap gesture on a single electrode, see `TapRecognizer`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Tap {
+    /// Electrode was pressed once and not again within the window
+    Single(u8),
+    /// Electrode was pressed twice within the window
+    Double(u8),
+}
+
+/// Recognizes single and double taps from touch statuses. A press is a
+/// single tap once the window after it passes without another press, so
+/// single taps are reported with a delay of the window. Time is passed in
+/// by the caller.
+#[derive(Debug, Clone)]
+pub struct TapRecognizer {
+    window: std::time::Duration,
+    previous: u16,
+    pressed_at: [Option<std::time::Instant>; 12],
+}
+
+/// Reads touch status and recognizes taps, see `TapRecognizer`
+pub struct TapDetector {
+    mpr121: Mpr121,
+    recognizer: TapRecognizer,
+}
+
+/// Output of `IdleDetector`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Activity {
+    /// No electrode was touched for the idle timeout
+    Idle,
+    /// First touch after being idle
+    Active,
+    /// Touch event passed through from the input
+    Event(TouchEvent),
+}
+
+/// Inactivity detector, e.g. for screen savers. Reports `Activity::Idle`
+/// once no electrode was touched for the timeout and `Activity::Active` on
+/// the next touch. Electrodes held down keep it active. Time is passed in by
+/// the caller.
+#[derive(Debug, Clone)]
+pub struct IdleDetector {
+    timeout: std::time::Duration,
+    swallow_first_touch: bool,
+    last_activity: Option<std::time::Instant>,
+    idle: bool,
+    held: u16,
+    swallowed: u16,
+}
+
+/// Output of `ExclusiveSelector`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    /// Winning press, passed through from the input
+    Selected { pin: u8 },
+    /// Winning electrode was released after being held for `held_for`
+    SelectionComplete {
+        pin: u8,
+        held_for: std::time::Duration,
+    },
+}
+
+/// First touch wins arbitration, e.g. for menus. The first press after all
+/// electrodes were released is selected, any other press is ignored until
+/// all electrodes are released again, even after the selected one was
+/// released. When several electrodes are pressed in the same poll, the
+/// lowest one wins. Time is passed in by the caller.
+#[derive(Debug, Clone, Default)]
+pub struct ExclusiveSelector {
+    held: u16,
+    locked: bool,
+    selected: Option<(u8, std::time::Instant)>,
+}
+
+/// Tracks when electrodes were last started. For a while after that
+/// baselines are being initialized (and autoconfiguration runs, if enabled),
+/// so touch status and filtered data are not reliable and events derived
+/// from them should be suppressed. Time is passed in by the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EnableBlanking {
+    window: std::time::Duration,
+    enabled_at: Option<std::time::Instant>,
+}
+
+/// Touch activity during one window of `UsageAggregator`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsageSummary {
+    /// Start of the window, later than window boundary for the first window
+    pub start: std::time::SystemTime,
+    /// End of the window
+    pub end: std::time::SystemTime,
+    /// Number of touches of each electrode
+    pub touches: [u32; 12],
+    /// Number of sessions started in the window, a session starts with a
+    /// touch after no activity for at least the idle gap
+    pub sessions: u32,
+    /// Most touched electrode, lowest one on ties, `None` without touches
+    pub busiest: Option<u8>,
+    /// Total time when at least one electrode was touched
+    pub active: std::time::Duration,
+}
+
+/// Aggregates touch events into fixed windows of time and reports a
+/// `UsageSummary` at the end of each window. Windows without any events
+/// since the previous summary are not reported.
+pub struct UsageAggregator {
+    window: std::time::Duration,
+    idle_gap: std::time::Duration,
+    align: bool,
+    on_summary: Box<dyn FnMut(UsageSummary) + Send>,
+    current: Option<UsageSummary>,
+    held: u16,
+    held_since: std::time::SystemTime,
+    last_activity: Option<std::time::SystemTime>,
+}
+
+/// Cumulative touch time and touch count of each electrode, fed with
+/// timestamped events. Heatmaps of several processes or devices can be
+/// merged. Time is passed in by the caller.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Heatmap {
+    touch_time: [std::time::Duration; 12],
+    touches: [u32; 12],
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pressed_at: [Option<std::time::Instant>; 12],
+}
+
+/// Two threshold (schmitt trigger) state of a single value, e.g. delta
+/// counts of an electrode: goes high when the value rises above
+/// `enter_above` and low when it falls below `exit_below`, values equal to
+/// or in between the thresholds keep the current state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hysteresis {
+    enter_above: i32,
+    exit_below: i32,
+    high: bool,
+}
+
+/// Software version of MPR121 touch/release hysteresis with debounce, for
+/// electrodes whose filtered data is processed by the application itself
+#[derive(Debug, Clone)]
+pub struct SoftwareHysteresis {
+    channels: [Hysteresis; 12],
+    touch_debounce: u8,
+    release_debounce: u8,
+    pending: [u8; 12],
+}
+
+/// Policy for combining samples skipped by decimation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregation {
+    /// Skipped samples are dropped, only the last sample of a window is used
+    Drop,
+    /// Samples of a window are averaged
+    Average,
+    /// Last sample of a window is used, together with minimum and maximum
+    /// over the window to spot transients between outputs
+    MinMax,
+}
+
+/// Filtered data of all electrodes aggregated over a decimation window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataFrame {
+    /// Time of the last sample in the window
+    pub timestamp: std::time::Instant,
+    /// Aggregated filtered data, see `Aggregation`
+    pub filtered: [u16; 12],
+    /// Minimum over the window (equal to `filtered` with `Aggregation::Drop`)
+    pub min: [u16; 12],
+    /// Maximum over the window (equal to `filtered` with `Aggregation::Drop`)
+    pub max: [u16; 12],
+}
+
+/// Aggregates every `factor` samples of filtered data into one `DataFrame`
+#[derive(Debug, Clone)]
+pub struct Decimator {
+    factor: usize,
+    policy: Aggregation,
+    count: usize,
+    sum: [u32; 12],
+    min: [u16; 12],
+    max: [u16; 12],
+}
+
+/// Iterator reading filtered data at steady rate, see `Mpr121::sample_stream`
+pub struct SampleStream<'a> {
+    pub(crate) mpr121: &'a mut Mpr121,
+    pub(crate) interval: std::time::Duration,
+    pub(crate) next: std::time::Instant,
+    pub(crate) decimator: Decimator,
+}
+
+/// Endless iterator of touch events, see `Mpr121::events`
+pub struct Events<'a, D = LinuxI2CDevice> {
+    pub(crate) mpr121: &'a mut Mpr121<D>,
+    pub(crate) interval: std::time::Duration,
+    pub(crate) next: std::time::Instant,
+    pub(crate) pending: std::collections::VecDeque<TouchEvent>,
+}
+
+/// Exponential moving average of per electrode values, computed in Q16.16
+/// fixed point. Floating point wrappers need the `float` feature, enabled
+/// by default.
+#[derive(Debug, Clone)]
+pub struct ExponentialAverage {
+    alpha: u32,
+    values: [Option<u32>; 12],
+}
+
+/// Reads filtered data of single electrodes smoothed with exponential moving
+/// average, for using electrodes as analog proximity sensors
+pub struct SmoothedReader {
+    mpr121: Mpr121,
+    average: ExponentialAverage,
+}
+
+/// Detects when baselines and filtered data of enabled electrodes stop
+/// changing, see `Mpr121::wait_for_baseline_settle`
+#[derive(Debug, Clone)]
+pub struct SettleDetector {
+    epsilon: u16,
+    required: u8,
+    electrodes: u16,
+    previous: Option<SensorFrame>,
+    stable: u8,
+    unsettled: u16,
+}
+
+impl Repeater {
+    /// Creates repeater emitting first repeat after `delay` and following
+    /// repeats every `interval`
+    pub fn new(delay: std::time::Duration, interval: std::time::Duration) -> Self {
+        Self {
+            delay,
+            interval,
+            min_interval: interval,
+            held: [None; 12],
+        }
+    }
+
+    /// Makes repeats accelerate, each repeat interval is a quarter shorter
+    /// than the previous one until `min_interval` is reached
+    pub fn with_acceleration(mut self, min_interval: std::time::Duration) -> Self {
+        self.min_interval = min_interval.min(self.interval);
+        self
+    }
+
+    /// Processes status read at `now` and returns repeats which are due.
+    /// Released electrodes stop repeating immediately, even if their repeat
+    /// was due already. At most one repeat per electrode is emitted per call.
+    pub fn update(
+        &mut self,
+        status: &Mpr121TouchStatus,
+        now: std::time::Instant,
+    ) -> Vec<TouchEvent> {
+        let mut events = Vec::new();
+        for (pin, held) in (0u8..).zip(self.held.iter_mut()) {
+            if !status.touched(pin) {
+                *held = None;
+                continue;
+            }
+            let h = held.get_or_insert(HeldPin {
+                next: now + self.delay,
+                interval: self.interval,
+                count: 0,
+            });
+            if now >= h.next {
+                h.count += 1;
+                events.push(TouchEvent::Repeat {
+                    pin,
+                    count: h.count,
+                });
+                if h.count > 1 {
+                    h.interval = (h.interval * 3 / 4).max(self.min_interval);
+                }
+                h.next = (h.next + h.interval).max(now);
+            }
+        }
+        events
+    }
+
+    /// Forgets all held electrodes
+    pub fn clear(&mut self) {
+        self.held = [None; 12];
+    }
+}
+
+impl TouchEvent {
+    /// Events turning `previous` status into `current`, in electrode order
+    pub fn diff(previous: &Mpr121TouchStatus, current: &Mpr121TouchStatus) -> Vec<TouchEvent> {
+        (Mpr121TouchStatus::first()..=Mpr121TouchStatus::last())
+            .filter_map(|pin| match (previous.touched(pin), current.touched(pin)) {
+                (false, true) => Some(TouchEvent::Touched { pin }),
+                (true, false) => Some(TouchEvent::Released { pin }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Electrode the event is about
+    pub fn pin(&self) -> u8 {
+        match *self {
+            TouchEvent::Touched { pin }
+            | TouchEvent::Released { pin }
+            | TouchEvent::Repeat { pin, .. } => pin,
+        }
+    }
+}
+
+impl MergedEvents {
+    /// Creates empty merger
+    pub fn new(max_skew: std::time::Duration) -> Self {
+        Self {
+            max_skew,
+            pending: std::collections::VecDeque::new(),
+        }
+    }
+
+    /// Adds event of `sensor`, fails if its global pin does not fit into u8
+    pub fn push(
+        &mut self,
+        sensor: usize,
+        kind: TouchEvent,
+        timestamp: std::time::Instant,
+    ) -> Result<(), Mpr121Error> {
+        let local_pin = kind.pin();
+        Mpr121::check_electrode(local_pin)?;
+        let global_pin = sensor
+            .checked_mul(12)
+            .and_then(|p| p.checked_add(usize::from(local_pin)))
+            .filter(|p| *p <= usize::from(u8::MAX))
+            .ok_or(Mpr121Error::InvalidElectrode(local_pin))? as u8;
+        let event = ArrayTouchEvent {
+            sensor,
+            local_pin,
+            global_pin,
+            kind,
+            timestamp,
+        };
+        // Stable for equal timestamps, keeping arrival order.
+        let at = self.pending.partition_point(|e| e.timestamp <= timestamp);
+        self.pending.insert(at, event);
+        Ok(())
+    }
+
+    /// Moves all events available in `receivers` into the merger, index of a
+    /// receiver is the sensor index
+    pub fn poll_receivers(
+        &mut self,
+        receivers: &[std::sync::mpsc::Receiver<(std::time::Instant, TouchEvent)>],
+    ) -> Result<(), Mpr121Error> {
+        for (sensor, receiver) in receivers.iter().enumerate() {
+            for (timestamp, event) in receiver.try_iter() {
+                self.push(sensor, event, timestamp)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the oldest event if it is at least `max_skew` old at `now`
+    pub fn pop(&mut self, now: std::time::Instant) -> Option<ArrayTouchEvent> {
+        match self.pending.front() {
+            Some(e) if e.timestamp + self.max_skew <= now => self.pending.pop_front(),
+            _ => None,
+        }
+    }
+
+    /// Returns all pending events regardless of their age, e.g. at shutdown
+    pub fn flush(&mut self) -> Vec<ArrayTouchEvent> {
+        self.pending.drain(..).collect()
+    }
+}
+
+impl TapRecognizer {
+    /// Creates recognizer treating two presses at most `window` apart as a
+    /// double tap
+    pub fn new(window: std::time::Duration) -> Self {
+        Self {
+            window,
+            previous: 0,
+            pressed_at: [None; 12],
+        }
+    }
+
+    /// Processes status read at `now` and returns recognized taps
+    pub fn update(&mut self, status: &Mpr121TouchStatus, now: std::time::Instant) -> Vec<Tap> {
+        let mut taps = Vec::new();
+        for (pin, pressed_at) in (0u8..).zip(self.pressed_at.iter_mut()) {
+            let touched = status.touched(pin);
+            let pressed = touched && self.previous & 1 << pin == 0;
+            if touched {
+                self.previous |= 1 << pin;
+            } else {
+                self.previous &= !(1 << pin);
+            }
+            if let Some(at) = *pressed_at {
+                if now.saturating_duration_since(at) <= self.window {
+                    if pressed {
+                        taps.push(Tap::Double(pin));
+                        *pressed_at = None;
+                    }
+                    continue;
+                }
+                taps.push(Tap::Single(pin));
+                *pressed_at = None;
+            }
+            if pressed {
+                *pressed_at = Some(now);
+            }
+        }
+        taps
+    }
+
+    /// Forgets previous status and pending presses
+    pub fn clear(&mut self) {
+        self.previous = 0;
+        self.pressed_at = [None; 12];
+    }
+}
+
+impl ExclusiveSelector {
+    /// Creates selector with nothing held
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Processes events of a single poll which happened at `now`. Repeats
+    /// are ignored.
+    pub fn process(&mut self, events: &[TouchEvent], now: std::time::Instant) -> Vec<Selection> {
+        let mut selections = Vec::new();
+        let mut pressed = 0u16;
+        for event in events {
+            match *event {
+                TouchEvent::Touched { pin } if pin <= Mpr121TouchStatus::last() => {
+                    self.held |= 1 << pin;
+                    pressed |= 1 << pin;
+                }
+                TouchEvent::Released { pin } => {
+                    self.held &= !(1 << pin);
+                    pressed &= !(1 << pin);
+                    if let Some((selected, since)) = self.selected {
+                        if selected == pin {
+                            selections.push(Selection::SelectionComplete {
+                                pin,
+                                held_for: now.saturating_duration_since(since),
+                            });
+                            self.selected = None;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if !self.locked && pressed != 0 {
+            let pin = pressed.trailing_zeros() as u8;
+            selections.push(Selection::Selected { pin });
+            self.selected = Some((pin, now));
+            self.locked = true;
+        }
+        if self.held == 0 {
+            self.locked = false;
+        }
+        selections
+    }
+
+    /// Currently selected electrode
+    pub fn selected(&self) -> Option<u8> {
+        self.selected.map(|(pin, _)| pin)
+    }
+}
+
+impl IdleDetector {
+    /// Creates detector reporting idle after `timeout` without touch. The
+    /// timer starts with the first call to `process`.
+    pub fn new(timeout: std::time::Duration) -> Self {
+        Self {
+            timeout,
+            swallow_first_touch: false,
+            last_activity: None,
+            idle: false,
+            held: 0,
+            swallowed: 0,
+        }
+    }
+
+    /// Makes the touch waking up from idle only report `Activity::Active`.
+    /// Its events are not passed through until the electrode is released, so
+    /// a long hold does not leak repeats or a release either.
+    pub fn swallow_first_touch(mut self, swallow: bool) -> Self {
+        self.swallow_first_touch = swallow;
+        self
+    }
+
+    /// Continues with state saved from another detector, e.g. across monitor
+    /// restarts, see `last_activity` and `is_idle`
+    pub fn restore(mut self, last_activity: std::time::Instant, idle: bool) -> Self {
+        self.last_activity = Some(last_activity);
+        self.idle = idle;
+        self
+    }
+
+    /// Time of the last touch activity, `None` before first `process`
+    pub fn last_activity(&self) -> Option<std::time::Instant> {
+        self.last_activity
+    }
+
+    /// Whether idle was reported and no touch came since
+    pub fn is_idle(&self) -> bool {
+        self.idle
+    }
+
+    /// Processes events of a single poll which happened at `now`, should be
+    /// called also when there are no events, so that idle is reported on
+    /// time. Only a touch wakes up from idle.
+    pub fn process(&mut self, events: &[TouchEvent], now: std::time::Instant) -> Vec<Activity> {
+        let mut output = Vec::new();
+        if self.idle
+            && events
+                .iter()
+                .any(|e| matches!(e, TouchEvent::Touched { .. }))
+        {
+            self.idle = false;
+            output.push(Activity::Active);
+            if self.swallow_first_touch {
+                for event in events {
+                    if let TouchEvent::Touched { pin } = *event {
+                        self.swallowed |= 1 << pin;
+                    }
+                }
+            }
+        }
+        for event in events {
+            let mask = 1u16.checked_shl(u32::from(event.pin())).unwrap_or(0);
+            match event {
+                TouchEvent::Touched { .. } => self.held |= mask,
+                TouchEvent::Released { .. } => self.held &= !mask,
+                TouchEvent::Repeat { .. } => {}
+            }
+            if self.swallowed & mask == 0 {
+                output.push(Activity::Event(*event));
+            } else if let TouchEvent::Released { .. } = event {
+                self.swallowed &= !mask;
+            }
+        }
+
+        if !events.is_empty() || self.held != 0 || self.last_activity.is_none() {
+            self.last_activity = Some(now);
+        }
+        let quiet_for = self.last_activity.map_or(std::time::Duration::ZERO, |t| {
+            now.saturating_duration_since(t)
+        });
+        if !self.idle && quiet_for >= self.timeout {
+            self.idle = true;
+            output.push(Activity::Idle);
+        }
+        output
+    }
+}
+
+impl EnableBlanking {
+    /// Worst case time until the second filter is filled with valid samples
+    /// after electrodes start, per datasheet limits: 18 samples (SFI = 3) at
+    /// the slowest sample interval of 128 ms. Faster configurations settle
+    /// sooner and may use a shorter window.
+    pub const DEFAULT_WINDOW: std::time::Duration = std::time::Duration::from_millis(18 * 128);
+
+    /// Creates tracker blanking `window` after electrodes are started
+    pub fn new(window: std::time::Duration) -> Self {
+        Self {
+            window,
+            enabled_at: None,
+        }
+    }
+
+    /// Length of blanking window
+    pub fn window(&self) -> std::time::Duration {
+        self.window
+    }
+
+    /// Changes length of blanking window, also for the last start
+    pub fn set_window(&mut self, window: std::time::Duration) {
+        self.window = window;
+    }
+
+    /// Records that electrodes were started at `at`
+    pub fn enabled(&mut self, at: std::time::Instant) {
+        self.enabled_at = Some(at);
+    }
+
+    /// End of blanking window, if it did not pass before `now`
+    pub fn settling_until(&self, now: std::time::Instant) -> Option<std::time::Instant> {
+        self.enabled_at
+            .map(|at| at + self.window)
+            .filter(|until| *until > now)
+    }
+
+    /// Whether data captured at `at` falls into blanking window
+    pub fn is_blanked(&self, at: std::time::Instant) -> bool {
+        match self.enabled_at {
+            Some(enabled) => at >= enabled && at < enabled + self.window,
+            None => false,
+        }
+    }
+}
+
+impl Default for EnableBlanking {
+    fn default() -> Self {
+        EnableBlanking::new(EnableBlanking::DEFAULT_WINDOW)
+    }
+}
+
+impl TapDetector {
+    /// Wraps sensor, see `TapRecognizer::new` for `window`
+    pub fn new(mpr121: Mpr121, window: std::time::Duration) -> Self {
+        Self {
+            mpr121,
+            recognizer: TapRecognizer::new(window),
+        }
+    }
+
+    /// Reads touch status and returns recognized taps. Needs to be called
+    /// repeatedly, more often than the window is long.
+    pub fn poll(&mut self) -> Result<Vec<Tap>, Mpr121Error> {
+        let status = self.mpr121.touch_status()?;
+        Ok(self.recognizer.update(&status, std::time::Instant::now()))
+    }
+
+    /// Wrapped sensor
+    pub fn inner_mut(&mut self) -> &mut Mpr121 {
+        &mut self.mpr121
+    }
+
+    /// Returns wrapped sensor
+    pub fn into_inner(self) -> Mpr121 {
+        self.mpr121
+    }
+}
+
+impl Zones {
+    /// Creates zones from electrode groups, zone index is the index of its
+    /// group. Electrodes may be shared between zones, empty zones are never
+    /// pressed.
+    pub fn new(zones: &[&[u8]]) -> Result<Self, Mpr121Error> {
+        for electrode in zones.iter().flat_map(|z| z.iter()) {
+            Mpr121::check_electrode(*electrode)?;
+        }
+        Ok(Self {
+            zones: zones.iter().map(|z| z.to_vec()).collect(),
+            press_debounce: 0,
+            release_debounce: 0,
+            pressed: vec![false; zones.len()],
+            pending: vec![0; zones.len()],
+        })
+    }
+
+    /// Sets number of additional consecutive statuses required before `update`
+    /// reports a zone pressed or released
+    pub fn with_debounce(mut self, press: u8, release: u8) -> Self {
+        self.press_debounce = press;
+        self.release_debounce = release;
+        self
+    }
+
+    /// Returns number of zones
+    pub fn len(&self) -> usize {
+        self.zones.len()
+    }
+
+    /// Returns true when there are no zones
+    pub fn is_empty(&self) -> bool {
+        self.zones.is_empty()
+    }
+
+    /// Returns whether each zone has any touched electrode in `status`,
+    /// without debouncing
+    pub fn zone_states(&self, status: &Mpr121TouchStatus) -> Vec<bool> {
+        self.zones
+            .iter()
+            .map(|z| z.iter().any(|e| status.touched(*e)))
+            .collect()
+    }
+
+    /// Processes next status and returns debounced state of each zone
+    pub fn update(&mut self, status: &Mpr121TouchStatus) -> Vec<bool> {
+        for (i, touched) in self.zone_states(status).into_iter().enumerate() {
+            let debounce = if self.pressed[i] {
+                self.release_debounce
+            } else {
+                self.press_debounce
+            };
+            if touched == self.pressed[i] {
+                self.pending[i] = 0;
+            } else if self.pending[i] >= debounce {
+                self.pending[i] = 0;
+                self.pressed[i] = touched;
+            } else {
+                self.pending[i] += 1;
+            }
+        }
+        self.pressed.clone()
+    }
+
+    /// Returns debounced state of each zone after the last `update`
+    pub fn pressed(&self) -> &[bool] {
+        &self.pressed
+    }
+
+    /// Releases all zones
+    pub fn clear(&mut self) {
+        self.pressed.iter_mut().for_each(|p| *p = false);
+        self.pending.iter_mut().for_each(|p| *p = 0);
+    }
+}
+
+impl RotaryDecoder {
+    /// Creates decoder for electrodes listed in clockwise order around the
+    /// ring. At least 3 electrodes are needed to tell the direction.
+    pub fn new(ring: &[u8]) -> Result<Self, Mpr121Error> {
+        for (i, e) in ring.iter().enumerate() {
+            Mpr121::check_electrode(*e)?;
+            if ring[..i].contains(e) {
+                return Err(Mpr121Error::InvalidElectrode(*e));
+            }
+        }
+        let gate = Hysteresis::new(
+            i32::from(crate::MPR121_TOUCH_THRESHOLD_DEFAULT),
+            i32::from(crate::MPR121_RELEASE_THRESHOLD_DEFAULT),
+        );
+        Ok(Self {
+            ring: ring.to_vec(),
+            gates: vec![gate; ring.len()],
+            position: None,
+        })
+    }
+
+    /// Sets thresholds in delta counts of a ring electrode for
+    /// `update_deltas`, by default the chip default touch and release
+    /// thresholds. Electrodes not in the ring are ignored.
+    pub fn set_thresholds(&mut self, electrode: u8, enter_above: i32, exit_below: i32) {
+        if let Some(i) = self.ring.iter().position(|e| *e == electrode) {
+            self.gates[i].set_thresholds(enter_above, exit_below);
+        }
+    }
+
+    /// Same as `update`, with ring electrodes touched by their hysteresis
+    /// over `deltas` (baseline - filtered data), so deltas hovering around a
+    /// threshold do not make the position flap
+    pub fn update_deltas(&mut self, deltas: &[i32; 12]) -> Option<Rotation> {
+        let status = self
+            .ring
+            .iter()
+            .zip(self.gates.iter_mut())
+            .filter_map(|(e, gate)| match gate.update(deltas[*e as usize]) {
+                true => Some(*e),
+                false => None,
+            })
+            .fold(0u16, |acc, e| acc | 1 << e);
+        self.update(&Mpr121TouchStatus::new(status))
+    }
+
+    /// Processes next status, returns rotation when touch moved to an
+    /// adjacent electrode. While two adjacent electrodes are touched, the one
+    /// touched first keeps the position. Jumps over more electrodes and new
+    /// touches only set the position.
+    pub fn update(&mut self, status: &Mpr121TouchStatus) -> Option<Rotation> {
+        let n = self.ring.len();
+        let touched: Vec<usize> = (0..n).filter(|i| status.touched(self.ring[*i])).collect();
+        let last = match (self.position, touched.first()) {
+            (_, None) => {
+                self.position = None;
+                return None;
+            }
+            (Some(last), Some(_)) if touched.contains(&last) => return None,
+            (Some(last), Some(_)) => last,
+            (None, Some(first)) => {
+                self.position = Some(*first);
+                return None;
+            }
+        };
+        let next = (last + 1) % n;
+        let previous = (last + n - 1) % n;
+        let (position, rotation) = if n >= 3 && touched.contains(&next) {
+            (next, Some(Rotation::Clockwise))
+        } else if n >= 3 && touched.contains(&previous) {
+            (previous, Some(Rotation::CounterClockwise))
+        } else {
+            (touched[0], None)
+        };
+        self.position = Some(position);
+        rotation
+    }
+}
+
+impl Heatmap {
+    /// Width of bars rendered by `Display`
+    const BAR_WIDTH: u32 = 40;
+
+    /// Creates empty heatmap
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Processes event which happened at `at`. Touch time is counted from
+    /// touch to release, repeats are ignored.
+    pub fn record(&mut self, event: &TouchEvent, at: std::time::Instant) {
+        let i = event.pin() as usize;
+        if i >= self.touches.len() {
+            return;
+        }
+        match event {
+            TouchEvent::Touched { .. } => {
+                if self.pressed_at[i].is_none() {
+                    self.touches[i] += 1;
+                    self.pressed_at[i] = Some(at);
+                }
+            }
+            TouchEvent::Released { .. } => {
+                if let Some(since) = self.pressed_at[i].take() {
+                    self.touch_time[i] += at.saturating_duration_since(since);
+                }
+            }
+            TouchEvent::Repeat { .. } => {}
+        }
+    }
+
+    /// Returns heatmap as of `now`, with touches still held counted until
+    /// `now` and closed. Recording into `self` continues unaffected.
+    pub fn sample(&self, now: std::time::Instant) -> Heatmap {
+        let mut sample = Heatmap {
+            touch_time: self.touch_time,
+            touches: self.touches,
+            pressed_at: [None; 12],
+        };
+        for (time, since) in sample.touch_time.iter_mut().zip(self.pressed_at.iter()) {
+            if let Some(since) = since {
+                *time += now.saturating_duration_since(*since);
+            }
+        }
+        sample
+    }
+
+    /// Adds touch times and counts of `other`. Touches still held in `other`
+    /// are not included, merge its `sample` to include them.
+    pub fn merge(&mut self, other: &Heatmap) {
+        for i in 0..self.touches.len() {
+            self.touch_time[i] += other.touch_time[i];
+            self.touches[i] += other.touches[i];
+        }
+    }
+
+    /// Cumulative touch time of each electrode, without touches still held
+    pub fn touch_time(&self) -> &[std::time::Duration; 12] {
+        &self.touch_time
+    }
+
+    /// Number of touches of each electrode
+    pub fn touches(&self) -> &[u32; 12] {
+        &self.touches
+    }
+
+    /// Touch time of each electrode scaled so that the longest one is
+    /// `scale`, all zeros when nothing was touched for any time
+    pub fn normalized(&self, scale: u32) -> [u32; 12] {
+        let max = self.touch_time.iter().max().map_or(0, |t| t.as_nanos());
+        let mut normalized = [0; 12];
+        if max == 0 {
+            return normalized;
+        }
+        for (n, t) in normalized.iter_mut().zip(self.touch_time.iter()) {
+            *n = (t.as_nanos() * u128::from(scale) / max) as u32;
+        }
+        normalized
+    }
+}
+
+impl std::fmt::Display for Heatmap {
+    /// Renders a bar of touch time relative to the longest one, touch count
+    /// and total touch time of each electrode, one per line
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let bars = self.normalized(Heatmap::BAR_WIDTH);
+        for (pin, bar) in bars.iter().enumerate() {
+            writeln!(
+                f,
+                "{:>2} |{:<width$}| {:>5} touches {:>8} ms",
+                pin,
+                "#".repeat(*bar as usize),
+                self.touches[pin],
+                self.touch_time[pin].as_millis(),
+                width = Heatmap::BAR_WIDTH as usize
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl UsageAggregator {
+    /// Creates aggregator with windows of `window` length, calling
+    /// `on_summary` when a window ends
+    pub fn new<F>(window: std::time::Duration, idle_gap: std::time::Duration, on_summary: F) -> Self
+    where
+        F: FnMut(UsageSummary) + Send + 'static,
+    {
+        Self {
+            window: window.max(std::time::Duration::from_secs(1)),
+            idle_gap,
+            align: false,
+            on_summary: Box::new(on_summary),
+            current: None,
+            held: 0,
+            held_since: std::time::UNIX_EPOCH,
+            last_activity: None,
+        }
+    }
+
+    /// Aligns windows to multiples of the window length since the Unix epoch,
+    /// e.g. hourly windows to the top of the hour. The first window, and the
+    /// first window after unreported ones, then starts at its first event and
+    /// ends at the next boundary.
+    pub fn aligned(mut self) -> Self {
+        self.align = true;
+        self
+    }
+
+    /// Accounts event which happened at `at`. Time going backwards (clock
+    /// adjustment) is treated as no time passing.
+    pub fn record(&mut self, event: &TouchEvent, at: std::time::SystemTime) {
+        self.tick(at);
+        let at = self.last_activity.map_or(at, |last| at.max(last));
+        let summary = self.current.as_mut().expect("window started by tick");
+        match *event {
+            TouchEvent::Touched { pin } if pin <= Mpr121TouchStatus::last() => {
+                let idle = match self.last_activity {
+                    Some(last) => at.duration_since(last).unwrap_or_default() >= self.idle_gap,
+                    None => true,
+                };
+                if idle && self.held == 0 {
+                    summary.sessions += 1;
+                }
+                if self.held == 0 {
+                    self.held_since = at;
+                }
+                self.held |= 1 << pin;
+                summary.touches[pin as usize] += 1;
+            }
+            TouchEvent::Released { pin } if self.held >> pin & 0x1 != 0 => {
+                self.held &= !(1 << pin);
+                if self.held == 0 {
+                    summary.active += at.duration_since(self.held_since).unwrap_or_default();
+                }
+            }
+            _ => return,
+        }
+        self.last_activity = Some(at);
+    }
+
+    /// Reports windows which ended before `now`, to be called periodically
+    /// so that summaries are reported also when nothing is touched
+    pub fn tick(&mut self, now: std::time::SystemTime) {
+        let end = match &self.current {
+            None => {
+                self.current = Some(self.new_window(now));
+                return;
+            }
+            Some(current) => current.end,
+        };
+        if now < end {
+            return;
+        }
+        let mut summary = self.current.take().expect("window exists");
+        if self.held != 0 {
+            summary.active += end.duration_since(self.held_since).unwrap_or_default();
+            self.held_since = end;
+        }
+        summary.busiest = (0u8..)
+            .zip(summary.touches.iter())
+            .filter(|(_, t)| **t > 0)
+            .max_by_key(|(pin, t)| (**t, std::cmp::Reverse(*pin)))
+            .map(|(pin, _)| pin);
+        (self.on_summary)(summary);
+        let next = if now < end + self.window {
+            self.window_at(end, end)
+        } else {
+            self.new_window(now)
+        };
+        self.current = Some(next);
+    }
+
+    fn new_window(&self, start: std::time::SystemTime) -> UsageSummary {
+        let since_epoch = start
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let boundary = if self.align {
+            let window = self.window.as_secs();
+            std::time::UNIX_EPOCH
+                + std::time::Duration::from_secs(since_epoch.as_secs() / window * window)
+        } else {
+            start
+        };
+        self.window_at(start, boundary)
+    }
+
+    fn window_at(
+        &self,
+        start: std::time::SystemTime,
+        boundary: std::time::SystemTime,
+    ) -> UsageSummary {
+        UsageSummary {
+            start,
+            end: boundary + self.window,
+            touches: [0; 12],
+            sessions: 0,
+            busiest: None,
+            active: std::time::Duration::from_secs(0),
+        }
+    }
+}
+
+impl Decimator {
+    /// Creates decimator emitting one frame per `factor` samples, factor of 0
+    /// is treated as 1
+    pub fn new(factor: usize, policy: Aggregation) -> Self {
+        Self {
+            factor: factor.max(1),
+            policy,
+            count: 0,
+            sum: [0; 12],
+            min: [u16::MAX; 12],
+            max: [0; 12],
+        }
+    }
+
+    /// Adds a sample, returns aggregated frame when window is complete
+    pub fn push(
+        &mut self,
+        timestamp: std::time::Instant,
+        filtered: &[u16; 12],
+    ) -> Option<DataFrame> {
+        self.count += 1;
+        for (i, f) in filtered.iter().enumerate() {
+            self.sum[i] += u32::from(*f);
+            self.min[i] = self.min[i].min(*f);
+            self.max[i] = self.max[i].max(*f);
+        }
+        if self.count < self.factor {
+            return None;
+        }
+
+        let mut frame = DataFrame {
+            timestamp,
+            filtered: *filtered,
+            min: self.min,
+            max: self.max,
+        };
+        match self.policy {
+            Aggregation::Drop => {
+                frame.min = *filtered;
+                frame.max = *filtered;
+            }
+            Aggregation::Average => {
+                let count = self.count as u32;
+                for (f, sum) in frame.filtered.iter_mut().zip(self.sum.iter()) {
+                    *f = ((sum + count / 2) / count) as u16;
+                }
+            }
+            Aggregation::MinMax => {}
+        }
+        self.reset();
+        Some(frame)
+    }
+
+    /// Discards samples of the current window
+    pub fn reset(&mut self) {
+        self.count = 0;
+        self.sum = [0; 12];
+        self.min = [u16::MAX; 12];
+        self.max = [0; 12];
+    }
+}
+
+impl ExponentialAverage {
+    /// One in Q16.16 fixed point
+    pub const ONE: u32 = 1 << 16;
+
+    /// Creates average where `alpha` in range 0.0-1.0 is the weight of a new
+    /// sample, lower values give smoother but slower output
+    #[cfg(feature = "float")]
+    pub fn new(alpha: f32) -> Self {
+        ExponentialAverage::new_fixed((alpha.clamp(0.0, 1.0) * Self::ONE as f32).round() as u32)
+    }
+
+    /// Same as `new` with `alpha` in Q16.16 fixed point (0 to `ONE`)
+    pub fn new_fixed(alpha: u32) -> Self {
+        Self {
+            alpha: alpha.min(Self::ONE),
+            values: [None; 12],
+        }
+    }
+
+    /// Adds sample of electrode and returns its new average, first sample is
+    /// taken as is
+    #[cfg(feature = "float")]
+    pub fn update(&mut self, electrode: u8, sample: u16) -> Result<f32, Mpr121Error> {
+        Ok(ExponentialAverage::to_float(
+            self.update_fixed(electrode, sample)?,
+        ))
+    }
+
+    /// Same as `update` with average returned in Q16.16 fixed point, rounded
+    /// to nearest
+    pub fn update_fixed(&mut self, electrode: u8, sample: u16) -> Result<u32, Mpr121Error> {
+        Mpr121::check_electrode(electrode)?;
+        let sample = u32::from(sample) << 16;
+        let value = self.values[electrode as usize].map_or(sample, |v| {
+            let step = (i64::from(sample) - i64::from(v)) * i64::from(self.alpha);
+            (i64::from(v) + ((step + (1 << 15)) >> 16)) as u32
+        });
+        self.values[electrode as usize] = Some(value);
+        Ok(value)
+    }
+
+    /// Current average of electrode, `None` before first sample
+    #[cfg(feature = "float")]
+    pub fn value(&self, electrode: u8) -> Option<f32> {
+        self.value_fixed(electrode)
+            .map(ExponentialAverage::to_float)
+    }
+
+    /// Same as `value` in Q16.16 fixed point
+    pub fn value_fixed(&self, electrode: u8) -> Option<u32> {
+        self.values.get(electrode as usize).copied().flatten()
+    }
+
+    /// Forgets averages of all electrodes
+    pub fn clear(&mut self) {
+        self.values = [None; 12];
+    }
+
+    #[cfg(feature = "float")]
+    fn to_float(value: u32) -> f32 {
+        value as f32 / Self::ONE as f32
+    }
+}
+
+impl SmoothedReader {
+    /// Wraps sensor, `alpha` is smoothing factor as in `ExponentialAverage::new`
+    #[cfg(feature = "float")]
+    pub fn new(mpr121: Mpr121, alpha: f32) -> Self {
+        Self {
+            mpr121,
+            average: ExponentialAverage::new(alpha),
+        }
+    }
+
+    /// Same as `new` with `alpha` in Q16.16 fixed point
+    pub fn new_fixed(mpr121: Mpr121, alpha: u32) -> Self {
+        Self {
+            mpr121,
+            average: ExponentialAverage::new_fixed(alpha),
+        }
+    }
+
+    /// Reads filtered data of electrode and returns its smoothed value
+    #[cfg(feature = "float")]
+    pub fn read_smoothed(&mut self, electrode: u8) -> Result<f32, Mpr121Error> {
+        let value = self.read_smoothed_fixed(electrode)?;
+        Ok(value as f32 / ExponentialAverage::ONE as f32)
+    }
+
+    /// Same as `read_smoothed` with value in Q16.16 fixed point
+    pub fn read_smoothed_fixed(&mut self, electrode: u8) -> Result<u32, Mpr121Error> {
+        Mpr121::check_electrode(electrode)?;
+        self.mpr121.check_running()?;
+        let data = self
+            .mpr121
+            .read_block(Mpr121::REG_FILTDATA_0L + 2 * electrode, 2)?;
+        let sample = (u16::from(data[1]) << 8 | u16::from(data[0])) & 0x3FF;
+        self.average.update_fixed(electrode, sample)
+    }
+
+    /// Averages collected so far
+    pub fn average(&self) -> &ExponentialAverage {
+        &self.average
+    }
+
+    /// Wrapped sensor
+    pub fn inner_mut(&mut self) -> &mut Mpr121 {
+        &mut self.mpr121
+    }
+
+    /// Returns wrapped sensor
+    pub fn into_inner(self) -> Mpr121 {
+        self.mpr121
+    }
+}
+
+impl<'a> Iterator for SampleStream<'a> {
+    type Item = Result<DataFrame, Mpr121Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let now = std::time::Instant::now();
+            if self.next > now {
+                std::thread::sleep(self.next - now);
+            }
+            self.next += self.interval;
+            let filtered = match self.mpr121.filtered_data() {
+                Ok(f) => f,
+                Err(e) => {
+                    self.decimator.reset();
+                    return Some(Err(e));
+                }
+            };
+            if let Some(frame) = self.decimator.push(std::time::Instant::now(), &filtered) {
+                return Some(Ok(frame));
+            }
+        }
+    }
+}
+
+impl<'a, D: I2CDevice<Error = LinuxI2CError>> Iterator for Events<'a, D> {
+    type Item = Result<TouchEvent, Mpr121Error>;
+
+    /// Returns next buffered event, polling until there is one. Errors are
+    /// returned as items, iteration never ends.
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+            let now = std::time::Instant::now();
+            if self.next > now {
+                std::thread::sleep(self.next - now);
+            }
+            self.next = (self.next + self.interval).max(now);
+            match self.mpr121.poll_events() {
+                Ok(events) => self.pending.extend(events),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+impl SettleDetector {
+    /// Creates detector for first `electrodes` electrodes, which are settled
+    /// once `required` consecutive frames differ by at most `epsilon` counts
+    /// in both baseline and filtered data
+    pub fn new(epsilon: u16, required: u8, electrodes: u8) -> Self {
+        let electrodes = ((1u32 << electrodes.min(12)) - 1) as u16;
+        Self {
+            epsilon,
+            required: required.max(1),
+            electrodes,
+            previous: None,
+            stable: 0,
+            unsettled: electrodes,
+        }
+    }
+
+    /// Adds next frame, returns true when all electrodes are settled
+    pub fn push(&mut self, frame: &SensorFrame) -> bool {
+        if let Some(previous) = self.previous.replace(*frame) {
+            let differs =
+                |a: u16, b: u16| (i32::from(a) - i32::from(b)).abs() > i32::from(self.epsilon);
+            self.unsettled = (0..12)
+                .filter(|i| self.electrodes >> i & 0x1 != 0)
+                .filter(|i| {
+                    differs(previous.baseline[*i], frame.baseline[*i])
+                        || differs(previous.filtered[*i], frame.filtered[*i])
+                })
+                .fold(0, |mask, i| mask | 1 << i);
+            self.stable = if self.unsettled == 0 {
+                self.stable.saturating_add(1)
+            } else {
+                0
+            };
+        }
+        self.stable >= self.required
+    }
+
+    /// Mask of electrodes which changed between last two frames, all
+    /// electrodes before two frames are seen
+    pub fn unsettled(&self) -> u16 {
+        self.unsettled
+    }
+}
+
+impl Hysteresis {
+    /// Creates new hysteresis in low state. `exit_below` is expected not to
+    /// exceed `enter_above`, otherwise values in between flip the state on
+    /// every update.
+    pub fn new(enter_above: i32, exit_below: i32) -> Self {
+        Self {
+            enter_above,
+            exit_below,
+            high: false,
+        }
+    }
+
+    /// Changes thresholds keeping the current state, the new thresholds
+    /// apply from the next update
+    pub fn set_thresholds(&mut self, enter_above: i32, exit_below: i32) {
+        self.enter_above = enter_above;
+        self.exit_below = exit_below;
+    }
+
+    /// Threshold the value has to rise above to go high
+    pub fn enter_above(&self) -> i32 {
+        self.enter_above
+    }
+
+    /// Threshold the value has to fall below to go low
+    pub fn exit_below(&self) -> i32 {
+        self.exit_below
+    }
+
+    /// Current state
+    pub fn is_high(&self) -> bool {
+        self.high
+    }
+
+    /// Returns if `value` would change the current state
+    pub fn crosses(&self, value: i32) -> bool {
+        if self.high {
+            value < self.exit_below
+        } else {
+            value > self.enter_above
+        }
+    }
+
+    /// Processes new value and returns resulting state
+    pub fn update(&mut self, value: i32) -> bool {
+        if self.crosses(value) {
+            self.high = !self.high;
+        }
+        self.high
+    }
+
+    /// Returns to low state
+    pub fn reset(&mut self) {
+        self.high = false;
+    }
+}
+
+impl SoftwareHysteresis {
+    /// Creates new hysteresis with given touch and release thresholds (in
+    /// delta counts) for all electrodes and no debounce. Electrode becomes
+    /// touched when its delta exceeds `touch` and released when it falls
+    /// below `release`.
+    pub fn new(touch: u16, release: u16) -> Self {
+        Self {
+            channels: [Hysteresis::new(i32::from(touch), i32::from(release)); 12],
+            touch_debounce: 0,
+            release_debounce: 0,
+            pending: [0; 12],
+        }
+    }
+
+    /// Sets touch and release thresholds of a single electrode, keeping its
+    /// state. Out of range electrodes are ignored.
+    pub fn set_thresholds(&mut self, electrode: u8, touch: u16, release: u16) {
+        if let Some(channel) = self.channels.get_mut(electrode as usize) {
+            channel.set_thresholds(i32::from(touch), i32::from(release));
+        }
+    }
+
+    /// Sets number of additional consecutive samples required before a touch
+    /// or release is reported, same as DT and DR fields of the debounce register
+    pub fn with_debounce(mut self, touch: u8, release: u8) -> Self {
+        self.touch_debounce = touch;
+        self.release_debounce = release;
+        self
+    }
+
+    /// Processes new delta (baseline - filtered data) for a given electrode
+    /// and returns its debounced state. Out of range electrodes are never touched.
+    pub fn update(&mut self, electrode: u8, delta: i32) -> bool {
+        let i = electrode as usize;
+        if electrode > Mpr121TouchStatus::last() {
+            return false;
+        }
+
+        let channel = &mut self.channels[i];
+        let debounce = if channel.is_high() {
+            self.release_debounce
+        } else {
+            self.touch_debounce
+        };
+        if !channel.crosses(delta) {
+            self.pending[i] = 0;
+        } else if self.pending[i] >= debounce {
+            self.pending[i] = 0;
+            channel.update(delta);
+        } else {
+            self.pending[i] += 1;
+        }
+
+        channel.is_high()
+    }
+
+    /// Processes deltas of all electrodes at once and returns resulting status
+    pub fn update_all(&mut self, deltas: &[i32; 12]) -> Mpr121TouchStatus {
+        for (i, delta) in deltas.iter().enumerate() {
+            self.update(i as u8, *delta);
+        }
+        self.status()
+    }
+
+    /// Returns current debounced state of all electrodes
+    pub fn status(&self) -> Mpr121TouchStatus {
+        let status = self
+            .channels
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.is_high())
+            .fold(0u16, |acc, (i, _)| acc | 1 << i);
+        Mpr121TouchStatus::new(status)
+    }
+
+    /// Clears state of all electrodes back to released
+    pub fn clear(&mut self) {
+        self.channels.iter_mut().for_each(Hysteresis::reset);
+        self.pending = [0; 12];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zones() {
+        assert!(matches!(
+            Zones::new(&[&[0, 1], &[12]]),
+            Err(Mpr121Error::InvalidElectrode(12))
+        ));
+        let status = Mpr121TouchStatus::new;
+        let mut zones = Zones::new(&[&[0, 1, 2], &[3, 4], &[2, 5], &[]]).unwrap();
+        assert_eq!(zones.len(), 4);
+        assert_eq!(zones.zone_states(&status(0)), [false; 4]);
+        assert_eq!(
+            zones.zone_states(&status(0b100)),
+            [true, false, true, false]
+        );
+        assert_eq!(
+            zones.zone_states(&status(0b11000)),
+            [false, true, false, false]
+        );
+        assert_eq!(zones.update(&status(0b10)), [true, false, false, false]);
+
+        let mut zones = Zones::new(&[&[0, 1], &[2]]).unwrap().with_debounce(1, 2);
+        assert_eq!(zones.update(&status(0b01)), [false, false]);
+        // Touch moving within the zone keeps counting towards the press
+        assert_eq!(zones.update(&status(0b10)), [true, false]);
+        // Single status on the other zone is not a press
+        assert_eq!(zones.update(&status(0b100)), [true, false]);
+        assert_eq!(zones.update(&status(0)), [true, false]);
+        // Bounce resets the release debounce
+        assert_eq!(zones.update(&status(0b01)), [true, false]);
+        assert_eq!(zones.update(&status(0)), [true, false]);
+        assert_eq!(zones.update(&status(0)), [true, false]);
+        assert_eq!(zones.update(&status(0)), [false, false]);
+        assert_eq!(zones.pressed(), [false, false]);
+
+        zones.update(&status(0b11));
+        zones.update(&status(0b11));
+        assert_eq!(zones.pressed(), [true, false]);
+        zones.clear();
+        assert_eq!(zones.pressed(), [false, false]);
+    }
+
+    #[test]
+    fn heatmap() {
+        let ms = std::time::Duration::from_millis;
+        let t0 = std::time::Instant::now();
+        let touched = |pin| TouchEvent::Touched { pin };
+        let released = |pin| TouchEvent::Released { pin };
+        let mut heatmap = Heatmap::new();
+        for (event, t) in [
+            (touched(0), 0),
+            (touched(0), 50),
+            (TouchEvent::Repeat { pin: 0, count: 1 }, 80),
+            (released(0), 100),
+            (released(0), 150),
+            (touched(0), 200),
+            (released(0), 300),
+            (touched(3), 300),
+            (released(3), 350),
+            (released(5), 400),
+            (touched(7), 400),
+        ] {
+            heatmap.record(&event, t0 + ms(t));
+        }
+        assert_eq!(heatmap.touches()[0], 2);
+        assert_eq!(heatmap.touch_time()[0], ms(200));
+        assert_eq!(heatmap.touch_time()[3], ms(50));
+        // Still held, not counted until sampled
+        assert_eq!(heatmap.touches()[7], 1);
+        assert_eq!(heatmap.touch_time()[7], ms(0));
+        let sample = heatmap.sample(t0 + ms(500));
+        assert_eq!(sample.touch_time()[7], ms(100));
+        assert_eq!(heatmap.sample(t0 + ms(800)).touch_time()[7], ms(400));
+        heatmap.record(&released(7), t0 + ms(900));
+        assert_eq!(heatmap.touch_time()[7], ms(500));
+
+        assert_eq!(
+            sample.normalized(100),
+            [100, 0, 0, 25, 0, 0, 0, 50, 0, 0, 0, 0]
+        );
+        assert_eq!(Heatmap::new().normalized(100), [0; 12]);
+        let text = sample.to_string();
+        let lines: Vec<_> = text.lines().collect();
+        assert_eq!(lines.len(), 12);
+        assert_eq!(
+            lines[3],
+            format!(" 3 |{:<40}|     1 touches       50 ms", "#".repeat(10))
+        );
+        assert!(lines[0].starts_with(&format!(" 0 |{}|", "#".repeat(40))));
+
+        // Open press of the merged heatmap is not included
+        let mut merged = sample.clone();
+        merged.merge(&heatmap);
+        assert_eq!(merged.touches()[0], 4);
+        assert_eq!(merged.touch_time()[0], ms(400));
+        assert_eq!(merged.touch_time()[7], ms(600));
+        let mut other = Heatmap::new();
+        other.record(&touched(1), t0);
+        merged.merge(&other);
+        assert_eq!(merged.touches()[1], 1);
+        assert_eq!(merged.touch_time()[1], ms(0));
+        merged.merge(&other.sample(t0 + ms(30)));
+        assert_eq!(merged.touch_time()[1], ms(30));
+    }
+
+    #[test]
+    fn hysteresis() {
+        let mut h = Hysteresis::new(10, 4);
+        // Equal to a threshold is not crossing it
+        assert!(!h.update(10));
+        assert!(h.update(11));
+        assert!(h.update(4));
+        assert!(!h.update(3));
+        assert!(!h.update(4));
+        assert!(!h.update(-100));
+
+        // New thresholds while high apply from the next update
+        assert!(h.update(11));
+        h.set_thresholds(20, 15);
+        assert!(h.is_high());
+        assert_eq!((h.enter_above(), h.exit_below()), (20, 15));
+        assert!(h.update(15));
+        assert!(!h.crosses(15));
+        assert!(h.crosses(14));
+        assert!(!h.update(14));
+        assert!(!h.update(20));
+        assert!(h.update(21));
+        h.reset();
+        assert!(!h.is_high());
+    }
+
+    #[test]
+    fn software_hysteresis() {
+        let mut h = SoftwareHysteresis::new(12, 6);
+        assert!(!h.update(0, 12));
+        assert!(h.update(0, 13));
+        // Stays touched in between thresholds
+        assert!(h.update(0, 6));
+        assert!(!h.update(0, 5));
+        assert!(!h.update(0, 12));
+        assert!(!h.update(12, 100));
+
+        let mut h = SoftwareHysteresis::new(12, 6).with_debounce(2, 1);
+        assert!(!h.update(3, 20));
+        assert!(!h.update(3, 20));
+        assert!(h.update(3, 20));
+        assert!(h.update(3, 0));
+        // Bounce back resets the debounce counter
+        assert!(h.update(3, 10));
+        assert!(h.update(3, 0));
+        assert!(!h.update(3, 0));
+
+        let mut deltas = [0; 12];
+        deltas[1] = 50;
+        deltas[11] = 50;
+        let ts = h.update_all(&deltas);
+        assert!(!ts.touched(1));
+        h.update_all(&deltas);
+        let ts = h.update_all(&deltas);
+        assert!(ts.touched(1));
+        assert!(ts.touched(11));
+        assert!(!ts.touched(0));
+        h.clear();
+        assert!(!h.status().was_touched());
+
+        // Per electrode thresholds
+        let mut h = SoftwareHysteresis::new(12, 6);
+        h.set_thresholds(2, 30, 20);
+        h.set_thresholds(12, 0, 0);
+        assert!(h.update(1, 25));
+        assert!(!h.update(2, 25));
+        assert!(h.update(2, 31));
+        h.set_thresholds(2, 8, 4);
+        assert!(h.update(2, 10));
+        assert!(!h.update(2, 3));
+    }
+
+    #[test]
+    fn decimation() {
+        let now = std::time::Instant::now();
+        let sample = |v: u16| {
+            let mut s = [v; 12];
+            s[11] = 1000 - v;
+            s
+        };
+        let input = [100, 104, 90, 130, 101, 102];
+
+        let mut d = Decimator::new(3, Aggregation::Drop);
+        let frames: Vec<_> = input
+            .iter()
+            .filter_map(|v| d.push(now, &sample(*v)))
+            .collect();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].filtered[0], 90);
+        assert_eq!(frames[0].min[0], 90);
+        assert_eq!(frames[0].max[0], 90);
+        assert_eq!(frames[1].filtered[11], 898);
+
+        let mut d = Decimator::new(3, Aggregation::Average);
+        let frames: Vec<_> = input
+            .iter()
+            .filter_map(|v| d.push(now, &sample(*v)))
+            .collect();
+        assert_eq!(frames[0].filtered[0], 98);
+        assert_eq!(frames[1].filtered[0], 111);
+        assert_eq!(frames[1].filtered[11], 889);
+
+        let mut d = Decimator::new(3, Aggregation::MinMax);
+        let frames: Vec<_> = input
+            .iter()
+            .filter_map(|v| d.push(now, &sample(*v)))
+            .collect();
+        assert_eq!(frames[0].filtered[0], 90);
+        assert_eq!(frames[0].min[0], 90);
+        assert_eq!(frames[0].max[0], 104);
+        // Transient between outputs is visible in the envelope
+        assert_eq!(frames[1].filtered[0], 102);
+        assert_eq!(frames[1].min[0], 101);
+        assert_eq!(frames[1].max[0], 130);
+        assert_eq!(frames[1].min[11], 870);
+        assert_eq!(frames[1].max[11], 899);
+
+        let mut d = Decimator::new(0, Aggregation::Average);
+        assert_eq!(d.push(now, &sample(7)).unwrap().filtered[0], 7);
+    }
+
+    #[test]
+    fn baseline_settling() {
+        let frame = |v: u16| SensorFrame {
+            status: 0,
+            filtered: [v; 12],
+            baseline: [v + 8; 12],
+        };
+        let mut d = SettleDetector::new(2, 2, 4);
+        assert_eq!(d.unsettled(), 0b1111);
+        assert!(!d.push(&frame(100)));
+        assert!(!d.push(&frame(110)));
+        assert_eq!(d.unsettled(), 0b1111);
+        assert!(!d.push(&frame(112)));
+        assert_eq!(d.unsettled(), 0);
+        assert!(d.push(&frame(111)));
+
+        // One electrode jumps, stability count restarts
+        let mut f = frame(111);
+        f.baseline[2] += 3;
+        assert!(!d.push(&f));
+        assert_eq!(d.unsettled(), 0b0100);
+        assert!(!d.push(&f));
+        assert!(d.push(&f));
+
+        // Disabled electrodes do not matter
+        f.filtered[7] = 500;
+        assert!(d.push(&f));
+        let mut d = SettleDetector::new(0, 1, 0);
+        assert!(!d.push(&f));
+        assert!(d.push(&frame(0)));
+    }
+
+    #[test]
+    #[cfg(feature = "float")]
+    fn exponential_average() {
+        let mut a = ExponentialAverage::new(0.5);
+        assert_eq!(a.value(0), None);
+        assert_eq!(a.update(0, 100).unwrap(), 100.0);
+        assert_eq!(a.update(0, 200).unwrap(), 150.0);
+        assert_eq!(a.update(0, 150).unwrap(), 150.0);
+        assert_eq!(a.update(11, 8).unwrap(), 8.0);
+        assert_eq!(a.value(0), Some(150.0));
+        assert!(a.update(12, 0).is_err());
+        a.clear();
+        assert_eq!(a.value(11), None);
+
+        let mut a = ExponentialAverage::new(2.0);
+        a.update(3, 10).unwrap();
+        assert_eq!(a.update(3, 20).unwrap(), 20.0);
+    }
+
+    #[test]
+    fn fixed_point_average() {
+        let one = ExponentialAverage::ONE;
+        let mut a = ExponentialAverage::new_fixed(one / 4);
+        assert_eq!(a.update_fixed(0, 100).unwrap(), 100 * one);
+        assert_eq!(a.update_fixed(0, 200).unwrap(), 125 * one);
+        assert_eq!(a.update_fixed(0, 0).unwrap(), 125 * one * 3 / 4);
+        assert_eq!(a.value_fixed(0), Some(125 * one * 3 / 4));
+        assert!(a.update_fixed(12, 0).is_err());
+        assert_eq!(ExponentialAverage::new_fixed(2 * one).alpha, one);
+
+        // Fixed point stays within rounding error of floating point
+        let alpha_fixed = (0.1 * f64::from(one)).round() as u32;
+        let alpha = f64::from(alpha_fixed) / f64::from(one);
+        let mut a = ExponentialAverage::new_fixed(alpha_fixed);
+        let mut expected = 0f64;
+        for (i, sample) in [0u16, 1023, 512, 3, 700, 700, 700, 1, 1023, 250]
+            .iter()
+            .cycle()
+            .take(200)
+            .enumerate()
+        {
+            let sample = f64::from(*sample);
+            expected = if i == 0 {
+                sample
+            } else {
+                expected + alpha * (sample - expected)
+            };
+            let got = f64::from(a.update_fixed(5, sample as u16).unwrap()) / f64::from(one);
+            assert!((got - expected).abs() < 0.001, "{} vs {}", got, expected);
+        }
+    }
+
+    #[test]
+    fn autorepeat() {
+        use std::time::{Duration, Instant};
+        let ms = Duration::from_millis;
+        let t0 = Instant::now();
+        let held = Mpr121TouchStatus::new(0b0101);
+        let mut r = Repeater::new(ms(500), ms(100));
+        assert!(r.update(&held, t0).is_empty());
+        assert!(r.update(&held, t0 + ms(499)).is_empty());
+        assert_eq!(
+            r.update(&held, t0 + ms(500)),
+            vec![
+                TouchEvent::Repeat { pin: 0, count: 1 },
+                TouchEvent::Repeat { pin: 2, count: 1 }
+            ]
+        );
+        assert!(r.update(&held, t0 + ms(550)).is_empty());
+        assert_eq!(r.update(&held, t0 + ms(600)).len(), 2);
+
+        // Pin 2 released right when its repeat is due, pin 0 keeps going
+        let one = Mpr121TouchStatus::new(0b0001);
+        assert_eq!(
+            r.update(&one, t0 + ms(700)),
+            vec![TouchEvent::Repeat { pin: 0, count: 3 }]
+        );
+        // Pin 2 pressed again starts with initial delay
+        assert_eq!(r.update(&held, t0 + ms(800)).len(), 1);
+        assert_eq!(r.update(&held, t0 + ms(1300)).len(), 2);
+        r.clear();
+        assert!(r.update(&held, t0 + ms(1400)).is_empty());
+
+        let mut r = Repeater::new(ms(100), ms(100)).with_acceleration(ms(50));
+        let times: Vec<u64> = (0..1000)
+            .filter(|t| !r.update(&one, t0 + ms(*t)).is_empty())
+            .take(6)
+            .collect();
+        assert_eq!(times, vec![100, 200, 275, 332, 382, 432]);
+    }
+
+    #[test]
+    fn usage_aggregation() {
+        use std::time::{Duration, UNIX_EPOCH};
+        let secs = Duration::from_secs;
+        let (tx, rx) = std::sync::mpsc::channel();
+        let mut u =
+            UsageAggregator::new(secs(3600), secs(60), move |s| tx.send(s).unwrap()).aligned();
+        let t0 = UNIX_EPOCH + secs(10 * 3600 + 1800);
+        let touch = |pin| TouchEvent::Touched { pin };
+        let release = |pin| TouchEvent::Released { pin };
+
+        u.record(&touch(1), t0);
+        u.record(&touch(2), t0 + secs(5));
+        u.record(&release(1), t0 + secs(6));
+        u.record(&release(2), t0 + secs(10));
+        // Within idle gap, same session
+        u.record(&touch(2), t0 + secs(30));
+        u.record(&release(2), t0 + secs(40));
+        // Clock stepped back, counted as no time passing
+        u.record(&touch(2), t0 + secs(20));
+        u.record(&release(2), t0 + secs(45));
+        // New session, held over the top of the hour
+        u.record(&touch(5), t0 + secs(1790));
+        assert!(rx.try_recv().is_err());
+        u.tick(t0 + secs(1800));
+        let first = rx.try_recv().unwrap();
+        assert_eq!(first.start, t0);
+        assert_eq!(first.end, UNIX_EPOCH + secs(11 * 3600));
+        assert_eq!(first.touches[1..6], [1, 3, 0, 0, 1]);
+        assert_eq!(first.sessions, 2);
+        assert_eq!(first.busiest, Some(2));
+        assert_eq!(first.active, secs(10 + 10 + 5 + 10));
+
+        u.record(&release(5), t0 + secs(1805));
+        // Jump over several windows, empty ones are not reported
+        u.record(&touch(0), t0 + secs(5 * 3600));
+        let second = rx.try_recv().unwrap();
+        assert_eq!(second.start, first.end);
+        assert_eq!(second.sessions, 0);
+        assert_eq!(second.busiest, None);
+        assert_eq!(second.active, secs(5));
+        assert!(rx.try_recv().is_err());
+        u.tick(UNIX_EPOCH + secs(16 * 3600));
+        let third = rx.try_recv().unwrap();
+        assert_eq!(third.start, t0 + secs(5 * 3600));
+        assert_eq!(third.busiest, Some(0));
+        assert_eq!(third.sessions, 1);
+        assert_eq!(third.active, secs(1800));
+    }
+
+    #[test]
+    fn event_iterator() {
+        let session = "mpr121-i2c 1
+read_byte_data 5e = 8f
+read_word_data 00 = 03 00
+read_word_data 00 = 03 00
+read_word_data 00 ! Remote I/O error (os error 121)
+read_word_data 00 = 00 00
+";
+        let replay = crate::bus::I2cReplay::new(session.as_bytes()).unwrap();
+        let mut mpr121 = Mpr121::from_device(replay, "replay", 0x5A);
+        let mut events = mpr121.events(std::time::Duration::from_millis(0));
+        // Both edges of a single poll are buffered, polls without a change
+        // yield nothing and errors do not end the iteration
+        assert_eq!(
+            events.next().unwrap().unwrap(),
+            TouchEvent::Touched { pin: 0 }
+        );
+        assert_eq!(
+            events.next().unwrap().unwrap(),
+            TouchEvent::Touched { pin: 1 }
+        );
+        assert!(events.next().unwrap().is_err());
+        assert_eq!(
+            events.next().unwrap().unwrap(),
+            TouchEvent::Released { pin: 0 }
+        );
+        assert_eq!(
+            events.next().unwrap().unwrap(),
+            TouchEvent::Released { pin: 1 }
+        );
+        // Replay is exhausted, so polling fails from now on
+        assert!(events.next().unwrap().is_err());
+        assert_eq!(mpr121.into_device().remaining(), 0);
+    }
+
+    #[test]
+    fn idle_detection() {
+        let ms = std::time::Duration::from_millis;
+        let t0 = std::time::Instant::now();
+        let touched = |pin| TouchEvent::Touched { pin };
+        let released = |pin| TouchEvent::Released { pin };
+        let event = Activity::Event;
+        let mut detector = IdleDetector::new(ms(1000)).swallow_first_touch(true);
+        let mut at = |events: &[TouchEvent], t| detector.process(events, t0 + ms(t));
+
+        assert_eq!(at(&[], 0), vec![]);
+        assert_eq!(at(&[touched(1)], 500), vec![event(touched(1))]);
+        // Held electrode keeps it active
+        assert_eq!(at(&[], 2000), vec![]);
+        assert_eq!(at(&[released(1)], 2500), vec![event(released(1))]);
+        assert_eq!(at(&[], 3400), vec![]);
+        assert_eq!(at(&[], 3500), vec![Activity::Idle]);
+        assert_eq!(at(&[], 9000), vec![]);
+
+        // Wake up touch is swallowed including repeats of a long hold
+        assert_eq!(at(&[touched(2)], 9100), vec![Activity::Active]);
+        let repeat = TouchEvent::Repeat { pin: 2, count: 1 };
+        assert_eq!(at(&[repeat, touched(3)], 9600), vec![event(touched(3))]);
+        assert_eq!(
+            at(&[released(2), released(3)], 9700),
+            vec![event(released(3))]
+        );
+        assert_eq!(at(&[touched(2)], 9800), vec![event(touched(2))]);
+        assert_eq!(at(&[released(2)], 9900), vec![event(released(2))]);
+        assert_eq!(at(&[], 10900), vec![Activity::Idle]);
+
+        // Without swallowing the wake up touch passes through
+        let mut detector = IdleDetector::new(ms(1000)).restore(t0, true);
+        assert!(detector.is_idle());
+        assert_eq!(
+            detector.process(&[touched(4)], t0 + ms(10)),
+            vec![Activity::Active, event(touched(4))]
+        );
+        assert_eq!(detector.last_activity(), Some(t0 + ms(10)));
+
+        // Restored timer continues from the saved activity
+        let mut detector = IdleDetector::new(ms(1000)).restore(t0, false);
+        assert_eq!(detector.process(&[], t0 + ms(1000)), vec![Activity::Idle]);
+    }
+
+    #[test]
+    fn exclusive_selection() {
+        let ms = std::time::Duration::from_millis;
+        let t0 = std::time::Instant::now();
+        let touched = |pin| TouchEvent::Touched { pin };
+        let released = |pin| TouchEvent::Released { pin };
+        let complete = |pin, held| Selection::SelectionComplete {
+            pin,
+            held_for: ms(held),
+        };
+        let mut selector = ExclusiveSelector::new();
+        let mut at = |events: &[TouchEvent], t| selector.process(events, t0 + ms(t));
+
+        // Simultaneous presses, lowest pin wins
+        assert_eq!(
+            at(&[touched(7), touched(3)], 0),
+            vec![Selection::Selected { pin: 3 }]
+        );
+        assert_eq!(at(&[released(7)], 10), vec![]);
+        assert_eq!(at(&[released(3)], 50), vec![complete(3, 50)]);
+
+        // Overlapping: second press suppressed, also after winner releases
+        assert_eq!(at(&[touched(1)], 100), vec![Selection::Selected { pin: 1 }]);
+        assert_eq!(at(&[touched(2)], 110), vec![]);
+        assert_eq!(at(&[released(1)], 120), vec![complete(1, 20)]);
+        assert_eq!(at(&[touched(4)], 130), vec![]);
+        assert_eq!(at(&[released(2), released(4)], 140), vec![]);
+
+        // Nested: winner held while another press comes and goes
+        assert_eq!(at(&[touched(5)], 200), vec![Selection::Selected { pin: 5 }]);
+        assert_eq!(at(&[touched(0)], 210), vec![]);
+        assert_eq!(at(&[released(0)], 220), vec![]);
+        assert_eq!(at(&[TouchEvent::Repeat { pin: 5, count: 1 }], 230), vec![]);
+        assert_eq!(at(&[released(5)], 300), vec![complete(5, 100)]);
+
+        // Release of winner and a new press in the same poll, the new press
+        // did not follow an all released state
+        assert_eq!(at(&[touched(6)], 400), vec![Selection::Selected { pin: 6 }]);
+        assert_eq!(at(&[released(6), touched(8)], 410), vec![complete(6, 10)]);
+        assert_eq!(at(&[released(8)], 420), vec![]);
+        assert_eq!(at(&[touched(9)], 430), vec![Selection::Selected { pin: 9 }]);
+    }
+
+    #[test]
+    fn status_diff() {
+        let status = Mpr121TouchStatus::new;
+        assert_eq!(TouchEvent::diff(&status(0), &status(0)), vec![]);
+        assert_eq!(
+            TouchEvent::diff(&status(0b1000_0000_0101), &status(0b1000_0000_0110)),
+            vec![
+                TouchEvent::Released { pin: 0 },
+                TouchEvent::Touched { pin: 1 }
+            ]
+        );
+        assert_eq!(
+            TouchEvent::diff(&status(0), &status(0x1800)),
+            vec![TouchEvent::Touched { pin: 11 }]
+        );
+    }
+
+    #[test]
+    fn enable_blanking() {
+        let ms = std::time::Duration::from_millis;
+        let t0 = std::time::Instant::now();
+        let mut blanking = EnableBlanking::new(ms(100));
+        assert_eq!(blanking.settling_until(t0), None);
+        assert!(!blanking.is_blanked(t0));
+
+        blanking.enabled(t0 + ms(10));
+        assert!(!blanking.is_blanked(t0));
+        assert!(blanking.is_blanked(t0 + ms(10)));
+        assert!(blanking.is_blanked(t0 + ms(109)));
+        assert!(!blanking.is_blanked(t0 + ms(110)));
+        assert_eq!(blanking.settling_until(t0 + ms(50)), Some(t0 + ms(110)));
+        assert_eq!(blanking.settling_until(t0 + ms(110)), None);
+
+        blanking.set_window(ms(200));
+        assert_eq!(blanking.settling_until(t0 + ms(110)), Some(t0 + ms(210)));
+        blanking.enabled(t0 + ms(500));
+        assert!(!blanking.is_blanked(t0 + ms(300)));
+        assert!(blanking.is_blanked(t0 + ms(600)));
+        assert_eq!(EnableBlanking::default().window(), ms(2304));
+    }
+
+    #[test]
+    fn tap_detection() {
+        let ms = std::time::Duration::from_millis;
+        let t0 = std::time::Instant::now();
+        let mut taps = TapRecognizer::new(ms(300));
+        let mut feed = |steps: &[(u64, u16)]| -> Vec<(u64, Tap)> {
+            steps
+                .iter()
+                .flat_map(|(t, bits)| {
+                    taps.update(&Mpr121TouchStatus::new(*bits), t0 + ms(*t))
+                        .into_iter()
+                        .map(move |tap| (*t, tap))
+                })
+                .collect()
+        };
+        // Double tap on 1, single tap on 2 reported once the window passes,
+        // held electrode 3 is a single tap too
+        assert_eq!(
+            feed(&[
+                (0, 0b0010),
+                (50, 0b0000),
+                (100, 0b0110),
+                (150, 0b1000),
+                (400, 0b1000),
+                (460, 0b0000),
+            ]),
+            vec![
+                (100, Tap::Double(1)),
+                (460, Tap::Single(2)),
+                (460, Tap::Single(3))
+            ]
+        );
+        // Second press after the window starts a new tap
+        assert_eq!(
+            feed(&[
+                (1000, 0b0001),
+                (1050, 0b0000),
+                (1400, 0b0001),
+                (1450, 0b0000),
+                (1800, 0b0000),
+            ]),
+            vec![(1400, Tap::Single(0)), (1800, Tap::Single(0))]
+        );
+        // Triple tap is a double tap followed by a pending single tap
+        assert_eq!(
+            feed(&[
+                (2000, 0b0001),
+                (2050, 0b0000),
+                (2100, 0b0001),
+                (2150, 0b0000),
+                (2200, 0b0001),
+                (2250, 0b0000),
+                (2600, 0b0000),
+            ]),
+            vec![(2100, Tap::Double(0)), (2600, Tap::Single(0))]
+        );
+    }
+
+    #[test]
+    fn touch_wheel() {
+        let status = |electrodes: &[u8]| {
+            Mpr121TouchStatus::new(electrodes.iter().fold(0, |s, e| s | 1 << e))
+        };
+        let mut wheel = RotaryDecoder::new(&[2, 4, 6, 8]).unwrap();
+        assert_eq!(wheel.update(&status(&[4])), None);
+        assert_eq!(wheel.update(&status(&[4, 6])), None);
+        assert_eq!(wheel.update(&status(&[6])), Some(Rotation::Clockwise));
+        assert_eq!(wheel.update(&status(&[8])), Some(Rotation::Clockwise));
+        assert_eq!(wheel.update(&status(&[2])), Some(Rotation::Clockwise));
+        assert_eq!(
+            wheel.update(&status(&[8])),
+            Some(Rotation::CounterClockwise)
+        );
+        // Jump over the ring only moves the position
+        assert_eq!(wheel.update(&status(&[4])), None);
+        assert_eq!(
+            wheel.update(&status(&[2])),
+            Some(Rotation::CounterClockwise)
+        );
+        // Lifting finger forgets the position
+        assert_eq!(wheel.update(&status(&[0, 1])), None);
+        assert_eq!(wheel.update(&status(&[4])), None);
+
+        assert!(RotaryDecoder::new(&[1, 2, 12]).is_err());
+        assert!(RotaryDecoder::new(&[1, 2, 1]).is_err());
+        let mut pair = RotaryDecoder::new(&[0, 1]).unwrap();
+        pair.update(&status(&[0]));
+        assert_eq!(pair.update(&status(&[1])), None);
+
+        // Deltas between the thresholds keep electrodes touched
+        let mut wheel = RotaryDecoder::new(&[2, 4, 6]).unwrap();
+        wheel.set_thresholds(6, 20, 10);
+        let deltas = |d2, d4, d6| {
+            let mut d = [0; 12];
+            d[2] = d2;
+            d[4] = d4;
+            d[6] = d6;
+            d
+        };
+        assert_eq!(wheel.update_deltas(&deltas(13, 0, 0)), None);
+        assert_eq!(wheel.update_deltas(&deltas(13, 13, 0)), None);
+        assert_eq!(
+            wheel.update_deltas(&deltas(0, 9, 0)),
+            Some(Rotation::Clockwise)
+        );
+        // Hovering at the threshold of electrode 6 does not rotate
+        assert_eq!(wheel.update_deltas(&deltas(0, 12, 20)), None);
+        assert_eq!(wheel.update_deltas(&deltas(0, 12, 21)), None);
+        assert_eq!(
+            wheel.update_deltas(&deltas(0, 5, 10)),
+            Some(Rotation::Clockwise)
+        );
+        assert_eq!(wheel.update_deltas(&deltas(0, 0, 10)), None);
+        assert_eq!(wheel.update_deltas(&deltas(0, 0, 9)), None);
+        assert_eq!(wheel.update_deltas(&deltas(0, 13, 0)), None);
+    }
+
+    #[test]
+    fn merged_events() {
+        use std::time::{Duration, Instant};
+        let ms = Duration::from_millis;
+        let t0 = Instant::now();
+        let touched = |pin| TouchEvent::Touched { pin };
+        let mut m = MergedEvents::new(ms(20));
+
+        m.push(1, touched(3), t0 + ms(10)).unwrap();
+        m.push(0, touched(5), t0 + ms(15)).unwrap();
+        // Out of order arrival within skew
+        m.push(2, touched(0), t0 + ms(5)).unwrap();
+        assert_eq!(m.pop(t0 + ms(24)), None);
+        let e = m.pop(t0 + ms(25)).unwrap();
+        assert_eq!((e.sensor, e.local_pin, e.global_pin), (2, 0, 24));
+        assert_eq!(m.pop(t0 + ms(25)), None);
+        // Stalled sensor 2 does not hold back others beyond skew
+        assert_eq!(m.pop(t0 + ms(30)).unwrap().global_pin, 15);
+        assert_eq!(m.pop(t0 + ms(35)).unwrap().global_pin, 5);
+        // Late event is still delivered
+        m.push(2, TouchEvent::Released { pin: 0 }, t0 + ms(6))
+            .unwrap();
+        assert_eq!(m.pop(t0 + ms(35)).unwrap().global_pin, 24);
+        assert!(m.pop(t0 + ms(1000)).is_none());
+
+        assert!(m.push(21, touched(4), t0).is_err());
+        assert!(m.push(0, touched(12), t0).is_err());
+        m.push(21, touched(3), t0).unwrap();
+        assert_eq!(m.flush()[0].global_pin, 255);
+
+        let (tx0, rx0) = std::sync::mpsc::channel();
+        let (tx1, rx1) = std::sync::mpsc::channel();
+        tx1.send((t0 + ms(1), touched(1))).unwrap();
+        tx0.send((t0 + ms(2), touched(2))).unwrap();
+        m.poll_receivers(&[rx0, rx1]).unwrap();
+        let order: Vec<u8> = m.flush().iter().map(|e| e.global_pin).collect();
+        assert_eq!(order, vec![13, 2]);
+    }
+}
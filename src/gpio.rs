@@ -0,0 +1,244 @@
+//! Decoding of GPIO (LED driver) configuration of electrodes 4 to 11
+
+use crate::Mpr121Error;
+
+/// Mode of a GPIO capable pin (electrodes 4 to 11), decoded from the GPIO
+/// enable, direction and control registers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum GpioMode {
+    /// Pin is not used as GPIO
+    Disabled,
+    /// High impedance input
+    Input,
+    /// Input with internal pull-up
+    InputPullUp,
+    /// Input with internal pull-down
+    InputPullDown,
+    /// CMOS push-pull output
+    OutputPushPull,
+    /// Low side only open drain output
+    OutputOpenDrain,
+    /// High side only open drain output, used for driving LEDs
+    Led,
+    /// Control bits combination not defined by datasheet
+    Reserved,
+}
+
+/// State of a single GPIO pin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GpioPinState {
+    /// Pin (electrode) number, 4 to 11
+    pub pin: u8,
+    /// Configured mode
+    pub mode: GpioMode,
+    /// Level from GPIO data register
+    pub level: bool,
+}
+
+/// Snapshot of all GPIO registers of MPR121
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GpioState {
+    pins: [GpioPinState; 8],
+}
+
+/// LED sequence shown by `Mpr121::identify`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlinkPattern {
+    /// Number of blinks
+    pub count: u8,
+    /// Time the outputs stay toggled
+    pub on: std::time::Duration,
+    /// Time between blinks
+    pub off: std::time::Duration,
+}
+
+impl Default for BlinkPattern {
+    /// Three blinks of 200 ms
+    fn default() -> Self {
+        Self {
+            count: 3,
+            on: std::time::Duration::from_millis(200),
+            off: std::time::Duration::from_millis(200),
+        }
+    }
+}
+
+impl GpioMode {
+    /// Decodes mode from enable, direction and both control bits of a pin
+    fn decode(en: bool, dir: bool, ctl0: bool, ctl1: bool) -> Self {
+        if !en {
+            return GpioMode::Disabled;
+        }
+        match (dir, ctl0, ctl1) {
+            (false, false, false) => GpioMode::Input,
+            (false, true, false) => GpioMode::InputPullDown,
+            (false, true, true) => GpioMode::InputPullUp,
+            (true, false, false) => GpioMode::OutputPushPull,
+            (true, true, false) => GpioMode::OutputOpenDrain,
+            (true, true, true) => GpioMode::Led,
+            (_, false, true) => GpioMode::Reserved,
+        }
+    }
+
+    /// Whether pin drives its output
+    pub fn is_output(self) -> bool {
+        matches!(
+            self,
+            GpioMode::OutputPushPull | GpioMode::OutputOpenDrain | GpioMode::Led
+        )
+    }
+}
+
+impl std::fmt::Display for GpioMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            GpioMode::Disabled => "disabled",
+            GpioMode::Input => "input",
+            GpioMode::InputPullUp => "input-pullup",
+            GpioMode::InputPullDown => "input-pulldown",
+            GpioMode::OutputPushPull => "output",
+            GpioMode::OutputOpenDrain => "output-od",
+            GpioMode::Led => "led",
+            GpioMode::Reserved => "reserved",
+        })
+    }
+}
+
+impl GpioState {
+    /// Number of the first GPIO capable pin
+    pub const FIRST_PIN: u8 = 4;
+
+    /// Decodes raw GPIO register values, bit 0 of each corresponds to pin 4
+    pub fn from_registers(en: u8, dir: u8, ctl0: u8, ctl1: u8, data: u8) -> Self {
+        let bit = |reg: u8, i: usize| reg >> i & 0x1 != 0;
+        let mut pins = [GpioPinState {
+            pin: 0,
+            mode: GpioMode::Disabled,
+            level: false,
+        }; 8];
+        for (i, p) in pins.iter_mut().enumerate() {
+            p.pin = GpioState::FIRST_PIN + i as u8;
+            p.mode = GpioMode::decode(bit(en, i), bit(dir, i), bit(ctl0, i), bit(ctl1, i));
+            p.level = bit(data, i);
+        }
+        Self { pins }
+    }
+
+    /// Converts electrode indexed output and input masks into values of
+    /// direction and enable registers
+    pub(crate) fn direction_and_enable(outputs: u16, inputs: u16) -> Result<(u8, u8), Mpr121Error> {
+        let invalid = (outputs | inputs) & !0x0FF0 | outputs & inputs;
+        if invalid != 0 {
+            return Err(Mpr121Error::InvalidGpioPins { mask: invalid });
+        }
+        let dir = (outputs >> GpioState::FIRST_PIN) as u8;
+        let en = ((outputs | inputs) >> GpioState::FIRST_PIN) as u8;
+        Ok((dir, en))
+    }
+
+    /// Returns state of a given pin (4 to 11)
+    pub fn pin(&self, pin: u8) -> Option<&GpioPinState> {
+        pin.checked_sub(GpioState::FIRST_PIN)
+            .and_then(|i| self.pins.get(i as usize))
+    }
+
+    /// Returns states of all GPIO capable pins
+    pub fn pins(&self) -> &[GpioPinState] {
+        &self.pins
+    }
+
+    /// Electrode indexed mask of pins configured as outputs
+    pub fn outputs(&self) -> u16 {
+        self.pins
+            .iter()
+            .filter(|p| p.mode.is_output())
+            .fold(0, |mask, p| mask | 1 << p.pin)
+    }
+}
+
+impl std::fmt::Display for GpioState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "pin mode           level")?;
+        for p in self.pins.iter() {
+            writeln!(
+                f,
+                "{:>3} {:<14} {}",
+                p.pin,
+                p.mode,
+                if p.level { "high" } else { "low" }
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gpio_mode_decoding() {
+        use GpioMode::*;
+        // (en, dir, ctl0, ctl1) -> mode
+        let matrix = [
+            ((false, false, false, false), Disabled),
+            ((false, true, true, true), Disabled),
+            ((false, false, true, false), Disabled),
+            ((true, false, false, false), Input),
+            ((true, false, true, false), InputPullDown),
+            ((true, false, true, true), InputPullUp),
+            ((true, false, false, true), Reserved),
+            ((true, true, false, false), OutputPushPull),
+            ((true, true, true, false), OutputOpenDrain),
+            ((true, true, true, true), Led),
+            ((true, true, false, true), Reserved),
+        ];
+        for ((en, dir, ctl0, ctl1), mode) in matrix.iter() {
+            assert_eq!(GpioMode::decode(*en, *dir, *ctl0, *ctl1), *mode);
+        }
+
+        let state = GpioState::from_registers(
+            0b1111_0001,
+            0b1100_0000,
+            0b1010_0001,
+            0b1000_0001,
+            0b0100_0001,
+        );
+        assert_eq!(state.pins().len(), 8);
+        assert_eq!(state.pin(3), None);
+        assert_eq!(state.pin(12), None);
+        assert_eq!(state.pin(4).unwrap().mode, InputPullUp);
+        assert!(state.pin(4).unwrap().level);
+        assert_eq!(state.pin(5).unwrap().mode, Disabled);
+        assert_eq!(state.pin(8).unwrap().mode, Input);
+        assert_eq!(state.pin(9).unwrap().mode, InputPullDown);
+        assert_eq!(state.pin(10).unwrap().mode, OutputPushPull);
+        assert!(state.pin(10).unwrap().level);
+        assert_eq!(state.pin(11).unwrap().mode, Led);
+        assert!(!state.pin(11).unwrap().level);
+        assert!(state.to_string().contains(" 11 led            low"));
+        assert_eq!(state.outputs(), 0b1100_0000_0000);
+        assert_eq!(GpioState::from_registers(0, 0xFF, 0, 0, 0).outputs(), 0);
+
+        assert_eq!(
+            GpioState::direction_and_enable(0b1111_1100_0000, 0b0011_0000).unwrap(),
+            (0b1111_1100, 0b1111_1111)
+        );
+        assert_eq!(GpioState::direction_and_enable(0, 0).unwrap(), (0, 0));
+        match GpioState::direction_and_enable(0b1_0001_0000, 0b1000) {
+            Err(Mpr121Error::InvalidGpioPins { mask }) => assert_eq!(mask, 0b1000),
+            _ => panic!("Expected invalid pins"),
+        }
+        match GpioState::direction_and_enable(0b1_0001_0000, 0b1_0000_0000_0000) {
+            Err(Mpr121Error::InvalidGpioPins { mask }) => assert_eq!(mask, 0x1000),
+            _ => panic!("Expected invalid pins"),
+        }
+        match GpioState::direction_and_enable(0b0011_0000, 0b0001_0000) {
+            Err(Mpr121Error::InvalidGpioPins { mask }) => assert_eq!(mask, 0b0001_0000),
+            _ => panic!("Expected invalid pins"),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Ready made configurations for common kinds of electrodes, to be written
+//! with `Mpr121::import_config_registers`
+
+use crate::{ConfigImage, Mpr121};
+
+/// Configuration for electrodes with high resistance and large area, like
+/// fruit, plants or water connected by a wire. Starts from
+/// `ConfigImage::test_config` and changes:
+///
+/// | Registers | Value |
+/// |-----------|-------|
+/// | Touch / release thresholds (0x41-0x58) | 24 / 12, body contact gives large deltas, noise too |
+/// | Debounce (0x5B) | 0x22, touch and release confirmed by 2 extra samples |
+/// | CONFIG1 (0x5C) | 0x20, 6 samples first filter, 32uA |
+/// | CONFIG2 (0x5D) | 0x60, 2us so resistive electrodes charge fully, 4 samples second filter, 1ms period |
+///
+/// A 100pF electrode charges to about 200 counts, a 30pF one to about 660,
+/// both within the usable range of the ADC.
+pub fn high_impedance_electrodes() -> ConfigImage {
+    let mut image = ConfigImage::test_config();
+    for (reg, value) in image.registers.iter_mut() {
+        *value = match *reg {
+            Mpr121::REG_TOUCHTH_0..=0x58 if *reg % 2 == 1 => 24,
+            Mpr121::REG_RELEASETH_0..=0x58 => 12,
+            Mpr121::REG_DEBOUNCE => 0x22,
+            Mpr121::REG_CONFIG1 => 0x20,
+            Mpr121::REG_CONFIG2 => 0x60,
+            _ => *value,
+        };
+    }
+    image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn high_impedance_preset() {
+        let image = high_impedance_electrodes();
+        assert_eq!(ConfigImage::from_bytes(&image.to_bytes()).unwrap(), image);
+        assert_eq!(
+            image.registers().len(),
+            ConfigImage::test_config().registers().len()
+        );
+        assert_eq!(image.registers().last(), Some(&(Mpr121::REG_ECR, 0x8F)));
+        for electrode in 0..12 {
+            let touch = image.get(Mpr121::REG_TOUCHTH_0 + 2 * electrode).unwrap();
+            let release = image.get(Mpr121::REG_RELEASETH_0 + 2 * electrode).unwrap();
+            assert!(release > 0 && release < touch, "electrode {}", electrode);
+        }
+        // Proximity stays disabled
+        assert_eq!(image.get(Mpr121::REG_PROXTOUCHTH), Some(0));
+        assert_eq!(image.get(Mpr121::REG_DEBOUNCE), Some(0x22));
+        // Charge time 2us, charge current 32uA
+        assert_eq!(image.get(Mpr121::REG_CONFIG2).unwrap() >> 5, 3);
+        assert_eq!(image.get(Mpr121::REG_CONFIG1).unwrap() & 0x3F, 32);
+        #[cfg(feature = "float")]
+        {
+            let c = Mpr121::capacitance_pf(204, 32, 3, 3.3);
+            assert!((c - 97.3).abs() < 0.5, "{}", c);
+            let c = Mpr121::capacitance_pf(662, 32, 3, 3.3);
+            assert!((c - 30.0).abs() < 0.5, "{}", c);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Protocol constants of MPR121, for tools talking to the chip directly.
+//! The driver uses the same constants, e.g. a bring-up script can issue the
+//! soft reset by hand before handing the chip over to `Mpr121`:
+//!
+//! ```rust,no_run
+//! use adafruit_mpr121::{protocol, Mpr121};
+//! use i2cdev::core::I2CDevice;
+//! use i2cdev::linux::LinuxI2CDevice;
+//!
+//! let mut dev = LinuxI2CDevice::new("/dev/i2c-1", protocol::ADDRESS_FIRST).unwrap();
+//! dev.smbus_write_byte_data(protocol::SOFT_RESET_REGISTER, protocol::SOFT_RESET_COMMAND)
+//!     .unwrap();
+//! assert_eq!(
+//!     dev.smbus_read_byte_data(protocol::CONFIG2_REGISTER).unwrap(),
+//!     protocol::CONFIG2_RESET_VALUE
+//! );
+//! drop(dev);
+//!
+//! // Same as above, followed by complete configuration
+//! let mut mpr121 = Mpr121::new(1, protocol::ADDRESS_FIRST).unwrap();
+//! mpr121.reset().unwrap();
+//! ```
+
+/// First I2C address MPR121 can use (ADDR pin connected to ground)
+pub const ADDRESS_FIRST: u16 = 0x5A;
+
+/// Last I2C address MPR121 can use (ADDR pin connected to SCL)
+pub const ADDRESS_LAST: u16 = 0x5D;
+
+/// All I2C addresses MPR121 can use
+pub const ADDRESSES: std::ops::RangeInclusive<u16> = ADDRESS_FIRST..=ADDRESS_LAST;
+
+/// Soft reset register
+pub const SOFT_RESET_REGISTER: u8 = 0x80;
+
+/// Value written to soft reset register to reset the chip
+pub const SOFT_RESET_COMMAND: u8 = 0x63;
+
+/// Filter and global charge time configuration register (CONFIG2)
+pub const CONFIG2_REGISTER: u8 = 0x5D;
+
+/// Value of CONFIG2 right after reset, used to verify the reset worked
+pub const CONFIG2_RESET_VALUE: u8 = 0x24;
+
+/// Electrode configuration register (ECR)
+pub const ECR_REGISTER: u8 = 0x5E;
+
+/// ECR value written by `Mpr121::reset`: all 12 electrodes enabled, baseline
+/// tracking enabled and initialized from 5 MSB of first filtered data
+pub const ECR_RUN: u8 = 0x8F;
@@ -0,0 +1,687 @@
+//! Recording of touch events to a file and replaying them later, and an
+//! audit log of events and errors for unattended installations
+
+use std::io::{BufRead, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::{Mpr121Error, TouchEvent};
+
+/// Writes timestamped touch events as text, one event per line, after a
+/// header with format version. Timestamps are microseconds since recording
+/// started.
+pub struct EventRecorder<W: Write> {
+    out: W,
+    start: Instant,
+}
+
+/// Reads events written by `EventRecorder`, either as fast as possible or
+/// honoring the original timing between events
+pub struct EventReplayer<R: BufRead> {
+    input: R,
+    line: usize,
+    realtime: Option<Instant>,
+    failed: bool,
+}
+
+/// Failure to replay recorded events
+#[derive(Debug)]
+pub enum ReplayError {
+    /// Reading the recording failed
+    Io(std::io::Error),
+    /// Recording does not start with the expected header
+    BadHeader,
+    /// Recording was written by unsupported format version
+    UnsupportedVersion(u32),
+    /// Line of the recording could not be parsed, e.g. a truncated file
+    Corrupt { line: usize },
+}
+
+impl<W: Write> EventRecorder<W> {
+    /// Current version of the recording format
+    pub const VERSION: u32 = 1;
+    const MAGIC: &'static str = "mpr121-events";
+
+    /// Writes header and starts recording, `start` is the time of offset 0
+    pub fn new(mut out: W, start: Instant) -> std::io::Result<Self> {
+        writeln!(out, "{} {}", Self::MAGIC, Self::VERSION)?;
+        Ok(Self { out, start })
+    }
+
+    /// Writes event which happened at `at`, events before start are recorded
+    /// at offset 0
+    pub fn record(&mut self, at: Instant, event: &TouchEvent) -> std::io::Result<()> {
+        let offset = at.saturating_duration_since(self.start).as_micros();
+        match event {
+            TouchEvent::Touched { pin } => writeln!(self.out, "{} touched {}", offset, pin),
+            TouchEvent::Released { pin } => writeln!(self.out, "{} released {}", offset, pin),
+            TouchEvent::Repeat { pin, count } => {
+                writeln!(self.out, "{} repeat {} {}", offset, pin, count)
+            }
+        }
+    }
+
+    /// Flushes and returns the underlying writer
+    pub fn into_inner(mut self) -> std::io::Result<W> {
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+impl<R: BufRead> EventReplayer<R> {
+    /// Checks header of the recording and prepares replay as fast as possible
+    pub fn new(mut input: R) -> Result<Self, ReplayError> {
+        let mut header = String::new();
+        input.read_line(&mut header).map_err(ReplayError::Io)?;
+        let mut fields = header.trim_end().split(' ');
+        if fields.next() != Some(EventRecorder::<Vec<u8>>::MAGIC) {
+            return Err(ReplayError::BadHeader);
+        }
+        match fields.next().map(str::parse::<u32>) {
+            Some(Ok(EventRecorder::<Vec<u8>>::VERSION)) => {}
+            Some(Ok(version)) => return Err(ReplayError::UnsupportedVersion(version)),
+            _ => return Err(ReplayError::BadHeader),
+        }
+        Ok(Self {
+            input,
+            line: 1,
+            realtime: None,
+            failed: false,
+        })
+    }
+
+    /// Makes replay sleep between events as long as the original events were
+    /// apart, starting from the first call to `next`
+    pub fn realtime(mut self) -> Self {
+        self.realtime = Some(Instant::now());
+        self
+    }
+
+    #[deny(clippy::indexing_slicing)]
+    fn parse(line: &str) -> Option<(Duration, TouchEvent)> {
+        // Truncated last line lacks the newline
+        let line = line.strip_suffix('\n')?;
+        let mut fields = line.split(' ');
+        let offset = Duration::from_micros(fields.next()?.parse().ok()?);
+        let kind = fields.next()?;
+        let pin: u8 = fields.next()?.parse().ok()?;
+        if pin > 11 {
+            return None;
+        }
+        let event = match kind {
+            "touched" => TouchEvent::Touched { pin },
+            "released" => TouchEvent::Released { pin },
+            "repeat" => TouchEvent::Repeat {
+                pin,
+                count: fields.next()?.parse().ok()?,
+            },
+            _ => return None,
+        };
+        match fields.next() {
+            None => Some((offset, event)),
+            Some(_) => None,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for EventReplayer<R> {
+    type Item = Result<(Duration, TouchEvent), ReplayError>;
+
+    /// Returns next event with its offset from the start of recording, stops
+    /// after the first error
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let mut line = String::new();
+        match self.input.read_line(&mut line) {
+            Ok(0) => return None,
+            Ok(_) => self.line += 1,
+            Err(e) => {
+                self.failed = true;
+                return Some(Err(ReplayError::Io(e)));
+            }
+        }
+        let (offset, event) = match Self::parse(&line) {
+            Some(e) => e,
+            None => {
+                self.failed = true;
+                return Some(Err(ReplayError::Corrupt { line: self.line }));
+            }
+        };
+        if let Some(start) = self.realtime {
+            let elapsed = start.elapsed();
+            if offset > elapsed {
+                std::thread::sleep(offset - elapsed);
+            }
+        }
+        Some(Ok((offset, event)))
+    }
+}
+
+/// Settings of `FileEventLog`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventLogConfig {
+    /// Directory of the log files, created when missing
+    pub dir: PathBuf,
+    /// Name of the current log file, rotated files get suffix `.1` (newest)
+    /// to `.N`
+    pub file_name: String,
+    /// Size in bytes after which the log is rotated
+    pub max_bytes: u64,
+    /// Also rotate when a record falls on a different day (UTC) than the
+    /// current file
+    pub daily: bool,
+    /// Number of rotated files kept, older ones are deleted
+    pub keep: usize,
+    /// How often written records are synced to disk, zero syncs every record
+    pub sync_every: Duration,
+    /// Number of records waiting for the writer thread before new ones are
+    /// dropped
+    pub queue: usize,
+}
+
+/// Appends touch events and errors to a file as JSON lines, e.g.
+/// `{"ts_ms":1700000000000,"event":"touched","pin":3}`, rotating files by
+/// size and day. Writing and syncing happens on a thread of its own, so
+/// logging never blocks the caller: records go through a bounded queue and
+/// when it is full, the new record is dropped (queued ones are kept). Drops
+/// are counted by `dropped` and written to the log as a
+/// `{"ts_ms":...,"dropped":N}` record, N being the total so far, before the
+/// next record the writer gets. A file cut short by a crash is truncated to
+/// its last complete line when the log is opened again.
+pub struct FileEventLog {
+    tx: Option<mpsc::SyncSender<LogRecord>>,
+    dropped: Arc<AtomicU64>,
+    writer: Option<std::thread::JoinHandle<std::io::Result<()>>>,
+}
+
+enum LogRecord {
+    Event(SystemTime, TouchEvent),
+    Error(SystemTime, String),
+}
+
+struct LogWriter {
+    config: EventLogConfig,
+    file: std::fs::File,
+    len: u64,
+    day: Option<u64>,
+    dirty: bool,
+    last_sync: Instant,
+}
+
+impl EventLogConfig {
+    /// Log `events.log` in `dir`, rotated at 1 MiB and daily, keeping 7
+    /// files, synced every second, with queue of 256 records
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        EventLogConfig {
+            dir: dir.into(),
+            file_name: String::from("events.log"),
+            max_bytes: 1 << 20,
+            daily: true,
+            keep: 7,
+            sync_every: Duration::from_secs(1),
+            queue: 256,
+        }
+    }
+
+    fn path(&self, index: usize) -> PathBuf {
+        match index {
+            0 => self.dir.join(&self.file_name),
+            i => self.dir.join(format!("{}.{}", self.file_name, i)),
+        }
+    }
+}
+
+impl FileEventLog {
+    /// Opens the current log file, recovering it after a crash, and starts
+    /// the writer thread
+    pub fn open(config: EventLogConfig) -> std::io::Result<Self> {
+        Self::start(config, None)
+    }
+
+    fn start(config: EventLogConfig, gate: Option<mpsc::Receiver<()>>) -> std::io::Result<Self> {
+        let mut writer = LogWriter::open(config)?;
+        let (tx, rx) = mpsc::sync_channel(writer.config.queue);
+        let dropped = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&dropped);
+        let handle = std::thread::Builder::new()
+            .name(String::from("mpr121-event-log"))
+            .spawn(move || {
+                if let Some(gate) = gate {
+                    let _ = gate.recv();
+                }
+                writer.run(rx, &counter)
+            })?;
+        Ok(FileEventLog {
+            tx: Some(tx),
+            dropped,
+            writer: Some(handle),
+        })
+    }
+
+    /// Queues `event` which happened at `at`, returns false when it was
+    /// dropped
+    pub fn log_event(&self, at: SystemTime, event: &TouchEvent) -> bool {
+        self.send(LogRecord::Event(at, *event))
+    }
+
+    /// Queues `error` which happened at `at`, returns false when it was
+    /// dropped
+    pub fn log_error(&self, at: SystemTime, error: &Mpr121Error) -> bool {
+        self.send(LogRecord::Error(at, error.to_string()))
+    }
+
+    /// Number of records dropped so far, because the queue was full or the
+    /// writer thread failed
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Writes all queued records, syncs the file and stops the writer
+    /// thread, returning the first error it hit
+    pub fn close(mut self) -> std::io::Result<()> {
+        self.stop()
+    }
+
+    fn send(&self, record: LogRecord) -> bool {
+        let sent = match &self.tx {
+            Some(tx) => tx.try_send(record).is_ok(),
+            None => false,
+        };
+        if !sent {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        sent
+    }
+
+    fn stop(&mut self) -> std::io::Result<()> {
+        self.tx = None;
+        match self.writer.take().map(std::thread::JoinHandle::join) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err(std::io::Error::other("event log writer panicked")),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for FileEventLog {
+    fn drop(&mut self) {
+        let _ = self.stop();
+    }
+}
+
+impl LogWriter {
+    fn open(config: EventLogConfig) -> std::io::Result<Self> {
+        std::fs::create_dir_all(&config.dir)?;
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(config.path(0))?;
+        let len = Self::recover(&mut file)?;
+        let day = Self::last_day(&mut file, len)?;
+        Ok(LogWriter {
+            config,
+            file,
+            len,
+            day,
+            dirty: false,
+            last_sync: Instant::now(),
+        })
+    }
+
+    /// Truncates `file` after its last newline, returns the new length
+    fn recover(file: &mut std::fs::File) -> std::io::Result<u64> {
+        let mut end = file.seek(SeekFrom::End(0))?;
+        let mut chunk = [0u8; 4096];
+        let complete = loop {
+            if end == 0 {
+                break 0;
+            }
+            let start = end.saturating_sub(chunk.len() as u64);
+            let size = (end - start) as usize;
+            file.seek(SeekFrom::Start(start))?;
+            file.read_exact(&mut chunk[..size])?;
+            if let Some(pos) = chunk[..size].iter().rposition(|b| *b == b'\n') {
+                break start + pos as u64 + 1;
+            }
+            end = start;
+        };
+        if complete != file.metadata()?.len() {
+            file.set_len(complete)?;
+            file.sync_all()?;
+        }
+        file.seek(SeekFrom::Start(complete))?;
+        Ok(complete)
+    }
+
+    /// Day of the last record of `file` with `len` bytes, if it can be read
+    fn last_day(file: &mut std::fs::File, len: u64) -> std::io::Result<Option<u64>> {
+        let mut tail = [0u8; 256];
+        let start = len.saturating_sub(tail.len() as u64);
+        let size = (len - start) as usize;
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut tail[..size])?;
+        file.seek(SeekFrom::Start(len))?;
+        let tail = String::from_utf8_lossy(&tail[..size]);
+        let ts_ms = tail
+            .trim_end()
+            .rsplit('\n')
+            .next()
+            .and_then(|line| line.strip_prefix("{\"ts_ms\":"))
+            .and_then(|rest| rest.split(',').next())
+            .and_then(|ts| ts.parse::<u64>().ok());
+        Ok(ts_ms.map(|ms| ms / 1000 / 86400))
+    }
+
+    fn run(&mut self, rx: mpsc::Receiver<LogRecord>, dropped: &AtomicU64) -> std::io::Result<()> {
+        let mut reported = 0;
+        loop {
+            let timeout = match self.dirty {
+                true => self
+                    .config
+                    .sync_every
+                    .saturating_sub(self.last_sync.elapsed()),
+                false => Duration::from_secs(3600),
+            };
+            match rx.recv_timeout(timeout) {
+                Ok(record) => {
+                    let total = dropped.load(Ordering::Relaxed);
+                    if total != reported {
+                        reported = total;
+                        let line = format!(
+                            "{{\"ts_ms\":{},\"dropped\":{}}}\n",
+                            Self::millis(record.time()),
+                            total
+                        );
+                        self.write(record.time(), &line)?;
+                    }
+                    self.write(record.time(), &record.to_json())?;
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+            if self.dirty && self.last_sync.elapsed() >= self.config.sync_every {
+                self.sync()?;
+            }
+        }
+        self.sync()
+    }
+
+    fn write(&mut self, at: SystemTime, line: &str) -> std::io::Result<()> {
+        let day = Self::day(at);
+        let full = self.len + line.len() as u64 > self.config.max_bytes;
+        let new_day = self.day.is_some_and(|d| d != day);
+        if self.len > 0 && (full || self.config.daily && new_day) {
+            self.rotate()?;
+        }
+        self.day = Some(day);
+        self.file.write_all(line.as_bytes())?;
+        self.len += line.len() as u64;
+        self.dirty = true;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.sync()?;
+        if self.config.keep == 0 {
+            std::fs::remove_file(self.config.path(0))?;
+        }
+        for i in (0..self.config.keep).rev() {
+            let from = self.config.path(i);
+            if Path::exists(&from) {
+                std::fs::rename(from, self.config.path(i + 1))?;
+            }
+        }
+        self.file = std::fs::File::create(self.config.path(0))?;
+        self.len = 0;
+        Ok(())
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        if self.dirty {
+            self.file.sync_data()?;
+            self.dirty = false;
+        }
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    fn millis(at: SystemTime) -> u128 {
+        at.duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis()
+    }
+
+    fn day(at: SystemTime) -> u64 {
+        at.duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            / 86400
+    }
+}
+
+impl LogRecord {
+    fn time(&self) -> SystemTime {
+        match self {
+            LogRecord::Event(at, _) | LogRecord::Error(at, _) => *at,
+        }
+    }
+
+    fn to_json(&self) -> String {
+        let ts = LogWriter::millis(self.time());
+        match self {
+            LogRecord::Event(_, TouchEvent::Touched { pin }) => format!(
+                "{{\"ts_ms\":{},\"event\":\"touched\",\"pin\":{}}}\n",
+                ts, pin
+            ),
+            LogRecord::Event(_, TouchEvent::Released { pin }) => format!(
+                "{{\"ts_ms\":{},\"event\":\"released\",\"pin\":{}}}\n",
+                ts, pin
+            ),
+            LogRecord::Event(_, TouchEvent::Repeat { pin, count }) => format!(
+                "{{\"ts_ms\":{},\"event\":\"repeat\",\"pin\":{},\"count\":{}}}\n",
+                ts, pin, count
+            ),
+            LogRecord::Error(_, message) => {
+                let mut escaped = String::with_capacity(message.len());
+                for c in message.chars() {
+                    match c {
+                        '"' => escaped.push_str("\\\""),
+                        '\\' => escaped.push_str("\\\\"),
+                        c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+                        c => escaped.push(c),
+                    }
+                }
+                format!("{{\"ts_ms\":{},\"error\":\"{}\"}}\n", ts, escaped)
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplayError::Io(e) => write!(f, "Failed to read recording: {}", e),
+            ReplayError::BadHeader => write!(f, "Not a recording of this kind"),
+            ReplayError::UnsupportedVersion(v) => {
+                write!(f, "Unsupported recording version {}", v)
+            }
+            ReplayError::Corrupt { line } => write!(f, "Corrupt recording at line {}", line),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ReplayError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Mpr121TouchStatus, Repeater};
+
+    #[test]
+    fn record_replay() {
+        let ms = Duration::from_millis;
+        let t0 = Instant::now();
+        let mut repeater = Repeater::new(ms(300), ms(100));
+        let held = Mpr121TouchStatus::new(0b1001);
+        let mut events = vec![(ms(0), TouchEvent::Touched { pin: 3 })];
+        for t in (0..600).step_by(10) {
+            events.extend(
+                repeater
+                    .update(&held, t0 + ms(t))
+                    .into_iter()
+                    .map(|e| (ms(t), e)),
+            );
+        }
+        events.push((ms(600), TouchEvent::Released { pin: 3 }));
+
+        let mut recorder = EventRecorder::new(Vec::new(), t0).unwrap();
+        for (offset, event) in events.iter() {
+            recorder.record(t0 + *offset, event).unwrap();
+        }
+        let bytes = recorder.into_inner().unwrap();
+        let replayed: Vec<_> = EventReplayer::new(&bytes[..])
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(replayed, events);
+
+        // Truncated in the middle of the last line
+        let truncated = &bytes[..bytes.len() - 3];
+        let mut replayer = EventReplayer::new(truncated).unwrap();
+        let count = events.len();
+        assert_eq!(replayer.by_ref().take(count - 1).count(), count - 1);
+        match replayer.next() {
+            Some(Err(ReplayError::Corrupt { line })) => assert_eq!(line, count + 1),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(replayer.next().is_none());
+
+        assert!(matches!(
+            EventReplayer::new(&b"mpr121-events 2\n"[..]),
+            Err(ReplayError::UnsupportedVersion(2))
+        ));
+        assert!(matches!(
+            EventReplayer::new(&b"garbage"[..]),
+            Err(ReplayError::BadHeader)
+        ));
+        // Malformed inputs must be rejected without panicking
+        for input in [
+            &b"mpr121-events 1\n5 touched 12\n"[..],
+            b"mpr121-events 1\n99999999999999999999 touched 1\n",
+            b"mpr121-events 1\n5 repeat 1\n",
+            b"mpr121-events 1\n5 repeat 1 2 3\n",
+            b"mpr121-events 1\n-5 touched 1\n",
+            b"mpr121-events 1\n5 touched \xFF\n",
+            b"mpr121-events 1\n\n",
+        ] {
+            let mut replayer = EventReplayer::new(input).unwrap();
+            assert!(replayer.next().unwrap().is_err());
+            assert!(replayer.next().is_none());
+        }
+        assert!(EventReplayer::new(&b"mpr121-events \xFF\n"[..]).is_err());
+        assert!(EventReplayer::new(&b""[..]).is_err());
+    }
+
+    fn log_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mpr121-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn read_lines(path: &Path) -> Vec<String> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect()
+    }
+
+    #[test]
+    fn event_log() {
+        let day = Duration::from_secs(86400);
+        let t0 = SystemTime::UNIX_EPOCH + day * 20000;
+        let dir = log_dir("event-log");
+        let mut config = EventLogConfig::new(&dir);
+        config.max_bytes = 100;
+        config.keep = 2;
+        config.sync_every = Duration::from_secs(0);
+
+        // Size rotation, only `keep` rotated files stay
+        let log = FileEventLog::open(config.clone()).unwrap();
+        for pin in 0..8 {
+            assert!(log.log_event(t0, &TouchEvent::Touched { pin }));
+        }
+        log.close().unwrap();
+        let line = |pin| {
+            format!(
+                "{{\"ts_ms\":1728000000000,\"event\":\"touched\",\"pin\":{}}}",
+                pin
+            )
+        };
+        assert_eq!(read_lines(&config.path(0)), [line(6), line(7)]);
+        assert_eq!(read_lines(&config.path(1)), [line(4), line(5)]);
+        assert_eq!(read_lines(&config.path(2)), [line(2), line(3)]);
+        assert!(!config.path(3).exists());
+
+        // Crash in the middle of a line, then daily rotation
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(config.path(0))
+            .unwrap();
+        file.write_all(b"{\"ts_ms\":17280").unwrap();
+        drop(file);
+        config.max_bytes = 1000;
+        let log = FileEventLog::open(config.clone()).unwrap();
+        log.log_event(t0, &TouchEvent::Repeat { pin: 1, count: 2 });
+        log.log_error(
+            t0 + day,
+            &Mpr121Error::WriteVerifyFailed {
+                reg: 0x5D,
+                expected: 0x24,
+                got: 0x20,
+            },
+        );
+        log.close().unwrap();
+        let lines = read_lines(&config.path(1));
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[..2], [line(6), line(7)]);
+        assert_eq!(
+            lines[2],
+            "{\"ts_ms\":1728000000000,\"event\":\"repeat\",\"pin\":1,\"count\":2}"
+        );
+        let lines = read_lines(&config.path(0));
+        assert_eq!(lines.len(), 1);
+        assert!(lines[0].starts_with("{\"ts_ms\":1728086400000,\"error\":\""));
+
+        // Full queue drops new records without blocking
+        config.queue = 2;
+        let (release, gate) = mpsc::channel();
+        let log = FileEventLog::start(config.clone(), Some(gate)).unwrap();
+        let sent: Vec<_> = (0..5)
+            .map(|pin| log.log_event(t0 + day, &TouchEvent::Released { pin }))
+            .collect();
+        assert_eq!(sent, [true, true, false, false, false]);
+        assert_eq!(log.dropped(), 3);
+        release.send(()).unwrap();
+        log.close().unwrap();
+        let lines = read_lines(&config.path(0));
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[1], "{\"ts_ms\":1728086400000,\"dropped\":3}");
+        assert!(lines[3].ends_with("\"released\",\"pin\":1}"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
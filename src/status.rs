@@ -0,0 +1,756 @@
+//! Touch status and per electrode data decoding
+
+use crate::Mpr121;
+
+/// Touch status for all pins.
+///
+/// Proximity detection (status bit 12) is exposed only via `proximity` and
+/// never as a 13th pin: iterators, `touched` and `was_touched` cover
+/// electrodes 0 to 11 only. When proximity detection is not enabled on the
+/// chip, bit 12 is masked out and `Display` omits the proximity field.
+#[derive(Debug)]
+pub struct Mpr121TouchStatus {
+    status: u16,
+    proximity_enabled: bool,
+}
+
+/// Non-electrode bits of the touch status register. Bits 13 and 14 are
+/// reserved and always read as zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct StatusFlags {
+    /// Bit 12, proximity electrode detected an approach (false when
+    /// proximity detection is disabled)
+    pub proximity: bool,
+    /// Bit 15 (OVCF), over current detected on REXT pin, electrodes and
+    /// GPIOs are stopped until the flag is cleared
+    pub over_current: bool,
+}
+
+#[cfg(feature = "bitflags")]
+bitflags::bitflags! {
+    /// Touched electrodes as flags, see `Mpr121TouchStatus::as_flags`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub struct Electrodes: u16 {
+        const E0 = 1 << 0;
+        const E1 = 1 << 1;
+        const E2 = 1 << 2;
+        const E3 = 1 << 3;
+        const E4 = 1 << 4;
+        const E5 = 1 << 5;
+        const E6 = 1 << 6;
+        const E7 = 1 << 7;
+        const E8 = 1 << 8;
+        const E9 = 1 << 9;
+        const E10 = 1 << 10;
+        const E11 = 1 << 11;
+    }
+}
+
+/// Convenient iterator for pins in `Mpr121TouchStatus`
+pub struct Mpr121TouchStatusIterator<'a> {
+    status: &'a Mpr121TouchStatus,
+    count: u8,
+}
+
+/// Electrodes combined into proximity detection electrode, from ELEPROX_EN
+/// field of electrode configuration register
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum ProximityMode {
+    /// Proximity detection is disabled
+    Disabled,
+    /// Electrodes 0 and 1 are used for proximity detection
+    Electrodes0To1,
+    /// Electrodes 0 to 3 are used for proximity detection
+    Electrodes0To3,
+    /// All electrodes are used for proximity detection
+    Electrodes0To11,
+}
+
+/// Touch status, filtered data and baseline of all electrodes read at once
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SensorFrame {
+    /// Raw touch status register
+    pub status: u16,
+    /// 10 bit filtered data of each electrode
+    pub filtered: [u16; 12],
+    /// Baseline of each electrode, scaled to the 10 bit range of filtered data
+    pub baseline: [u16; 12],
+}
+
+/// Caller owned result of `Mpr121::poll_into`, reused across polls. Plain
+/// data with C layout, so it can be shared with C code as is.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct PollOutput {
+    /// Touch status register, electrode bits corrected for inverted pins
+    pub status: u16,
+    /// Input: whether `deltas` should be read too
+    pub with_deltas: bool,
+    /// Baseline minus filtered data of each electrode, only written when
+    /// `with_deltas` is set
+    pub deltas: [i16; 12],
+}
+
+/// Everything known about a single electrode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ElectrodeInfo {
+    /// Electrode number, 0 to 11
+    pub pin: u8,
+    /// Electrode is enabled for touch sensing
+    pub enabled: bool,
+    /// Electrode is currently touched
+    pub touched: bool,
+    /// Filtered data
+    pub filtered: u16,
+    /// Baseline value
+    pub baseline: u16,
+    /// Baseline minus filtered data, grows when touched
+    pub delta: i32,
+    /// Configured touch threshold
+    pub touch_threshold: u8,
+    /// Configured release threshold
+    pub release_threshold: u8,
+}
+
+impl Mpr121TouchStatus {
+    const PROXIMITY_BIT: u16 = 1 << 12;
+
+    /// Creates touch status from the low status byte (see
+    /// `Mpr121::touch_status_low`). Electrodes 8 to 11 are unknown and
+    /// reported as not touched, proximity as disabled.
+    pub fn from_low_byte(low: u8) -> Self {
+        Mpr121TouchStatus::new(u16::from(low))
+    }
+
+    /// Creates new touch status with proximity detection disabled
+    pub(crate) fn new(touch_status: u16) -> Self {
+        Mpr121TouchStatus::with_proximity(touch_status, false)
+    }
+
+    /// Creates new touch status, proximity bit is kept only if `proximity`
+    /// detection is enabled
+    pub(crate) fn with_proximity(touch_status: u16, proximity: bool) -> Self {
+        Self {
+            status: if proximity {
+                touch_status
+            } else {
+                touch_status & !Mpr121TouchStatus::PROXIMITY_BIT
+            },
+            proximity_enabled: proximity,
+        }
+    }
+
+    /// Returns if specific pin was touched
+    pub fn touched(&self, item: u8) -> bool {
+        if item <= Mpr121TouchStatus::last() {
+            return self.status >> item & 0x1 != 0;
+        }
+
+        false
+    }
+
+    /// Returns if proximity electrode detected an approach. Always false when
+    /// proximity detection is not enabled.
+    pub fn proximity(&self) -> bool {
+        self.status & Mpr121TouchStatus::PROXIMITY_BIT != 0
+    }
+
+    /// Same as `proximity`
+    pub fn proximity_touched(&self) -> bool {
+        self.proximity()
+    }
+
+    /// Returns if proximity electrode detected an approach while no electrode
+    /// is touched, i.e. hand is hovering near the pads
+    pub fn proximity_only(&self) -> bool {
+        self.proximity() && !self.was_touched()
+    }
+
+    /// Number of touched electrodes 0 to 11
+    pub fn touched_count(&self) -> u32 {
+        (self.status & 0x0FFF).count_ones()
+    }
+
+    /// Number of touched electrodes, counting proximity detection as one
+    /// more electrode. Over-current flag is not counted.
+    pub fn count_with_proximity(&self) -> u32 {
+        (self.status & (0x0FFF | Mpr121TouchStatus::PROXIMITY_BIT)).count_ones()
+    }
+
+    /// Returns touched electrodes 0 to 11 as flags, proximity and
+    /// over-current bits are left out
+    #[cfg(feature = "bitflags")]
+    pub fn as_flags(&self) -> Electrodes {
+        Electrodes::from_bits_truncate(self.status)
+    }
+
+    /// Returns decoded non-electrode bits of the status register
+    pub fn flags(&self) -> StatusFlags {
+        StatusFlags {
+            proximity: self.proximity(),
+            over_current: self.status >> 15 & 0x1 != 0,
+        }
+    }
+
+    /// Returns if proximity detection was enabled when status was read
+    pub fn proximity_enabled(&self) -> bool {
+        self.proximity_enabled
+    }
+
+    /// Renders electrodes as a grid of `[#]` (touched) and `[ ]` cells, 3 rows
+    /// of 4 electrodes with electrode 0 at top left, see `ascii_art_grid`
+    pub fn ascii_art(&self) -> String {
+        self.ascii_art_grid(4)
+    }
+
+    /// Same as `ascii_art` with `columns` electrodes per row, 12 columns give
+    /// a single row
+    pub fn ascii_art_grid(&self, columns: usize) -> String {
+        let columns = columns.max(1);
+        let mut art = String::new();
+        for (i, touched) in self.iter().enumerate() {
+            art.push_str(if touched { "[#]" } else { "[ ]" });
+            if (i + 1) % columns == 0 || i == Mpr121TouchStatus::last() as usize {
+                art.push('\n');
+            }
+        }
+        art
+    }
+
+    /// Returns keys of `map` whose electrode is touched, in order of `map`
+    pub fn decode<K: Copy>(&self, map: &[(u8, K)]) -> Vec<K> {
+        map.iter()
+            .filter(|(electrode, _)| self.touched(*electrode))
+            .map(|(_, key)| *key)
+            .collect()
+    }
+
+    /// Decodes keypad matrix where each key is an intersection of a row and
+    /// a column electrode. Returns (row, column) indices into `rows` and
+    /// `cols` when exactly one row and exactly one column are touched.
+    pub fn decode_matrix(&self, rows: &[u8], cols: &[u8]) -> Option<(usize, usize)> {
+        let single = |electrodes: &[u8]| {
+            let mut touched = electrodes
+                .iter()
+                .enumerate()
+                .filter(|(_, e)| self.touched(**e));
+            match (touched.next(), touched.next()) {
+                (Some((i, _)), None) => Some(i),
+                _ => None,
+            }
+        };
+        Some((single(rows)?, single(cols)?))
+    }
+
+    /// Returns if *any* pin was touched, proximity is not included
+    pub fn was_touched(&self) -> bool {
+        self.status & 0x0FFF != 0
+    }
+
+    /// Number of the first pin
+    pub fn first() -> u8 {
+        0
+    }
+
+    /// Number of the last pin
+    pub fn last() -> u8 {
+        11
+    }
+
+    pub fn iter(&self) -> Mpr121TouchStatusIterator<'_> {
+        Mpr121TouchStatusIterator::new(self)
+    }
+}
+
+/// Touched state of electrode, panics for electrodes outside 0 to 11 like
+/// slice indexing (unlike `touched`, which returns false)
+impl std::ops::Index<u8> for Mpr121TouchStatus {
+    type Output = bool;
+
+    fn index(&self, electrode: u8) -> &bool {
+        assert!(
+            electrode <= Mpr121TouchStatus::last(),
+            "electrode {} out of range 0..=11",
+            electrode
+        );
+        if self.touched(electrode) {
+            &true
+        } else {
+            &false
+        }
+    }
+}
+
+/// Compact form: mask of touched electrodes, followed by proximity state if
+/// proximity detection is enabled
+#[cfg(feature = "defmt")]
+impl defmt::Format for Mpr121TouchStatus {
+    fn format(&self, f: defmt::Formatter<'_>) {
+        if self.proximity_enabled {
+            defmt::write!(
+                f,
+                "touched={=u16:012b} prox={=bool}",
+                self.status & 0x0FFF,
+                self.proximity()
+            );
+        } else {
+            defmt::write!(f, "touched={=u16:012b}", self.status & 0x0FFF);
+        }
+    }
+}
+
+/// Lists state of every electrode. Width, fill and alignment work as for
+/// strings, precision truncates the text to that many characters.
+impl std::fmt::Display for Mpr121TouchStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let on_off = |on: bool| if on { "on" } else { "off" };
+        let mut text = String::from("Touch status: [");
+        for (i, touched) in self.iter().enumerate() {
+            if i > 0 {
+                text.push_str(", ");
+            }
+            text.push_str(&format!("{} is {}", i, on_off(touched)));
+        }
+        if self.proximity_enabled {
+            text.push_str(&format!(", prox is {}", on_off(self.proximity())));
+        }
+        text.push(']');
+        f.pad(&text)
+    }
+}
+
+impl<'a> Mpr121TouchStatusIterator<'a> {
+    fn new(status: &'a Mpr121TouchStatus) -> Self {
+        Self { status, count: 0 }
+    }
+}
+
+impl<'a> Iterator for Mpr121TouchStatusIterator<'a> {
+    type Item = bool;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.count += 1;
+
+        if self.count <= 12 {
+            Some(self.status.touched(self.count - 1))
+        } else {
+            None
+        }
+    }
+}
+
+impl ProximityMode {
+    /// Decodes mode from value of electrode configuration register
+    pub fn from_ecr(ecr: u8) -> Self {
+        match ecr >> 4 & 0x03 {
+            0 => ProximityMode::Disabled,
+            1 => ProximityMode::Electrodes0To1,
+            2 => ProximityMode::Electrodes0To3,
+            _ => ProximityMode::Electrodes0To11,
+        }
+    }
+}
+
+impl SensorFrame {
+    /// Decodes registers starting at touch status (at least 28 bytes) and
+    /// baseline registers (at least 12 bytes)
+    pub(crate) fn from_registers(data: &[u8], baseline: &[u8]) -> Self {
+        let filtered = &data[(Mpr121::REG_FILTDATA_0L - Mpr121::REG_TOUCHSTATUS_L) as usize..];
+        let mut frame = SensorFrame {
+            status: u16::from(data[1]) << 8 | u16::from(data[0]),
+            filtered: Mpr121::decode_filtered(filtered),
+            baseline: [0; 12],
+        };
+        // Baseline registers hold only 8 most significant bits of 10 bit value
+        for (b, d) in frame.baseline.iter_mut().zip(baseline) {
+            *b = u16::from(*d) << 2;
+        }
+        frame
+    }
+
+    /// Touch status of this frame
+    pub fn touch_status(&self) -> Mpr121TouchStatus {
+        Mpr121TouchStatus::new(self.status)
+    }
+
+    /// Difference between baseline and filtered data of given electrode
+    pub fn delta(&self, electrode: u8) -> i32 {
+        let i = electrode as usize;
+        i32::from(self.baseline[i]) - i32::from(self.filtered[i])
+    }
+}
+
+impl PollOutput {
+    /// Creates output for polls reading touch status, and deltas if
+    /// `with_deltas`
+    pub fn new(with_deltas: bool) -> Self {
+        Self {
+            with_deltas,
+            ..Self::default()
+        }
+    }
+}
+
+impl ElectrodeInfo {
+    /// Assembles per electrode information from a frame, thresholds of all
+    /// electrodes and value of electrode configuration register
+    pub fn from_frame(frame: &SensorFrame, thresholds: &[(u8, u8); 12], ecr: u8) -> Vec<Self> {
+        // ELE_EN bits enable electrodes 0 to n-1, values above 12 enable all
+        let enabled = ecr & 0x0F;
+        let status = frame.touch_status();
+        (Mpr121TouchStatus::first()..=Mpr121TouchStatus::last())
+            .map(|pin| {
+                let i = pin as usize;
+                ElectrodeInfo {
+                    pin,
+                    enabled: pin < enabled,
+                    touched: status.touched(pin),
+                    filtered: frame.filtered[i],
+                    baseline: frame.baseline[i],
+                    delta: frame.delta(pin),
+                    touch_threshold: thresholds[i].0,
+                    release_threshold: thresholds[i].1,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns enabled electrode with the largest delta above its touch
+    /// threshold, lower electrode wins a tie
+    pub fn dominant(electrodes: &[ElectrodeInfo]) -> Option<u8> {
+        electrodes
+            .iter()
+            .filter(|e| e.enabled && e.delta > i32::from(e.touch_threshold))
+            .fold(None, |best: Option<&ElectrodeInfo>, e| match best {
+                Some(b) if b.delta >= e.delta => Some(b),
+                _ => Some(e),
+            })
+            .map(|e| e.pin)
+    }
+}
+
+impl ElectrodeInfo {
+    /// Formats information about electrodes as a table
+    pub fn report(electrodes: &[ElectrodeInfo]) -> String {
+        let mut report =
+            String::from("pin enabled touched filtered baseline delta touch_th release_th\n");
+        for e in electrodes {
+            report.push_str(&format!(
+                "{:>3} {:>7} {:>7} {:>8} {:>8} {:>5} {:>8} {:>10}\n",
+                e.pin,
+                if e.enabled { "yes" } else { "no" },
+                if e.touched { "yes" } else { "no" },
+                e.filtered,
+                e.baseline,
+                e.delta,
+                e.touch_threshold,
+                e.release_threshold
+            ));
+        }
+        report
+    }
+}
+
+impl std::fmt::Display for ElectrodeInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Electrode {}: {}, {}, filtered {}, baseline {}, delta {}, thresholds {}/{}",
+            self.pin,
+            if self.enabled { "enabled" } else { "disabled" },
+            if self.touched { "touched" } else { "released" },
+            self.filtered,
+            self.baseline,
+            self.delta,
+            self.touch_threshold,
+            self.release_threshold
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "bitflags")]
+    #[test]
+    fn electrode_flags() {
+        let status = Mpr121TouchStatus::with_proximity(0b1001_0000_0000_0101, true);
+        let flags = status.as_flags();
+        assert_eq!(flags, Electrodes::E0 | Electrodes::E2);
+        assert!(flags.contains(Electrodes::E2));
+        assert!(flags.intersects(Electrodes::E2 | Electrodes::E11));
+        assert!(!flags.contains(Electrodes::E2 | Electrodes::E11));
+        for i in 0..12 {
+            let bit = Electrodes::from_bits_truncate(1 << i);
+            assert_eq!(flags.contains(bit), status.touched(i));
+        }
+        assert!(Mpr121TouchStatus::new(0).as_flags().is_empty());
+        assert_eq!(Mpr121TouchStatus::new(0xFFFF).as_flags(), Electrodes::all());
+    }
+
+    #[test]
+    fn index() {
+        let ts = Mpr121TouchStatus::new(0b1000_0000_0010);
+        assert!(!ts[0]);
+        assert!(ts[1]);
+        assert!(ts[11]);
+        assert!(std::panic::catch_unwind(|| ts[12]).is_err());
+    }
+
+    #[test]
+    fn display_padding() {
+        let ts = Mpr121TouchStatus::new(0b1);
+        let text = ts.to_string();
+        let width = text.len() + 4;
+        assert_eq!(
+            format!("{:>width$}", ts, width = width),
+            format!("    {}", text)
+        );
+        assert_eq!(
+            format!("{:^width$}", ts, width = width),
+            format!("  {}  ", text)
+        );
+        assert_eq!(
+            format!("{:*<width$}", ts, width = width),
+            format!("{}****", text)
+        );
+        assert_eq!(format!("{:.20}", ts), "Touch status: [0 is ");
+        assert_eq!(format!("{:>10}", ts), text);
+    }
+
+    #[test]
+    fn touch_status() {
+        assert_eq!(Mpr121TouchStatus::first(), 0);
+        assert_eq!(Mpr121TouchStatus::last(), 11);
+        {
+            let ts = Mpr121TouchStatus::new(0b101010101010);
+            let mut tsi = ts.iter();
+            for i in Mpr121TouchStatus::first()..=Mpr121TouchStatus::last() {
+                let v = tsi.next();
+                assert!(v.is_some());
+                let v = v.unwrap();
+                if i % 2 == 0 {
+                    assert!(!ts.touched(i));
+                    assert!(!v);
+                } else {
+                    assert!(ts.touched(i));
+                    assert!(v);
+                }
+            }
+            assert!(tsi.next().is_none());
+        }
+        {
+            let ts = Mpr121TouchStatus::new(0b010101010101);
+            let mut tsi = ts.iter();
+            for i in Mpr121TouchStatus::first()..=Mpr121TouchStatus::last() {
+                let v = tsi.next();
+                assert!(v.is_some());
+                let v = v.unwrap();
+                if i % 2 == 1 {
+                    assert!(!ts.touched(i));
+                    assert!(!v);
+                } else {
+                    assert!(ts.touched(i));
+                    assert!(v);
+                }
+            }
+            assert!(tsi.next().is_none());
+        }
+    }
+
+    #[test]
+    fn electrode_info() {
+        let mut data = [0u8; 28];
+        data[0] = 0b0000_0100;
+        data[1] = 0b0000_1000;
+        // Filtered data of electrode 2 and 11, extra bits are ignored
+        data[8] = 0x2C;
+        data[9] = 0xFD;
+        data[26] = 0x00;
+        data[27] = 0x02;
+        let mut baseline = [0u8; 12];
+        baseline[2] = 0x4E;
+        baseline[11] = 0x90;
+        let frame = SensorFrame::from_registers(&data, &baseline);
+        assert_eq!(frame.filtered[2], 0x12C);
+        assert_eq!(frame.filtered[11], 0x200);
+        assert_eq!(frame.baseline[2], 0x138);
+        assert_eq!(frame.delta(2), 12);
+        assert_eq!(frame.delta(11), 64);
+
+        let mut thresholds = [(12, 6); 12];
+        thresholds[11] = (40, 20);
+        let info = ElectrodeInfo::from_frame(&frame, &thresholds, 0x8A);
+        assert_eq!(info.len(), 12);
+        assert!(info[2].touched);
+        assert!(info[2].enabled);
+        assert_eq!(info[2].delta, 12);
+        assert!(info[11].touched);
+        assert!(!info[11].enabled);
+        assert_eq!(info[11].touch_threshold, 40);
+        assert_eq!(info[11].release_threshold, 20);
+        assert!(!info[0].touched);
+        assert_eq!(
+            info[2].to_string(),
+            "Electrode 2: enabled, touched, filtered 300, baseline 312, delta 12, thresholds 12/6"
+        );
+        let report = ElectrodeInfo::report(&info);
+        let lines: Vec<_> = report.lines().collect();
+        assert_eq!(lines.len(), 13);
+        assert_eq!(
+            lines[0],
+            "pin enabled touched filtered baseline delta touch_th release_th"
+        );
+        assert_eq!(
+            lines[3],
+            "  2     yes     yes      300      312    12       12          6"
+        );
+        assert!(ElectrodeInfo::from_frame(&frame, &thresholds, 0x0F)
+            .iter()
+            .all(|i| i.enabled));
+        // Delta equal to threshold is not enough, electrode 11 is disabled
+        assert_eq!(ElectrodeInfo::dominant(&info), None);
+        let all = ElectrodeInfo::from_frame(&frame, &[(10, 5); 12], 0x0F);
+        assert_eq!(ElectrodeInfo::dominant(&all), Some(11));
+        let mut tie = all.clone();
+        tie[11].delta = 12;
+        assert_eq!(ElectrodeInfo::dominant(&tie), Some(2));
+    }
+
+    #[test]
+    fn keypad_matrix() {
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        enum Key {
+            Play,
+            Pause,
+            Next,
+        }
+        let map = [
+            (0, Key::Play),
+            (3, Key::Pause),
+            (11, Key::Next),
+            (12, Key::Play),
+        ];
+        assert_eq!(
+            Mpr121TouchStatus::new(0b1000_0000_1001).decode(&map),
+            vec![Key::Play, Key::Pause, Key::Next]
+        );
+        assert!(Mpr121TouchStatus::new(0b0110).decode(&map).is_empty());
+
+        let ts = Mpr121TouchStatus::new(0b1000_0010_0001);
+        assert_eq!(ts.ascii_art(), "[#][ ][ ][ ]\n[ ][#][ ][ ]\n[ ][ ][ ][#]\n");
+        assert_eq!(ts.ascii_art_grid(5).lines().count(), 3);
+        assert_eq!(ts.ascii_art_grid(0).lines().count(), 12);
+        assert_eq!(ts.ascii_art_grid(12).matches('#').count(), 3);
+
+        let rows = [0, 1, 2];
+        let cols = [4, 5, 6, 7];
+        assert_eq!(Mpr121TouchStatus::new(0).decode_matrix(&rows, &cols), None);
+        assert_eq!(
+            Mpr121TouchStatus::new(0b0100_0010).decode_matrix(&rows, &cols),
+            Some((1, 2))
+        );
+        assert_eq!(
+            Mpr121TouchStatus::new(0b1000_0100).decode_matrix(&rows, &cols),
+            Some((2, 3))
+        );
+        // Only a row, two rows, two columns
+        assert_eq!(
+            Mpr121TouchStatus::new(0b0001).decode_matrix(&rows, &cols),
+            None
+        );
+        assert_eq!(
+            Mpr121TouchStatus::new(0b0001_0011).decode_matrix(&rows, &cols),
+            None
+        );
+        assert_eq!(
+            Mpr121TouchStatus::new(0b0011_0001).decode_matrix(&rows, &cols),
+            None
+        );
+        // Electrodes outside of the matrix are ignored
+        assert_eq!(
+            Mpr121TouchStatus::new(0b1000_0001_0001).decode_matrix(&rows, &cols),
+            Some((0, 0))
+        );
+    }
+
+    #[cfg(feature = "defmt")]
+    #[test]
+    fn defmt_format() {
+        fn assert_format<T: defmt::Format>() {}
+        assert_format::<Mpr121TouchStatus>();
+        assert_format::<StatusFlags>();
+        assert_format::<SensorFrame>();
+        assert_format::<ElectrodeInfo>();
+        assert_format::<crate::Mpr121Error>();
+        assert_format::<crate::TouchEvent>();
+        assert_format::<crate::Tap>();
+        assert_format::<crate::ConfigDrift>();
+        assert_format::<crate::ConfigWarning>();
+        assert_format::<crate::TuningProfile>();
+        assert_format::<crate::GpioState>();
+    }
+
+    #[test]
+    fn proximity_bit() {
+        let raw = 0b1_0000_0000_0100;
+        let ts = Mpr121TouchStatus::new(raw);
+        assert!(!ts.proximity());
+        assert!(!ts.proximity_enabled());
+        assert!(ts.touched(2));
+        assert!(!ts.touched(12));
+        assert_eq!(ts.iter().count(), 12);
+        assert!(!ts.to_string().contains("prox"));
+        assert!(!Mpr121TouchStatus::new(0x1000).was_touched());
+
+        let ts = Mpr121TouchStatus::with_proximity(raw, true);
+        assert!(ts.proximity());
+        assert!(ts.proximity_touched());
+        assert!(!ts.touched(12));
+        assert_eq!(ts.iter().count(), 12);
+        assert!(ts.to_string().ends_with("11 is off, prox is on]"));
+        assert!(!ts.proximity_only());
+        let ts = Mpr121TouchStatus::with_proximity(0x1000, true);
+        assert!(!ts.was_touched());
+        assert!(ts.proximity_only());
+        assert!(!Mpr121TouchStatus::new(0x1000).proximity_only());
+        let ts = Mpr121TouchStatus::with_proximity(0, true);
+        assert!(ts.to_string().ends_with(", prox is off]"));
+
+        let ts = Mpr121TouchStatus::with_proximity(0x9801, true);
+        assert_eq!(ts.touched_count(), 2);
+        assert_eq!(ts.count_with_proximity(), 3);
+        let ts = Mpr121TouchStatus::new(0x9801);
+        assert_eq!(ts.touched_count(), 2);
+        assert_eq!(ts.count_with_proximity(), 2);
+
+        let flags = Mpr121TouchStatus::with_proximity(0x9001, true).flags();
+        assert!(flags.proximity);
+        assert!(flags.over_current);
+        let flags = Mpr121TouchStatus::new(0x1FFF).flags();
+        assert!(!flags.proximity);
+        assert!(!flags.over_current);
+
+        let ts = Mpr121TouchStatus::from_low_byte(0b1000_0001);
+        assert!(ts.touched(0));
+        assert!(ts.touched(7));
+        assert_eq!(ts.iter().filter(|t| *t).count(), 2);
+        assert!(!ts.proximity_enabled());
+
+        assert_eq!(ProximityMode::from_ecr(0x8F), ProximityMode::Disabled);
+        assert_eq!(ProximityMode::from_ecr(0x10), ProximityMode::Electrodes0To1);
+        assert_eq!(ProximityMode::from_ecr(0xAC), ProximityMode::Electrodes0To3);
+        assert_eq!(
+            ProximityMode::from_ecr(0xB0),
+            ProximityMode::Electrodes0To11
+        );
+    }
+}